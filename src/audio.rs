@@ -1,28 +1,96 @@
+use crate::error::AppError;
+use crate::profiles::{self, DeviceProfile};
+use crate::vad::Vad;
 use anyhow::{Context, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{SampleFormat, SupportedStreamConfig};
 use std::fs::File;
-use std::io::BufWriter;
-use std::path::PathBuf;
+use std::io::{BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const DEFAULT_INPUT_SAMPLE_RATE: u32 = 48000;
-const TARGET_OUTPUT_SAMPLE_RATE: u32 = 16000;
+/// Rate of the samples handed to the engine
+pub const TARGET_OUTPUT_SAMPLE_RATE: u32 = 16000;
+const SPILL_FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+/// With the energy VAD, frames quieter than this RMS level (about -40 dBFS) count as silence, or
+/// twice the noise floor of a calibrated device if that is higher
+const SILENCE_RMS: f32 = 0.01;
+/// Silence kept around speech when trimming, so word onsets and endings survive
+const TRIM_PADDING: Duration = Duration::from_millis(200);
+/// Sample rates outside this range are taken for a corrupt header rather than
+/// resampled, which could take unbounded memory
+const DECODE_SAMPLE_RATES: std::ops::RangeInclusive<u32> = 1000..=384_000;
+/// A file with more undecodable packets than this, if they are also most of its
+/// packets, is rejected as corrupt rather than damaged in places
+const MAX_BAD_PACKETS: usize = 10;
+/// Room in the sample buffer beyond `--max-duration`, for the audio captured
+/// while the recording is being stopped
+const BUFFER_MARGIN: Duration = Duration::from_secs(2);
 
 pub struct AudioRecorder {
     samples: Arc<Mutex<Vec<f32>>>,
+    /// Most samples `samples` may hold; audio beyond it is dropped
+    max_len: Arc<AtomicUsize>,
+    /// `--max-duration`, which `max_len` is worked out from
+    max_duration: Option<Duration>,
     stream: Option<cpal::Stream>,
+    fifo: Option<FifoSource>,
+    /// `--audio-device` selector; the system default when unset
+    device: Option<String>,
+    spill_dir: Option<PathBuf>,
+    spill: Option<SpillWriter>,
+    /// `--save-audio`: keep a WAV of everything handed to the engine here
+    save_dir: Option<PathBuf>,
+    /// Cut leading and trailing silence before transcription
+    trim_silence: bool,
+    /// Voice activity detector for trimming and `--auto-stop`
+    vad: Arc<Vad>,
+    /// Energy VAD threshold for the current device
+    silence_rms: f32,
+    finished_spills: Vec<PathBuf>,
     input_sample_rate: u32,
     output_sample_rate: u32,
+    /// Name of the device or pipe of the last recording
+    input_name: String,
+}
+
+/// Background thread that mirrors the sample buffer into a WAV file while
+/// recording, so the audio survives a crash.
+struct SpillWriter {
+    path: PathBuf,
+    done: Arc<AtomicBool>,
+    handle: JoinHandle<Result<()>>,
+}
+
+/// Raw PCM capture from a named pipe, fed by an external program.
+struct FifoSource {
+    sample_rate: u32,
+    recording: Arc<AtomicBool>,
 }
 
 impl Default for AudioRecorder {
     fn default() -> Self {
         Self {
             samples: Arc::new(Mutex::new(Vec::new())),
+            max_len: Arc::new(AtomicUsize::new(usize::MAX)),
+            max_duration: None,
             stream: None,
+            fifo: None,
+            device: None,
+            spill_dir: None,
+            spill: None,
+            save_dir: None,
+            trim_silence: false,
+            vad: Arc::new(Vad::Energy),
+            silence_rms: SILENCE_RMS,
+            finished_spills: Vec::new(),
             input_sample_rate: DEFAULT_INPUT_SAMPLE_RATE,
             output_sample_rate: TARGET_OUTPUT_SAMPLE_RATE,
+            input_name: String::new(),
         }
     }
 }
@@ -32,13 +100,124 @@ impl AudioRecorder {
         Self::default()
     }
 
+    /// Capture signed 16-bit little-endian mono PCM from a named pipe instead of
+    /// an audio device. The pipe is drained continuously so writers never block;
+    /// samples are only kept while recording.
+    pub fn with_fifo(path: PathBuf, sample_rate: u32) -> Self {
+        let recorder = Self {
+            input_name: format!("fifo:{}", path.display()),
+            ..Self::default()
+        };
+        let recording = Arc::new(AtomicBool::new(false));
+        spawn_fifo_reader(
+            path,
+            Arc::clone(&recorder.samples),
+            Arc::clone(&recorder.max_len),
+            Arc::clone(&recording),
+        );
+        Self {
+            fifo: Some(FifoSource {
+                sample_rate,
+                recording,
+            }),
+            ..recorder
+        }
+    }
+
+    /// Record from the input device matching `selector` (see [`input_device`])
+    /// instead of the system default.
+    pub fn with_device(self, selector: String) -> Self {
+        Self {
+            device: Some(selector),
+            ..self
+        }
+    }
+
+    /// Stream captured audio to WAV files in `dir` while recording. Files are
+    /// removed by [`AudioRecorder::finish`] once their audio has been handled;
+    /// anything left behind after a crash can be transcribed with `recover`.
+    pub fn spill_to(self, dir: PathBuf) -> Self {
+        Self {
+            spill_dir: Some(dir),
+            ..self
+        }
+    }
+
+    /// Also write the audio of each transcription to a WAV file in `dir`, for
+    /// debugging. Recordings are otherwise transcribed straight from memory.
+    pub fn save_audio_to(self, dir: PathBuf) -> Self {
+        Self {
+            save_dir: Some(dir),
+            ..self
+        }
+    }
+
+    /// Keep at most about `max_duration` of audio in memory per recording (or
+    /// chunk of one), however long the recording runs.
+    pub fn max_duration(self, max_duration: Duration) -> Self {
+        Self {
+            max_duration: Some(max_duration),
+            ..self
+        }
+    }
+
+    /// Drop leading and trailing silence from recordings, so the engine has less
+    /// audio to get through.
+    pub fn trim_silence(self) -> Self {
+        Self {
+            trim_silence: true,
+            ..self
+        }
+    }
+
+    /// Record the following recordings from the input device matching
+    /// `selector`. Returns the device's name.
+    pub fn set_device(&mut self, selector: String) -> Result<String> {
+        if self.fifo.is_some() {
+            anyhow::bail!("Audio is read from a FIFO, not an input device");
+        }
+        let name = input_device(Some(&selector))?.name()?;
+        self.device = Some(selector);
+        Ok(name)
+    }
+
+    /// The `--audio-device` selector currently recorded from, if any.
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    /// Detect voice activity with `vad` instead of by loudness.
+    pub fn with_vad(self, vad: Vad) -> Self {
+        Self {
+            vad: Arc::new(vad),
+            ..self
+        }
+    }
+
+    pub fn input_name(&self) -> &str {
+        &self.input_name
+    }
+
     pub fn start(&mut self) -> Result<()> {
-        let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .context("No input device available")?;
+        if let Some(fifo) = &self.fifo {
+            self.input_sample_rate = fifo.sample_rate;
+            self.max_len.store(self.buffer_limit(), Ordering::SeqCst);
+            self.samples.lock().unwrap().clear();
+            fifo.recording.store(true, Ordering::SeqCst);
+            return self.begin_spill();
+        }
 
-        log::debug!("Using input device: {}", device.name()?);
+        // Looked up on every start so a device plugged in (or back in) after
+        // launch is picked up
+        let device = input_device(self.device.as_deref())?;
+
+        self.input_name = device.name()?;
+        log::debug!("Using input device: {}", self.input_name);
+        let profile = profiles::get(&self.input_name);
+        if let Some(profile) = profile {
+            log::debug!("Applying calibration: {:?}", profile);
+        }
+        self.silence_rms = profile.map_or(SILENCE_RMS, |p| (p.noise_floor * 2.0).max(SILENCE_RMS));
 
         let default_config = device
             .default_input_config()
@@ -52,14 +231,15 @@ impl AudioRecorder {
             default_config.sample_format()
         );
 
+        self.max_len.store(self.buffer_limit(), Ordering::SeqCst);
         self.samples.lock().unwrap().clear();
         let samples = Arc::clone(&self.samples);
 
-        let stream = self.build_stream(&device, &default_config, samples)?;
+        let stream = self.build_stream(&device, &default_config, samples, profile)?;
 
         stream.play()?;
         self.stream = Some(stream);
-        Ok(())
+        self.begin_spill()
     }
 
     fn build_stream(
@@ -67,16 +247,20 @@ impl AudioRecorder {
         device: &cpal::Device,
         config: &SupportedStreamConfig,
         samples: Arc<Mutex<Vec<f32>>>,
+        profile: Option<DeviceProfile>,
     ) -> Result<cpal::Stream> {
         let channels = config.channels() as usize;
         let stream_config = config.config();
+        let max_len = Arc::clone(&self.max_len);
 
         let err_fn = |err| log::error!("Audio stream error: {}", err);
 
         let stream = match config.sample_format() {
             SampleFormat::F32 => device.build_input_stream(
                 &stream_config,
-                move |data: &[f32], _| Self::write_samples(&samples, data, channels),
+                move |data: &[f32], _| {
+                    Self::write_samples(&samples, data, channels, profile, &max_len)
+                },
                 err_fn,
                 None,
             )?,
@@ -85,7 +269,7 @@ impl AudioRecorder {
                 move |data: &[i16], _| {
                     let float_data: Vec<f32> =
                         data.iter().map(|&s| s as f32 / i16::MAX as f32).collect();
-                    Self::write_samples(&samples, &float_data, channels);
+                    Self::write_samples(&samples, &float_data, channels, profile, &max_len);
                 },
                 err_fn,
                 None,
@@ -95,7 +279,7 @@ impl AudioRecorder {
                 move |data: &[i32], _| {
                     let float_data: Vec<f32> =
                         data.iter().map(|&s| s as f32 / i32::MAX as f32).collect();
-                    Self::write_samples(&samples, &float_data, channels);
+                    Self::write_samples(&samples, &float_data, channels, profile, &max_len);
                 },
                 err_fn,
                 None,
@@ -106,30 +290,195 @@ impl AudioRecorder {
         Ok(stream)
     }
 
-    fn write_samples(samples: &Arc<Mutex<Vec<f32>>>, data: &[f32], channels: usize) {
+    /// Mix `data` down to mono, or pick the profile's channel, and apply its
+    /// gain. Once `samples` holds `max_len` samples, the rest is dropped.
+    fn write_samples(
+        samples: &Arc<Mutex<Vec<f32>>>,
+        data: &[f32],
+        channels: usize,
+        profile: Option<DeviceProfile>,
+        max_len: &AtomicUsize,
+    ) {
         let mut samples = samples.lock().unwrap();
-        if channels == 1 {
-            samples.extend_from_slice(data);
-        } else {
-            for chunk in data.chunks(channels) {
-                let mono: f32 = chunk.iter().sum::<f32>() / channels as f32;
-                samples.push(mono);
+        let max_len = max_len.load(Ordering::Relaxed);
+        if samples.len() >= max_len {
+            return;
+        }
+        match profile {
+            None if channels == 1 => samples.extend_from_slice(data),
+            None => {
+                for chunk in data.chunks(channels) {
+                    let mono: f32 = chunk.iter().sum::<f32>() / channels as f32;
+                    samples.push(mono);
+                }
+            }
+            Some(profile) => {
+                for chunk in data.chunks(channels) {
+                    let mono = match profile.channel.and_then(|channel| chunk.get(channel)) {
+                        Some(&sample) => sample,
+                        None => chunk.iter().sum::<f32>() / chunk.len() as f32,
+                    };
+                    samples.push((mono * profile.gain).clamp(-1.0, 1.0));
+                }
             }
         }
+        if samples.len() >= max_len {
+            samples.truncate(max_len);
+            log::warn!("Recording buffer is full, dropping further audio");
+        }
     }
 
-    pub async fn stop(&mut self) -> Result<PathBuf> {
+    /// `max_len` for the current input's sample rate.
+    fn buffer_limit(&self) -> usize {
+        match self.max_duration {
+            Some(max_duration) => {
+                ((max_duration + BUFFER_MARGIN).as_secs_f64() * self.input_sample_rate as f64)
+                    as usize
+            }
+            None => usize::MAX,
+        }
+    }
+
+    /// Stop recording and return the audio as mono samples at
+    /// [`TARGET_OUTPUT_SAMPLE_RATE`].
+    pub async fn stop(&mut self) -> Result<Vec<f32>> {
         self.stream = None;
+        if let Some(fifo) = &self.fifo {
+            fifo.recording.store(false, Ordering::SeqCst);
+        }
         // Brief delay to ensure the audio stream callback has finished
         // processing any remaining samples before we read the buffer
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
+        self.end_spill();
         let samples = self.samples.lock().unwrap().clone();
+        self.prepare(samples).await
+    }
+
+    /// Seconds of audio captured since recording started or the last split.
+    pub fn buffered_secs(&self) -> f64 {
+        self.samples.lock().unwrap().len() as f64 / self.input_sample_rate as f64
+    }
+
+    /// Seconds of silence at the end of the audio captured so far, looking back
+    /// at most `max_secs`.
+    pub fn trailing_silence_secs(&self, max_secs: f64) -> f64 {
+        let rate = self.input_sample_rate;
+        let samples = self.samples.lock().unwrap();
+        let max_len = (max_secs * rate as f64) as usize;
+        let recent = &samples[samples.len().saturating_sub(max_len)..];
+        trailing_silence(&self.vad, recent, rate, self.silence_rms) as f64 / rate as f64
+    }
+
+    /// Hand off the audio captured so far while recording continues.
+    pub async fn split(&mut self) -> Result<Vec<f32>> {
+        self.end_spill();
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        if let Err(e) = self.begin_spill() {
+            log::error!("Failed to restart audio spill: {}", e);
+        }
+        self.prepare(samples).await
+    }
+
+    /// Stop recording and throw the captured audio away.
+    pub fn cancel(&mut self) {
+        self.stream = None;
+        if let Some(fifo) = &self.fifo {
+            fifo.recording.store(false, Ordering::SeqCst);
+        }
+        self.end_spill();
+        self.finish();
+        self.samples.lock().unwrap().clear();
+    }
+
+    /// Remove spill files for audio that has been transcribed.
+    pub fn finish(&mut self) {
+        for path in self.finished_spills.drain(..) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::warn!("Failed to remove spill file {:?}: {}", path, e);
+            }
+        }
+    }
+
+    fn begin_spill(&mut self) -> Result<()> {
+        let Some(dir) = &self.spill_dir else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(dir).context("Failed to create spill directory")?;
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("recording-{}.wav", stamp));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: self.input_sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer =
+            hound::WavWriter::create(&path, spec).context("Failed to create spill file")?;
+        log::debug!("Spilling audio to {:?}", path);
+
+        let samples = Arc::clone(&self.samples);
+        let done = Arc::new(AtomicBool::new(false));
+        let thread_done = Arc::clone(&done);
+        let handle = std::thread::spawn(move || {
+            let mut written = 0;
+            loop {
+                let finished = thread_done.load(Ordering::SeqCst);
+                let pending = samples.lock().unwrap()[written..].to_vec();
+                for &sample in &pending {
+                    writer.write_sample(sample)?;
+                }
+                written += pending.len();
+                if finished {
+                    break;
+                }
+                // Flushing rewrites the header so the file is valid up to this point
+                writer.flush()?;
+                std::thread::sleep(SPILL_FLUSH_INTERVAL);
+            }
+            writer.finalize()?;
+            Ok(())
+        });
+
+        self.spill = Some(SpillWriter { path, done, handle });
+        Ok(())
+    }
+
+    fn end_spill(&mut self) {
+        let Some(spill) = self.spill.take() else {
+            return;
+        };
+        spill.done.store(true, Ordering::SeqCst);
+        match spill.handle.join() {
+            Ok(Ok(())) => self.finished_spills.push(spill.path),
+            Ok(Err(e)) => log::error!("Audio spill to {:?} failed: {}", spill.path, e),
+            Err(_) => log::error!("Audio spill thread panicked"),
+        }
+    }
+
+    /// Trim and resample captured audio for the engine.
+    async fn prepare(&self, samples: Vec<f32>) -> Result<Vec<f32>> {
         let input_sample_rate = self.input_sample_rate;
         let output_sample_rate = self.output_sample_rate;
+        let samples = if self.trim_silence {
+            let speech = speech_range(&self.vad, &samples, input_sample_rate, self.silence_rms);
+            log::debug!(
+                "Trimmed {} of {} samples of silence",
+                samples.len() - speech.len(),
+                samples.len()
+            );
+            samples[speech].to_vec()
+        } else {
+            samples
+        };
 
-        // WAV writing is blocking (hound), run in spawn_blocking
-        let wav_path = tokio::task::spawn_blocking(move || {
+        let save_dir = self.save_dir.clone();
+
+        // Resampling and WAV writing (hound) are blocking, run in spawn_blocking
+        tokio::task::spawn_blocking(move || {
             // Resample to output rate if needed
             let resampled = if input_sample_rate != output_sample_rate {
                 resample(&samples, input_sample_rate, output_sample_rate)
@@ -137,27 +486,12 @@ impl AudioRecorder {
                 samples.clone()
             };
 
-            let temp_file = tempfile::Builder::new()
-                .suffix(".wav")
-                .tempfile()?
-                .into_temp_path()
-                .keep()?;
-
-            let spec = hound::WavSpec {
-                channels: 1,
-                sample_rate: output_sample_rate,
-                bits_per_sample: 16,
-                sample_format: hound::SampleFormat::Int,
-            };
-
-            let file = File::create(&temp_file)?;
-            let mut writer = hound::WavWriter::new(BufWriter::new(file), spec)?;
-            for &sample in resampled.iter() {
-                let i16_sample =
-                    (sample * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-                writer.write_sample(i16_sample)?;
+            if let Some(dir) = save_dir {
+                match save_wav(&dir, &resampled, output_sample_rate) {
+                    Ok(path) => log::info!("Saved audio to {:?}", path),
+                    Err(e) => log::warn!("Failed to save audio: {:#}", e),
+                }
             }
-            writer.finalize()?;
 
             log::debug!(
                 "Recorded {} samples @ {}Hz -> {} samples @ {}Hz ({:.2}s)",
@@ -168,16 +502,338 @@ impl AudioRecorder {
                 resampled.len() as f64 / output_sample_rate as f64
             );
 
-            Ok::<PathBuf, anyhow::Error>(temp_file)
+            resampled
         })
         .await
-        .context("WAV writing task failed")??;
+        .context("Audio preparation task failed")
+    }
+}
+
+/// Write 16-bit samples to a new timestamped WAV file in `dir`.
+fn save_wav(dir: &Path, samples: &[f32], sample_rate: u32) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = dir.join(format!("recording-{}.wav", stamp));
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let file = File::create(&path).with_context(|| format!("Failed to create {:?}", path))?;
+    let mut writer = hound::WavWriter::new(BufWriter::new(file), spec)?;
+    for &sample in samples {
+        let i16_sample = (sample * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        writer.write_sample(i16_sample)?;
+    }
+    writer.finalize()?;
+    Ok(path)
+}
+
+/// Fail early when there is no microphone to record from.
+pub fn check_input_device(selector: Option<&str>) -> Result<()> {
+    input_device(selector)?;
+    Ok(())
+}
+
+/// Fail early when `--input-fifo` isn't a named pipe, rather than reopening
+/// it forever.
+pub fn check_fifo(path: &Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let is_fifo = std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo());
+    if !is_fifo {
+        let selector = format!("--input-fifo {:?} (create it with mkfifo)", path);
+        return Err(AppError::InputDeviceNotFound(selector).into());
+    }
+    Ok(())
+}
+
+/// The input device picked by `selector`: an index into the `list-devices`
+/// output, an exact device name, or a unique part of one (ignoring case).
+/// Without a selector, the system default.
+pub fn input_device(selector: Option<&str>) -> Result<cpal::Device> {
+    let host = cpal::default_host();
+    let Some(selector) = selector else {
+        return Ok(host.default_input_device().ok_or(AppError::NoMicrophone)?);
+    };
+    let mut devices: Vec<cpal::Device> = host
+        .input_devices()
+        .context("Failed to list input devices")?
+        .collect();
+    let names: Vec<String> = devices
+        .iter()
+        .map(|device| device.name().unwrap_or_default())
+        .collect();
+    let index = select_device(&names, selector)
+        .ok_or_else(|| AppError::InputDeviceNotFound(selector.to_string()))?;
+    Ok(devices.swap_remove(index))
+}
+
+fn select_device(names: &[String], selector: &str) -> Option<usize> {
+    if let Ok(index) = selector.parse::<usize>() {
+        return (index < names.len()).then_some(index);
+    }
+    if let Some(index) = names.iter().position(|name| name == selector) {
+        return Some(index);
+    }
+    let needle = selector.to_lowercase();
+    let mut matches = names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.to_lowercase().contains(&needle));
+    match (matches.next(), matches.next()) {
+        (Some((index, _)), None) => Some(index),
+        _ => None,
+    }
+}
+
+/// Where `--spill` writes in-progress recordings.
+pub fn spill_dir() -> PathBuf {
+    crate::model::cache_dir().join("recordings")
+}
+
+/// Spill files left behind by a crashed session, oldest first.
+pub fn orphaned_recordings() -> Result<Vec<PathBuf>> {
+    let dir = spill_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .context("Failed to read spill directory")?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wav"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Load a WAV file as 16 kHz mono samples ready for transcription.
+pub fn read_recording(path: &Path) -> Result<Vec<f32>> {
+    let reader =
+        hound::WavReader::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    let channels = spec.channels as usize;
+    let mono: Vec<f32> = if channels == 1 {
+        interleaved
+    } else {
+        interleaved
+            .chunks(channels)
+            .map(|chunk| chunk.iter().sum::<f32>() / channels as f32)
+            .collect()
+    };
+
+    if spec.sample_rate == TARGET_OUTPUT_SAMPLE_RATE {
+        Ok(mono)
+    } else {
+        Ok(resample(&mono, spec.sample_rate, TARGET_OUTPUT_SAMPLE_RATE))
+    }
+}
+
+/// Decode an audio file in any supported container and codec (WAV, FLAC, MP3,
+/// AAC/M4A, Ogg Vorbis) to 16 kHz mono samples. Corrupt frames are skipped,
+/// but a file that is mostly corrupt, has no audio or an implausible header is
+/// an error.
+pub fn decode_audio(path: &Path) -> Result<Vec<f32>> {
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+    use symphonia::core::errors::Error as DecodeError;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::probe::Hint;
+
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut format = symphonia::default::get_probe()
+        .format(&hint, stream, &Default::default(), &Default::default())
+        .with_context(|| format!("Unsupported audio format: {:?}", path))?
+        .format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .context("No audio track found")?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .context("Unknown sample rate")?;
+    if !DECODE_SAMPLE_RATES.contains(&sample_rate) {
+        anyhow::bail!("Implausible sample rate {} Hz", sample_rate);
+    }
+    // Checks the checksums of formats that have them, e.g. FLAC's MD5
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions { verify: true })
+        .context("Unsupported audio codec")?;
+
+    let mut mono = Vec::new();
+    let mut packets = 0;
+    let mut bad_packets = 0;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).context("Failed to read audio"),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        packets += 1;
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt frame costs a few milliseconds of audio, not the whole file
+            Err(DecodeError::DecodeError(e)) => {
+                log::debug!("Skipping undecodable packet: {}", e);
+                bad_packets += 1;
+                continue;
+            }
+            Err(e) => return Err(e).context("Failed to decode audio"),
+        };
+        let channels = decoded.spec().channels.count();
+        if channels == 0 || decoded.spec().rate != sample_rate {
+            log::debug!("Skipping packet with a different format than the track");
+            bad_packets += 1;
+            continue;
+        }
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+        buffer.copy_interleaved_ref(decoded);
+        mono.extend(
+            buffer
+                .samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    }
+
+    if bad_packets > MAX_BAD_PACKETS && bad_packets * 2 > packets {
+        anyhow::bail!(
+            "{} of {} packets could not be decoded, the file looks corrupt",
+            bad_packets,
+            packets
+        );
+    }
+    if bad_packets > 0 {
+        log::warn!(
+            "Skipped {} of {} packets of {:?} that could not be decoded",
+            bad_packets,
+            packets,
+            path
+        );
+    }
+    if decoder.finalize().verify_ok == Some(false) {
+        log::warn!(
+            "{:?} doesn't match its checksum, the audio may be damaged",
+            path
+        );
+    }
+    if mono.is_empty() {
+        anyhow::bail!("No audio could be decoded");
+    }
+
+    if sample_rate == TARGET_OUTPUT_SAMPLE_RATE {
+        Ok(mono)
+    } else {
+        Ok(resample(&mono, sample_rate, TARGET_OUTPUT_SAMPLE_RATE))
+    }
+}
+
+fn spawn_fifo_reader(
+    path: PathBuf,
+    samples: Arc<Mutex<Vec<f32>>>,
+    max_len: Arc<AtomicUsize>,
+    recording: Arc<AtomicBool>,
+) {
+    std::thread::spawn(move || loop {
+        // Opening blocks until a writer connects; reopen whenever the writer goes away
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                log::error!("Failed to open audio FIFO {:?}: {}", path, e);
+                std::thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+        log::debug!("Audio FIFO connected: {:?}", path);
+
+        let mut buf = [0u8; 4096];
+        let mut pending: Vec<u8> = Vec::new();
+        loop {
+            let n = match file.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => {
+                    log::error!("Audio FIFO read error: {}", e);
+                    break;
+                }
+            };
+            pending.extend_from_slice(&buf[..n]);
+            let whole = pending.len() / 2 * 2;
+            if recording.load(Ordering::SeqCst) {
+                let data: Vec<f32> = pending[..whole]
+                    .chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)
+                    .collect();
+                AudioRecorder::write_samples(&samples, &data, 1, None, &max_len);
+            }
+            pending.drain(..whole);
+        }
+        log::debug!("Audio FIFO writer disconnected: {:?}", path);
+    });
+}
 
-        Ok(wav_path)
+/// Number of samples at the end of `samples` without voice activity.
+fn trailing_silence(vad: &Vad, samples: &[f32], sample_rate: u32, threshold: f32) -> usize {
+    let voiced = vad.voiced_frames(samples, sample_rate, threshold);
+    match voiced.iter().rposition(|&voiced| voiced) {
+        Some(last) => samples
+            .len()
+            .saturating_sub((last + 1) * vad.frame_len(sample_rate)),
+        None => samples.len(),
     }
 }
 
-fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+/// The part of `samples` from the first to the last voiced frame, padded by
+/// `TRIM_PADDING`. Recordings without any voice activity are kept whole and
+/// left for the engine to judge.
+fn speech_range(
+    vad: &Vad,
+    samples: &[f32],
+    sample_rate: u32,
+    threshold: f32,
+) -> std::ops::Range<usize> {
+    let frame_len = vad.frame_len(sample_rate);
+    let padding = sample_rate as usize * TRIM_PADDING.as_millis() as usize / 1000;
+    let voiced = vad.voiced_frames(samples, sample_rate, threshold);
+    let (Some(first), Some(last)) = (
+        voiced.iter().position(|&voiced| voiced),
+        voiced.iter().rposition(|&voiced| voiced),
+    ) else {
+        return 0..samples.len();
+    };
+    let start = (first * frame_len).saturating_sub(padding);
+    let end = ((last + 1) * frame_len + padding).min(samples.len());
+    start..end
+}
+
+/// Linear resampling, good enough for speech.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
     let ratio = from_rate as f64 / to_rate as f64;
     let output_len = (samples.len() as f64 / ratio) as usize;
     let mut output = Vec::with_capacity(output_len);
@@ -199,3 +855,107 @@ fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_fifo() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("audio.pcm");
+        let missing = check_fifo(&file).unwrap_err();
+        assert_eq!(crate::error::exit_code(&missing), 3);
+        std::fs::write(&file, b"").unwrap();
+        assert!(check_fifo(&file).is_err());
+        #[cfg(target_os = "linux")]
+        {
+            let fifo = dir.path().join("audio.pipe");
+            nix::unistd::mkfifo(&fifo, nix::sys::stat::Mode::S_IRWXU).unwrap();
+            check_fifo(&fifo).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_speech_range() {
+        let rate = 16000;
+        let mut samples = vec![0.001; 3 * rate as usize];
+        // Speech from 1.0s to 1.5s
+        samples[16000..24000].fill(0.2);
+
+        // Padded by 200 ms from the start of the 30 ms frame where speech begins
+        let vad = Vad::Energy;
+        let speech = speech_range(&vad, &samples, rate, SILENCE_RMS);
+        assert_eq!(speech, 12640..27200);
+        assert_eq!(trailing_silence(&vad, &samples, rate, SILENCE_RMS), 24000);
+        // Nothing to trim without speech
+        assert_eq!(
+            speech_range(&vad, &samples[..8000], rate, SILENCE_RMS),
+            0..8000
+        );
+    }
+
+    #[test]
+    fn test_write_samples_stops_at_max_len() {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let max_len = AtomicUsize::new(5);
+        // Stereo, mixed down to mono
+        AudioRecorder::write_samples(&samples, &[0.2, 0.4, 0.6, 0.8], 2, None, &max_len);
+        assert_eq!(samples.lock().unwrap().len(), 2);
+        AudioRecorder::write_samples(&samples, &[0.1; 8], 2, None, &max_len);
+        assert_eq!(samples.lock().unwrap().len(), 5);
+        AudioRecorder::write_samples(&samples, &[0.1; 8], 2, None, &max_len);
+        assert_eq!(samples.lock().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_decode_audio_rejects_corrupt_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("tone.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&wav, spec).unwrap();
+        for i in 0..16000 {
+            let sample = ((i as f32 * 0.05).sin() * 8000.0) as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        assert_eq!(decode_audio(&wav).unwrap().len(), 16000);
+
+        // Garbage, and a header without the audio
+        let garbage = dir.path().join("garbage.mp3");
+        let bytes: Vec<u8> = (0..64 * 1024u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        std::fs::write(&garbage, bytes).unwrap();
+        assert!(decode_audio(&garbage).is_err());
+        let truncated = dir.path().join("truncated.wav");
+        std::fs::write(&truncated, &std::fs::read(&wav).unwrap()[..44]).unwrap();
+        assert!(decode_audio(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_select_device() {
+        let names: Vec<String> = [
+            "default",
+            "USB Headset Mono",
+            "USB Headset Stereo",
+            "HDA Intel",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        assert_eq!(select_device(&names, "1"), Some(1));
+        assert_eq!(select_device(&names, "4"), None);
+        assert_eq!(select_device(&names, "default"), Some(0));
+        assert_eq!(select_device(&names, "hda"), Some(3));
+        assert_eq!(select_device(&names, "headset stereo"), Some(2));
+        // Ambiguous
+        assert_eq!(select_device(&names, "headset"), None);
+    }
+}
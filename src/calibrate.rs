@@ -0,0 +1,140 @@
+use crate::audio;
+use crate::profiles::{self, DeviceProfile};
+use crate::report::{self, progress};
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{FromSample, Sample, SizedSample, SupportedStreamConfig};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const QUIET_TIME: Duration = Duration::from_secs(3);
+const SPEECH_TIME: Duration = Duration::from_secs(6);
+/// Skipped at the start of each phase, while the user reacts to the prompt
+const REACTION_TIME: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+struct CalibrationReport {
+    device: String,
+    channels: u16,
+    quiet_rms: Vec<f32>,
+    speech_rms: Vec<f32>,
+    profile: DeviceProfile,
+    path: PathBuf,
+}
+
+/// Measure the selected microphone's noise floor and speech level, and store a
+/// profile that is applied whenever the device is recorded from.
+pub fn run(selector: Option<&str>) -> Result<()> {
+    let device = audio::input_device(selector)?;
+    let name = device.name()?;
+    let config = device
+        .default_input_config()
+        .context("No default input config")?;
+    let channels = config.channels() as usize;
+    let rate = config.sample_rate().0 as usize;
+    progress!("Calibrating {:?}", name);
+
+    // Interleaved samples as delivered by the device
+    let samples = Arc::new(Mutex::new(Vec::new()));
+    let stream = build_stream(&device, &config, Arc::clone(&samples))?;
+    stream.play()?;
+
+    progress!("Stay quiet for {} seconds...", QUIET_TIME.as_secs());
+    std::thread::sleep(QUIET_TIME);
+    let speech_start = samples.lock().unwrap().len();
+    progress!(
+        "Now speak normally for {} seconds, e.g. read this sentence aloud a few times.",
+        SPEECH_TIME.as_secs()
+    );
+    std::thread::sleep(SPEECH_TIME);
+    drop(stream);
+
+    let samples = samples.lock().unwrap();
+    let skip = REACTION_TIME.as_millis() as usize * rate / 1000 * channels;
+    let quiet_rms = channel_rms(&samples[skip.min(speech_start)..speech_start], channels);
+    let speech_rms = channel_rms(
+        &samples[(speech_start + skip).min(samples.len())..],
+        channels,
+    );
+    if speech_rms.iter().all(|&level| level == 0.0) {
+        anyhow::bail!("No audio received from {:?}", name);
+    }
+
+    let profile = DeviceProfile::from_levels(&quiet_rms, &speech_rms);
+    profiles::save(&name, profile)?;
+
+    let report = CalibrationReport {
+        device: name,
+        channels: channels as u16,
+        quiet_rms,
+        speech_rms,
+        profile,
+        path: profiles::profiles_path(),
+    };
+    if report::json() {
+        return report::emit(&report);
+    }
+    println!();
+    println!("Gain: {:.2}x", report.profile.gain);
+    println!(
+        "Noise floor: {:.1} dBFS",
+        20.0 * report.profile.noise_floor.max(1e-6).log10()
+    );
+    match report.profile.channel {
+        Some(channel) => println!("Recording channel {} only", channel + 1),
+        None if channels > 1 => println!("Mixing all {} channels", channels),
+        None => {}
+    }
+    println!("Saved to {:?}", report.path);
+    Ok(())
+}
+
+/// RMS level of each channel of interleaved `samples`.
+fn channel_rms(samples: &[f32], channels: usize) -> Vec<f32> {
+    let mut sums = vec![0.0f64; channels];
+    for frame in samples.chunks_exact(channels) {
+        for (sum, &sample) in sums.iter_mut().zip(frame) {
+            *sum += (sample * sample) as f64;
+        }
+    }
+    let frames = (samples.len() / channels).max(1) as f64;
+    sums.iter()
+        .map(|sum| (sum / frames).sqrt() as f32)
+        .collect()
+}
+
+fn build_stream(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    samples: Arc<Mutex<Vec<f32>>>,
+) -> Result<cpal::Stream> {
+    match config.sample_format() {
+        cpal::SampleFormat::F32 => stream::<f32>(device, config, samples),
+        cpal::SampleFormat::I16 => stream::<i16>(device, config, samples),
+        cpal::SampleFormat::I32 => stream::<i32>(device, config, samples),
+        format => anyhow::bail!("Unsupported sample format: {:?}", format),
+    }
+}
+
+fn stream<T>(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    samples: Arc<Mutex<Vec<f32>>>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let stream = device.build_input_stream(
+        &config.config(),
+        move |data: &[T], _| {
+            let mut samples = samples.lock().unwrap();
+            samples.extend(data.iter().map(|&s| f32::from_sample(s)));
+        },
+        |err| log::error!("Audio input error: {}", err),
+        None,
+    )?;
+    Ok(stream)
+}
@@ -0,0 +1,79 @@
+//! Compose mode: transcripts collect in a buffer that is reviewed in the console
+//! and output as a whole on command, instead of being typed one by one.
+
+/// Dictated text waiting to be output.
+#[derive(Debug, Default)]
+pub struct Buffer {
+    text: String,
+}
+
+impl Buffer {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Append a transcript, separated from the previous one by a space.
+    pub fn push(&mut self, segment: &str) {
+        if !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        self.text.push_str(segment.trim());
+    }
+
+    pub fn replace(&mut self, text: String) {
+        self.text = text;
+    }
+
+    /// Remove everything after the second-to-last sentence end.
+    pub fn delete_last_sentence(&mut self) {
+        let body = self.text.trim_end().trim_end_matches(['.', '!', '?']);
+        let keep = body.rfind(['.', '!', '?']).map(|end| end + 1).unwrap_or(0);
+        self.text.truncate(keep);
+    }
+
+    /// The buffered text, leaving the buffer empty.
+    pub fn take(&mut self) -> String {
+        std::mem::take(&mut self.text)
+    }
+}
+
+/// Whether `transcript` is the spoken "delete last sentence" command.
+pub fn is_delete_last_sentence(transcript: &str) -> bool {
+    let command = transcript
+        .trim()
+        .trim_end_matches(['.', '!', '?'])
+        .to_ascii_lowercase();
+    matches!(
+        command.as_str(),
+        "delete last sentence" | "delete the last sentence"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_last_sentence() {
+        let mut buffer = Buffer::default();
+        buffer.push("Hi Anna. Thanks for the notes!");
+        buffer.push("I'll send them on Monday.");
+        buffer.delete_last_sentence();
+        assert_eq!(buffer.text(), "Hi Anna. Thanks for the notes!");
+        buffer.delete_last_sentence();
+        assert_eq!(buffer.text(), "Hi Anna.");
+        buffer.delete_last_sentence();
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_is_delete_last_sentence() {
+        assert!(is_delete_last_sentence("Delete last sentence."));
+        assert!(is_delete_last_sentence("delete the last sentence"));
+        assert!(!is_delete_last_sentence("Please delete the last sentence."));
+    }
+}
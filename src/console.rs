@@ -0,0 +1,41 @@
+use crate::control::ControlCommand;
+use crate::feedback::Rating;
+use crate::i18n::{tr, Msg};
+use std::io::{BufRead, IsTerminal};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Read single-letter commands from stdin, so the tool stays usable when global
+/// hotkeys can't be captured. Does nothing when stdin isn't a terminal.
+pub fn spawn(tx: UnboundedSender<ControlCommand>) {
+    if !std::io::stdin().is_terminal() {
+        return;
+    }
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let command = match line.trim() {
+                "r" => ControlCommand::ToggleRecording,
+                "p" => ControlCommand::TogglePostProcess,
+                "+" => ControlCommand::Rate(Rating::Good),
+                "-" => ControlCommand::Rate(Rating::Bad),
+                "o" => ControlCommand::OutputCompose,
+                "c" => ControlCommand::DiscardCompose,
+                "s" => ControlCommand::Status,
+                "q" => ControlCommand::Quit,
+                "" => continue,
+                _ => {
+                    println!("{}", tr(Msg::ConsoleHelp));
+                    continue;
+                }
+            };
+            if tx.send(command).is_err() {
+                break;
+            }
+        }
+    });
+
+    println!("{}", tr(Msg::ConsoleHelp));
+}
@@ -0,0 +1,24 @@
+use crate::feedback::Rating;
+
+/// Commands from trigger sources other than the hotkey (console, headset buttons, `ctl`).
+#[derive(Debug, Clone)]
+pub enum ControlCommand {
+    ToggleRecording,
+    /// Start recording unless already recording
+    StartRecording,
+    /// Stop recording if recording
+    StopRecording,
+    TogglePostProcess,
+    /// Rate the last transcription (with `--feedback`)
+    Rate(Rating),
+    /// Output the compose buffer (with `--compose`)
+    OutputCompose,
+    /// Empty the compose buffer (with `--compose`)
+    DiscardCompose,
+    /// Output the last transcription again
+    RepeatLast,
+    /// Record from the input device matching this selector from the next recording on
+    SetDevice(String),
+    Status,
+    Quit,
+}
@@ -0,0 +1,102 @@
+//! Spoken corrections: saying "replace X with Y" after a transcript has been
+//! typed fixes the last occurrence of X in it instead of typing the command.
+
+/// A parsed "replace X with Y" command.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Correction {
+    pub from: String,
+    pub to: String,
+}
+
+/// How to turn the previous output into the corrected one with the cursor at its end.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Edit {
+    /// Characters to delete with backspace
+    pub delete: usize,
+    /// Text to type after deleting
+    pub insert: String,
+    /// The previous output after the correction
+    pub result: String,
+}
+
+/// Parse a transcript like "Replace foo with bar." into a correction.
+pub fn parse(transcript: &str) -> Option<Correction> {
+    let text = transcript
+        .trim()
+        .trim_end_matches(['.', '!', '?'])
+        .trim_end();
+    let rest = strip_prefix_ignore_case(text, "replace ")?;
+    let split = rest.to_ascii_lowercase().rfind(" with ")?;
+    let clean = |s: &str| s.trim().trim_matches([',', '"', '\'']).trim().to_string();
+    let from = clean(&rest[..split]);
+    let to = clean(&rest[split + " with ".len()..]);
+    if from.is_empty() {
+        return None;
+    }
+    Some(Correction { from, to })
+}
+
+/// Apply `correction` to the last case-insensitive occurrence of its target in
+/// `previous`. `None` when the target doesn't occur.
+pub fn apply(previous: &str, correction: &Correction) -> Option<Edit> {
+    // ASCII lowercasing keeps byte offsets aligned with `previous`
+    let start = previous
+        .to_ascii_lowercase()
+        .rfind(&correction.from.to_ascii_lowercase())?;
+    let end = start + correction.from.len();
+    let tail = &previous[end..];
+    Some(Edit {
+        delete: previous[start..].chars().count(),
+        insert: format!("{}{}", correction.to, tail),
+        result: format!("{}{}{}", &previous[..start], correction.to, tail),
+    })
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &text[prefix.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn correction(from: &str, to: &str) -> Correction {
+        Correction {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("Replace Tuesday with Wednesday."),
+            Some(correction("Tuesday", "Wednesday"))
+        );
+        assert_eq!(
+            parse("replace the cat, with the dog"),
+            Some(correction("the cat", "the dog"))
+        );
+        assert_eq!(parse("Replace this."), None);
+        assert_eq!(parse("Please replace foo with bar."), None);
+    }
+
+    #[test]
+    fn test_apply_last_occurrence() {
+        let edit = apply(
+            "See you on Tuesday, not Tuesday night.",
+            &correction("tuesday", "Friday"),
+        )
+        .unwrap();
+        assert_eq!(edit.result, "See you on Tuesday, not Friday night.");
+        assert_eq!(edit.delete, "Tuesday night.".len());
+        assert_eq!(edit.insert, "Friday night.");
+    }
+
+    #[test]
+    fn test_apply_missing_target() {
+        assert_eq!(apply("Hello world", &correction("moon", "sun")), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Feedback for when the terminal isn't in view: short beeps (`--beep`) when
+//! recording starts and stops and when a transcription is done or failed, and
+//! desktop notifications with the result (`--notify`).
+
+#[cfg(target_os = "linux")]
+use crate::error::AppError;
+#[cfg(target_os = "linux")]
+use crate::output;
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample, SupportedStreamConfig};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Kept quiet, as the microphone may pick up the start beep
+const AMPLITUDE: f32 = 0.2;
+/// Fade in and out so tones don't click
+const FADE_SECS: f32 = 0.005;
+/// Notifications disappear after this long (where the desktop honours it)
+#[cfg(target_os = "linux")]
+const NOTIFICATION_TIMEOUT_MS: u32 = 4000;
+#[cfg(target_os = "linux")]
+const APP_NAME: &str = "parakeet-writer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    RecordingStarted,
+    RecordingStopped,
+    Done,
+    Failed,
+}
+
+/// A tone's frequency in Hz (0 for a pause) and length in milliseconds.
+type Tone = (f32, u64);
+
+impl Cue {
+    fn tones(self) -> &'static [Tone] {
+        match self {
+            Cue::RecordingStarted => &[(880.0, 70)],
+            Cue::RecordingStopped => &[(660.0, 70)],
+            Cue::Done => &[(660.0, 60), (0.0, 40), (990.0, 90)],
+            Cue::Failed => &[(330.0, 120), (0.0, 40), (220.0, 200)],
+        }
+    }
+}
+
+/// Play `cue` through the default output device without waiting for it.
+pub fn beep(cue: Cue) {
+    std::thread::spawn(move || {
+        if let Err(e) = play(cue.tones()) {
+            log::debug!("Failed to play {:?} cue: {:#}", cue, e);
+        }
+    });
+}
+
+fn play(tones: &'static [Tone]) -> Result<()> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .context("No audio output device")?;
+    let config = device
+        .default_output_config()
+        .context("No default output config")?;
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => output_stream::<f32>(&device, &config, tones),
+        cpal::SampleFormat::I16 => output_stream::<i16>(&device, &config, tones),
+        cpal::SampleFormat::I32 => output_stream::<i32>(&device, &config, tones),
+        format => anyhow::bail!("Unsupported sample format: {:?}", format),
+    }?;
+    stream.play()?;
+    let total_ms: u64 = tones.iter().map(|&(_, ms)| ms).sum();
+    // Let the device drain its buffer before the stream is dropped
+    std::thread::sleep(Duration::from_millis(total_ms + 100));
+    Ok(())
+}
+
+fn output_stream<T>(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    tones: &'static [Tone],
+) -> Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels() as usize;
+    let rate = config.sample_rate().0 as f32;
+    let mut frame = 0usize;
+    let stream = device.build_output_stream(
+        &config.config(),
+        move |data: &mut [T], _| {
+            for out in data.chunks_mut(channels) {
+                let value = sample_at(tones, frame as f32 / rate);
+                frame += 1;
+                for sample in out.iter_mut() {
+                    *sample = T::from_sample(value);
+                }
+            }
+        },
+        |err| log::debug!("Audio output error: {}", err),
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// The value of the tone sequence `secs` into it; silence once it's over.
+fn sample_at(tones: &[Tone], secs: f32) -> f32 {
+    let mut start = 0.0;
+    for &(hz, ms) in tones {
+        let len = ms as f32 / 1000.0;
+        if secs < start + len {
+            let t = secs - start;
+            let envelope = (t / FADE_SECS).min((len - t) / FADE_SECS).min(1.0);
+            return (t * hz * 2.0 * std::f32::consts::PI).sin() * AMPLITUDE * envelope;
+        }
+        start += len;
+    }
+    0.0
+}
+
+/// Fail early if the notification tool is missing.
+pub fn check_notify() -> Result<()> {
+    #[cfg(target_os = "linux")]
+    if !output::in_path("notify-send") {
+        return Err(AppError::OutputToolMissing {
+            tool: "notify-send",
+            package: "libnotify",
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Show a desktop notification without waiting for it.
+pub fn notify(summary: &str, body: &str) {
+    #[cfg(target_os = "linux")]
+    let spawned = Command::new("notify-send")
+        .args(["--app-name", APP_NAME, "--expire-time"])
+        .arg(NOTIFICATION_TIMEOUT_MS.to_string())
+        .arg("--")
+        .args([summary, body])
+        .spawn();
+    // Text is passed as arguments rather than spliced into the script
+    #[cfg(target_os = "macos")]
+    let spawned = Command::new("osascript")
+        .args(["-e", "on run argv"])
+        .args([
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+        ])
+        .args(["-e", "end run", summary, body])
+        .spawn();
+    if let Err(e) = spawned {
+        log::warn!("Failed to show notification: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tones_fade_and_end() {
+        let tones = [(1000.0, 100), (0.0, 50)];
+        assert_eq!(sample_at(&tones, 0.0), 0.0);
+        // A quarter period into the fade-in
+        assert!((sample_at(&tones, 0.00025) - AMPLITUDE * 0.05).abs() < 1e-4);
+        assert!(sample_at(&tones, 0.05025).abs() > AMPLITUDE * 0.99);
+        assert_eq!(sample_at(&tones, 0.12), 0.0);
+        assert_eq!(sample_at(&tones, 1.0), 0.0);
+    }
+}
@@ -0,0 +1,266 @@
+//! `daemon` mode: a Unix socket next to the hotkey, so scripts, compositor
+//! keybinds and status bars can drive a running instance with `ctl`. Requests
+//! and responses are single lines of JSON.
+
+use crate::audio;
+use crate::control::ControlCommand;
+use crate::i18n::{tr, trf, Msg};
+use crate::report;
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// What `ctl` can ask of the daemon.
+#[derive(Debug, Clone, Subcommand, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Start recording, as if the hotkey was pressed
+    StartRecording,
+    /// Stop recording and transcribe, as if the hotkey was released
+    Stop,
+    /// Start or stop recording
+    Toggle,
+    /// Record from another input device from the next recording on
+    SetDevice {
+        /// A number or (part of a) name from `list-devices`
+        device: String,
+    },
+    /// Show whether a recording is in progress
+    Status,
+    /// Print the last transcript that was output
+    LastText,
+    /// Output the last transcript again, e.g. after it went to the wrong window
+    Repeat,
+    /// Shut the daemon down
+    Quit,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Response {
+    ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recording: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recording_secs: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+/// State the event loop shares with the socket, so queries are answered
+/// without waiting for the loop.
+#[derive(Debug, Default)]
+pub struct Status {
+    /// When the current recording started
+    pub recording_since: Option<Instant>,
+    pub last_text: Option<String>,
+}
+
+pub type SharedStatus = Arc<Mutex<Status>>;
+
+/// Where the daemon listens: `$XDG_RUNTIME_DIR/parakeet-writer.sock`, or the
+/// temp directory where there's no runtime directory (macOS).
+pub fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("parakeet-writer.sock")
+}
+
+/// Accept `ctl` connections in the background, passing commands on to the event loop.
+pub async fn serve(commands: UnboundedSender<ControlCommand>, status: SharedStatus) -> Result<()> {
+    let path = socket_path();
+    if path.exists() {
+        if UnixStream::connect(&path).await.is_ok() {
+            bail!("Another daemon is already listening on {:?}", path);
+        }
+        // Left behind by a daemon that didn't shut down cleanly
+        std::fs::remove_file(&path).context("Failed to remove stale socket")?;
+    }
+    let listener =
+        UnixListener::bind(&path).with_context(|| format!("Failed to listen on {:?}", path))?;
+    let owner = restrict_to_user(&path)?;
+    log::info!("Listening for ctl commands on {:?}", path);
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    if let Err(e) = check_peer(&stream, owner) {
+                        log::warn!("Refused ctl connection: {:#}", e);
+                        continue;
+                    }
+                    let commands = commands.clone();
+                    let status = Arc::clone(&status);
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, commands, status).await {
+                            log::debug!("ctl connection failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("Failed to accept ctl connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Anyone who can connect can type into the focused window, so only the owner
+/// may. Returns the owner's user id.
+fn restrict_to_user(path: &Path) -> Result<u32> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .context("Failed to set socket permissions")?;
+    Ok(std::fs::metadata(path)
+        .context("Failed to read socket owner")?
+        .uid())
+}
+
+/// Only let the socket's owner in, also for connections made before its
+/// permissions were restricted.
+fn check_peer(stream: &UnixStream, owner: u32) -> Result<()> {
+    let uid = stream
+        .peer_cred()
+        .context("Failed to read peer credentials")?
+        .uid();
+    if uid != owner {
+        bail!("user {} doesn't own the socket", uid);
+    }
+    Ok(())
+}
+
+/// Remove the socket on shutdown.
+pub fn cleanup() {
+    let _ = std::fs::remove_file(socket_path());
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    commands: UnboundedSender<ControlCommand>,
+    status: SharedStatus,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => respond(request, &commands, &status),
+            Err(e) => Response {
+                error: Some(format!("Invalid request: {}", e)),
+                ..Response::default()
+            },
+        };
+        let mut json = serde_json::to_string(&response)?;
+        json.push('\n');
+        writer.write_all(json.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+fn respond(
+    request: Request,
+    commands: &UnboundedSender<ControlCommand>,
+    status: &SharedStatus,
+) -> Response {
+    let command = match request {
+        Request::StartRecording => ControlCommand::StartRecording,
+        Request::Stop => ControlCommand::StopRecording,
+        Request::Toggle => ControlCommand::ToggleRecording,
+        Request::Repeat => ControlCommand::RepeatLast,
+        Request::Quit => ControlCommand::Quit,
+        Request::SetDevice { device } => {
+            if let Err(e) = audio::input_device(Some(&device)) {
+                return Response {
+                    error: Some(e.to_string()),
+                    ..Response::default()
+                };
+            }
+            ControlCommand::SetDevice(device)
+        }
+        Request::Status => {
+            let since = status.lock().unwrap().recording_since;
+            return Response {
+                ok: true,
+                recording: Some(since.is_some()),
+                recording_secs: since.map(|since| since.elapsed().as_secs_f64()),
+                ..Response::default()
+            };
+        }
+        Request::LastText => {
+            return Response {
+                ok: true,
+                text: status.lock().unwrap().last_text.clone(),
+                ..Response::default()
+            };
+        }
+    };
+    match commands.send(command) {
+        Ok(()) => Response {
+            ok: true,
+            ..Response::default()
+        },
+        Err(_) => Response {
+            error: Some("The daemon is shutting down".to_string()),
+            ..Response::default()
+        },
+    }
+}
+
+/// `ctl`: send `request` to the running daemon and print its answer.
+pub async fn send(request: Request) -> Result<()> {
+    let path = socket_path();
+    let stream = UnixStream::connect(&path)
+        .await
+        .with_context(|| format!("No daemon is listening on {:?}", path))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut json = serde_json::to_string(&request)?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .context("The daemon closed the connection")?;
+    let response: Response = serde_json::from_str(&line).context("Invalid response")?;
+    if let Some(error) = response.error {
+        bail!(error);
+    }
+    if report::json() {
+        return report::emit(&response);
+    }
+    match request {
+        Request::Status => match response.recording_secs {
+            Some(secs) => println!("{}", trf(Msg::StatusRecording, &[&format!("{:.1}", secs)])),
+            None => println!("{}", tr(Msg::StatusIdle)),
+        },
+        Request::LastText => {
+            if let Some(text) = response.text {
+                println!("{}", text);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    #[tokio::test]
+    async fn test_check_peer() {
+        let dir = tempfile::tempdir().unwrap();
+        let uid = std::fs::metadata(dir.path()).unwrap().uid();
+        let (stream, _) = UnixStream::pair().unwrap();
+        check_peer(&stream, uid).unwrap();
+        assert!(check_peer(&stream, uid.wrapping_add(1)).is_err());
+    }
+}
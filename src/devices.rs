@@ -0,0 +1,96 @@
+use crate::report;
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct DevicesReport {
+    host: String,
+    devices: Vec<DeviceReport>,
+}
+
+#[derive(Serialize)]
+struct DeviceReport {
+    index: usize,
+    name: String,
+    default: bool,
+    configs: Vec<ConfigReport>,
+}
+
+/// One supported stream configuration range of a device.
+#[derive(Serialize)]
+struct ConfigReport {
+    channels: u16,
+    min_sample_rate: u32,
+    max_sample_rate: u32,
+    sample_format: String,
+}
+
+/// List the audio input devices that `--audio-device` can select, with the
+/// configurations each supports.
+pub fn run() -> Result<()> {
+    let host = cpal::default_host();
+    let default_name = host
+        .default_input_device()
+        .and_then(|device| device.name().ok());
+
+    let mut devices = Vec::new();
+    for (index, device) in host
+        .input_devices()
+        .context("Failed to list input devices")?
+        .enumerate()
+    {
+        let name = device.name().unwrap_or_default();
+        // Some ALSA devices refuse to report their configs while another program uses them
+        let configs = match device.supported_input_configs() {
+            Ok(configs) => configs
+                .map(|config| ConfigReport {
+                    channels: config.channels(),
+                    min_sample_rate: config.min_sample_rate().0,
+                    max_sample_rate: config.max_sample_rate().0,
+                    sample_format: format!("{:?}", config.sample_format()),
+                })
+                .collect(),
+            Err(e) => {
+                log::debug!("No configs for {:?}: {}", name, e);
+                Vec::new()
+            }
+        };
+        devices.push(DeviceReport {
+            index,
+            default: default_name.as_ref() == Some(&name),
+            name,
+            configs,
+        });
+    }
+
+    let report = DevicesReport {
+        host: host.id().name().to_string(),
+        devices,
+    };
+    if report::json() {
+        return report::emit(&report);
+    }
+
+    if report.devices.is_empty() {
+        println!("No input devices found ({})", report.host);
+        return Ok(());
+    }
+    println!("Input devices ({}):", report.host);
+    for device in &report.devices {
+        let default = if device.default { " (default)" } else { "" };
+        println!("{:>3}: {}{}", device.index, device.name, default);
+        for config in &device.configs {
+            let rates = if config.min_sample_rate == config.max_sample_rate {
+                format!("{} Hz", config.min_sample_rate)
+            } else {
+                format!("{}-{} Hz", config.min_sample_rate, config.max_sample_rate)
+            };
+            println!(
+                "       {} ch, {}, {}",
+                config.channels, rates, config.sample_format
+            );
+        }
+    }
+    Ok(())
+}
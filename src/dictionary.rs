@@ -0,0 +1,280 @@
+//! `--dictionary`: replacement rules for names and jargon the engine keeps
+//! mishearing ("cue bernetes" → "Kubernetes"), applied to every transcript
+//! before post-processing. `--hints` is a simpler list of how words are
+//! pronounced and how they are written ("jason" → "JSON"), applied before
+//! the rules. Both files are reloaded when they change, so entries can be
+//! added without restarting.
+
+use anyhow::{Context, Result};
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleSpec>,
+}
+
+/// A `[[rule]]` table as written in the file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    /// Literal text, matched as whole words
+    from: Option<String>,
+    /// Regular expression; `to` can refer to its groups as `$1` or `${name}`
+    regex: Option<String>,
+    to: String,
+    #[serde(default)]
+    ignore_case: bool,
+}
+
+struct Rule {
+    pattern: Regex,
+    to: String,
+    /// Whether `$` in `to` refers to groups (regex rules) or is taken literally
+    expand: bool,
+}
+
+/// What a file passed as a dictionary contains.
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    /// `[[rule]]` tables (`--dictionary`)
+    Rules,
+    /// `spoken = "written"` pairs (`--hints`)
+    Hints,
+}
+
+pub struct Dictionary {
+    path: PathBuf,
+    format: Format,
+    /// Modification time of the file the rules were read from
+    modified: Option<SystemTime>,
+    rules: Vec<Rule>,
+}
+
+impl Dictionary {
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::load_as(path, Format::Rules)
+    }
+
+    pub fn load_hints(path: &Path) -> Result<Self> {
+        Self::load_as(path, Format::Hints)
+    }
+
+    fn load_as(path: &Path, format: Format) -> Result<Self> {
+        let modified = modified(path);
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let rules = match format {
+            Format::Rules => parse(&contents),
+            Format::Hints => parse_hints(&contents),
+        }
+        .with_context(|| format!("Invalid {:?} file {:?}", format, path))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            modified,
+            rules,
+        })
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Read the rules again if the file changed since they were loaded. An
+    /// invalid file keeps the previous rules.
+    pub fn reload_if_changed(&mut self) {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        // Also for a broken file, so it is only reported once
+        self.modified = modified;
+        match Self::load_as(&self.path, self.format) {
+            Ok(dictionary) => {
+                log::info!(
+                    "Reloaded {} dictionary rules from {:?}",
+                    dictionary.rule_count(),
+                    self.path
+                );
+                *self = dictionary;
+            }
+            Err(e) => log::warn!("Keeping the previous dictionary rules: {:#}", e),
+        }
+    }
+
+    /// Apply every rule to `text`, in the order they appear in the file.
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for rule in &self.rules {
+            text = if rule.expand {
+                rule.pattern
+                    .replace_all(&text, rule.to.as_str())
+                    .into_owned()
+            } else {
+                rule.pattern
+                    .replace_all(&text, NoExpand(&rule.to))
+                    .into_owned()
+            };
+        }
+        text
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn parse(contents: &str) -> Result<Vec<Rule>> {
+    let file: RulesFile = toml::from_str(contents)?;
+    file.rule
+        .into_iter()
+        .enumerate()
+        .map(|(i, spec)| {
+            let (pattern, expand) = match (spec.from, spec.regex) {
+                (Some(from), None) if !from.is_empty() => (literal_pattern(&from), false),
+                (None, Some(regex)) => (regex, true),
+                _ => anyhow::bail!("Rule {} needs either `from` or `regex`", i + 1),
+            };
+            let pattern = RegexBuilder::new(&pattern)
+                .case_insensitive(spec.ignore_case)
+                .build()
+                .with_context(|| format!("Rule {} has an invalid regex", i + 1))?;
+            Ok(Rule {
+                pattern,
+                to: spec.to,
+                expand,
+            })
+        })
+        .collect()
+}
+
+fn parse_hints(contents: &str) -> Result<Vec<Rule>> {
+    let hints: BTreeMap<String, String> = toml::from_str(contents)?;
+    let mut hints: Vec<(String, String)> = hints.into_iter().collect();
+    // Longer phrases first, so "engine x" isn't preempted by a hint for "engine"
+    hints.sort_by_key(|(spoken, _)| std::cmp::Reverse(spoken.split_whitespace().count()));
+    hints
+        .into_iter()
+        .map(|(spoken, written)| {
+            let words: Vec<&str> = spoken
+                .split(|c: char| c.is_whitespace() || c == '-')
+                .filter(|word| !word.is_empty())
+                .collect();
+            if words.is_empty() {
+                anyhow::bail!("Hint for {:?} has nothing to match", written);
+            }
+            // However the engine joined the words up: "engine x", "engine-x" or "enginex"
+            let phrase = words
+                .iter()
+                .map(|word| regex::escape(word))
+                .collect::<Vec<_>>()
+                .join(r"[\s-]*");
+            let pattern = format!(
+                "{}{}{}",
+                boundary(spoken.trim().chars().next()),
+                phrase,
+                boundary(spoken.trim().chars().last())
+            );
+            Ok(Rule {
+                pattern: RegexBuilder::new(&pattern).case_insensitive(true).build()?,
+                to: written,
+                expand: false,
+            })
+        })
+        .collect()
+}
+
+/// Match `from` literally, but not inside a longer word.
+fn literal_pattern(from: &str) -> String {
+    format!(
+        "{}{}{}",
+        boundary(from.chars().next()),
+        regex::escape(from),
+        boundary(from.chars().last())
+    )
+}
+
+/// A word boundary to put next to `c`, if it is a word character.
+fn boundary(c: Option<char>) -> &'static str {
+    match c {
+        Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(rules: &str, text: &str) -> String {
+        let dictionary = Dictionary {
+            path: PathBuf::new(),
+            format: Format::Rules,
+            modified: None,
+            rules: parse(rules).unwrap(),
+        };
+        dictionary.apply(text)
+    }
+
+    #[test]
+    fn test_literal_and_regex_rules() {
+        let rules = r#"
+            [[rule]]
+            from = "cue bernetes"
+            to = "Kubernetes"
+            ignore_case = true
+
+            [[rule]]
+            from = "cat"
+            to = "$cat"
+
+            [[rule]]
+            regex = '(\d+) percent'
+            to = "$1%"
+        "#;
+        assert_eq!(
+            apply(
+                rules,
+                "Cue Bernetes runs on 90 percent of cue bernetes nodes."
+            ),
+            "Kubernetes runs on 90% of Kubernetes nodes."
+        );
+        // Whole words only, and `$` in literal rules is kept as is
+        assert_eq!(apply(rules, "concatenate the cat"), "concatenate the $cat");
+        // Literal rules match case unless told otherwise
+        assert_eq!(apply(rules, "Cat"), "Cat");
+    }
+
+    #[test]
+    fn test_hints() {
+        let dictionary = Dictionary {
+            path: PathBuf::new(),
+            format: Format::Hints,
+            modified: None,
+            rules: parse_hints("jason = \"JSON\"\n\"engine x\" = \"nginx\"\nengine = \"motor\"")
+                .unwrap(),
+        };
+        assert_eq!(
+            dictionary.apply("Put the Jason config behind Engine-X, then engine x again."),
+            "Put the JSON config behind nginx, then nginx again."
+        );
+        assert_eq!(dictionary.apply("Jasonette enginex"), "Jasonette nginx");
+        assert_eq!(dictionary.apply("the engine"), "the motor");
+        assert!(parse_hints("\" - \" = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(parse("[[rule]]\nto = \"x\"").is_err());
+        assert!(parse("[[rule]]\nfrom = \"a\"\nregex = \"b\"\nto = \"x\"").is_err());
+        assert!(parse("[[rule]]\nregex = \"(\"\nto = \"x\"").is_err());
+        assert!(parse("[[rule]]\nfrom = \"a\"\nto = \"x\"\ncase = true").is_err());
+        assert_eq!(parse("").unwrap().len(), 0);
+    }
+}
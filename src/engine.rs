@@ -0,0 +1,409 @@
+//! The speech recognition engines recordings can be transcribed with.
+
+use crate::error::AppError;
+use crate::model;
+use crate::worker::Worker;
+use anyhow::Result;
+use clap::ValueEnum;
+use std::path::Path;
+#[cfg(feature = "moonshine")]
+use transcribe_rs::engines::moonshine::{MoonshineEngine, MoonshineModelParams};
+use transcribe_rs::engines::parakeet::{
+    ParakeetEngine, ParakeetInferenceParams, ParakeetModelParams, TimestampGranularity,
+};
+#[cfg(feature = "whisper")]
+use transcribe_rs::engines::whisper::{WhisperEngine, WhisperInferenceParams};
+use transcribe_rs::{TranscriptionEngine, TranscriptionResult};
+
+const GIB: u64 = 1 << 30;
+/// Below this much total RAM `auto` picks Moonshine.
+const AUTO_MIN_TOTAL_MEMORY: u64 = 3 * GIB;
+/// With this many cores or fewer `auto` picks Moonshine.
+const AUTO_MAX_SLOW_CORES: usize = 2;
+
+/// The `--engine` choice: a specific engine, or one picked for this machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum EngineChoice {
+    /// Pick Parakeet or a lightweight engine: a downloaded model, or one for the machine's RAM and CPU cores
+    Auto,
+    /// Parakeet TDT 0.6B v3 (25 European languages, ~1 GB RAM), in the --model-variant
+    #[default]
+    Parakeet,
+    /// Moonshine Tiny (English only, ~200 MB RAM) for Raspberry Pis and old laptops
+    Moonshine,
+    /// Whisper via whisper.cpp (99 languages), in the size set with --model-size
+    Whisper,
+}
+
+/// Parakeet model precision (`--model-variant`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ParakeetVariant {
+    /// Quantized weights, ~478 MB download
+    #[default]
+    Int8,
+    /// Full-precision weights, ~2.5 GB download and more RAM, for a little more accuracy
+    Fp32,
+}
+
+/// Whisper model size (`--model-size`): larger is more accurate but slower.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum WhisperSize {
+    /// ~75 MB, the lightweight multilingual option
+    Tiny,
+    /// ~142 MB
+    Base,
+    /// ~466 MB
+    #[default]
+    Small,
+    /// ~1.5 GB
+    Medium,
+    /// ~1.6 GB, close to large-v3 accuracy at a fraction of the decoding cost
+    LargeV3Turbo,
+}
+
+impl EngineChoice {
+    /// Resolve `auto` to a concrete engine. A given model directory decides by
+    /// its contents, then a model that is already downloaded, then the
+    /// hardware. The decision is logged.
+    pub fn resolve(
+        self,
+        size: WhisperSize,
+        variant: ParakeetVariant,
+        model_path: Option<&Path>,
+    ) -> Result<EngineKind> {
+        let kind = match self {
+            EngineChoice::Parakeet => EngineKind::Parakeet(variant),
+            EngineChoice::Moonshine => EngineKind::Moonshine,
+            EngineChoice::Whisper => EngineKind::Whisper(size),
+            EngineChoice::Auto => {
+                if let Some(kind) = model_path.and_then(model::detect_engine) {
+                    log::info!("Engine: {} (matches the model directory)", kind.name());
+                    kind
+                } else if let Some(kind) =
+                    lightweight().and_then(|light| downloaded_choice(variant, light))
+                {
+                    // Rather than another download, whatever the hardware
+                    log::info!("Engine: {} (its model is downloaded)", kind.name());
+                    kind
+                } else if let Some(light) = lightweight() {
+                    let (kind, reason) = select_for_hardware(&Hardware::detect(), variant, light);
+                    log::info!("Engine: {} ({})", kind.name(), reason);
+                    kind
+                } else {
+                    log::info!("Engine: Parakeet v3 (the only engine in this build)");
+                    EngineKind::Parakeet(variant)
+                }
+            }
+        };
+        if !kind.compiled_in() {
+            return Err(kind.not_compiled_in().into());
+        }
+        Ok(kind)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineKind {
+    Parakeet(ParakeetVariant),
+    Moonshine,
+    Whisper(WhisperSize),
+}
+
+impl EngineKind {
+    pub const ALL: [EngineKind; 8] = [
+        EngineKind::Parakeet(ParakeetVariant::Int8),
+        EngineKind::Parakeet(ParakeetVariant::Fp32),
+        EngineKind::Moonshine,
+        EngineKind::Whisper(WhisperSize::Tiny),
+        EngineKind::Whisper(WhisperSize::Base),
+        EngineKind::Whisper(WhisperSize::Small),
+        EngineKind::Whisper(WhisperSize::Medium),
+        EngineKind::Whisper(WhisperSize::LargeV3Turbo),
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EngineKind::Parakeet(ParakeetVariant::Int8) => "Parakeet v3",
+            EngineKind::Parakeet(ParakeetVariant::Fp32) => "Parakeet v3 FP32",
+            EngineKind::Moonshine => "Moonshine Tiny",
+            EngineKind::Whisper(WhisperSize::Tiny) => "Whisper Tiny",
+            EngineKind::Whisper(WhisperSize::Base) => "Whisper Base",
+            EngineKind::Whisper(WhisperSize::Small) => "Whisper Small",
+            EngineKind::Whisper(WhisperSize::Medium) => "Whisper Medium",
+            EngineKind::Whisper(WhisperSize::LargeV3Turbo) => "Whisper Large v3 Turbo",
+        }
+    }
+
+    /// The cargo feature that builds this engine in, if it is optional.
+    fn feature(self) -> Option<&'static str> {
+        match self {
+            EngineKind::Parakeet(_) => None,
+            EngineKind::Moonshine => Some("moonshine"),
+            EngineKind::Whisper(_) => Some("whisper"),
+        }
+    }
+
+    fn compiled_in(self) -> bool {
+        match self {
+            EngineKind::Parakeet(_) => true,
+            EngineKind::Moonshine => cfg!(feature = "moonshine"),
+            EngineKind::Whisper(_) => cfg!(feature = "whisper"),
+        }
+    }
+
+    fn not_compiled_in(self) -> AppError {
+        AppError::FeatureMissing {
+            name: self.name(),
+            feature: self.feature().unwrap_or_default(),
+        }
+    }
+}
+
+/// The engine `auto` falls back to where Parakeet is too heavy: Moonshine
+/// Tiny, or Whisper Tiny in builds without Moonshine.
+fn lightweight() -> Option<EngineKind> {
+    [
+        EngineKind::Moonshine,
+        EngineKind::Whisper(WhisperSize::Tiny),
+    ]
+    .into_iter()
+    .find(|kind| kind.compiled_in())
+}
+
+/// The engine `auto` picks without looking at the hardware, because only
+/// its model is downloaded.
+fn downloaded_choice(variant: ParakeetVariant, light: EngineKind) -> Option<EngineKind> {
+    let parakeet = EngineKind::Parakeet(variant);
+    match (model::is_downloaded(parakeet), model::is_downloaded(light)) {
+        (true, false) => Some(parakeet),
+        (false, true) => Some(light),
+        _ => None,
+    }
+}
+
+/// What `auto` bases its decision on. Only what stays the same between runs,
+/// so the same machine gets the same engine (and model) every time.
+#[derive(Debug, Default)]
+struct Hardware {
+    total_memory: Option<u64>,
+    cores: usize,
+    providers: Vec<&'static str>,
+}
+
+impl Hardware {
+    fn detect() -> Self {
+        let (total_memory, _) = memory_bytes();
+        Self {
+            total_memory,
+            cores: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            providers: model::available_providers(),
+        }
+    }
+}
+
+fn select_for_hardware(
+    hw: &Hardware,
+    variant: ParakeetVariant,
+    light: EngineKind,
+) -> (EngineKind, String) {
+    let gib = |bytes: u64| bytes as f64 / GIB as f64;
+    // Both engines currently run on the CPU, GPU providers are only reported
+    let summary = format!(
+        "{} cores, {} RAM, providers: {}",
+        hw.cores,
+        hw.total_memory
+            .map_or("unknown".into(), |b| format!("{:.1} GiB", gib(b))),
+        hw.providers.join(", ")
+    );
+
+    let reason = if hw.total_memory.is_some_and(|b| b < AUTO_MIN_TOTAL_MEMORY) {
+        Some("little RAM")
+    } else if hw.cores <= AUTO_MAX_SLOW_CORES {
+        Some("few CPU cores")
+    } else {
+        None
+    };
+    match reason {
+        Some(reason) => (light, format!("{}: {}", reason, summary)),
+        None => (EngineKind::Parakeet(variant), summary),
+    }
+}
+
+/// Total and available physical memory.
+#[cfg(target_os = "linux")]
+pub fn memory_bytes() -> (Option<u64>, Option<u64>) {
+    let Ok(meminfo) = std::fs::read_to_string("/proc/meminfo") else {
+        return (None, None);
+    };
+    let field = |name: &str| -> Option<u64> {
+        let line = meminfo.lines().find(|l| l.starts_with(name))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    };
+    (field("MemTotal:"), field("MemAvailable:"))
+}
+
+#[cfg(target_os = "macos")]
+pub fn memory_bytes() -> (Option<u64>, Option<u64>) {
+    let total = std::process::Command::new("sysctl")
+        .args(["-n", "hw.memsize"])
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse().ok());
+    // macOS compresses and swaps memory aggressively, free memory isn't meaningful
+    (total, None)
+}
+
+/// A loaded model of one of the supported engines.
+pub enum Engine {
+    Parakeet(ParakeetEngine),
+    #[cfg(feature = "moonshine")]
+    Moonshine(MoonshineEngine),
+    /// With the parameters every transcription uses
+    #[cfg(feature = "whisper")]
+    Whisper(WhisperEngine, WhisperInferenceParams),
+    /// Any of the above, in a child process (`--isolate-engine`)
+    Isolated(Worker),
+}
+
+impl Engine {
+    /// Load the model at `model_path`: a directory for the ONNX engines, the
+    /// GGML file for Whisper.
+    pub fn load(kind: EngineKind, model_path: &Path) -> Result<Self> {
+        let engine = match kind {
+            EngineKind::Parakeet(variant) => {
+                let params = match variant {
+                    ParakeetVariant::Int8 => ParakeetModelParams::int8(),
+                    ParakeetVariant::Fp32 => ParakeetModelParams::fp32(),
+                };
+                let mut engine = ParakeetEngine::new();
+                engine
+                    .load_model_with_params(model_path, params)
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+                Engine::Parakeet(engine)
+            }
+            #[cfg(feature = "moonshine")]
+            EngineKind::Moonshine => {
+                let mut engine = MoonshineEngine::new();
+                engine
+                    .load_model_with_params(model_path, MoonshineModelParams::tiny())
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+                Engine::Moonshine(engine)
+            }
+            #[cfg(feature = "whisper")]
+            EngineKind::Whisper(_) => {
+                let mut engine = WhisperEngine::new();
+                engine
+                    .load_model(model_path)
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+                Engine::Whisper(engine, WhisperInferenceParams::default())
+            }
+            #[allow(unreachable_patterns)]
+            _ => return Err(kind.not_compiled_in().into()),
+        };
+        Ok(engine)
+    }
+
+    /// Transcribe 16 kHz mono samples. Where the engine reports timings, the
+    /// result has sentence-level segments (Moonshine has none).
+    pub fn transcribe_samples(&mut self, samples: Vec<f32>) -> Result<TranscriptionResult> {
+        let result = match self {
+            Engine::Parakeet(engine) => {
+                let params = ParakeetInferenceParams {
+                    timestamp_granularity: TimestampGranularity::Segment,
+                };
+                engine.transcribe_samples(samples, Some(params))
+            }
+            #[cfg(feature = "moonshine")]
+            Engine::Moonshine(engine) => engine.transcribe_samples(samples, None),
+            #[cfg(feature = "whisper")]
+            Engine::Whisper(engine, params) => {
+                engine.transcribe_samples(samples, Some(params.clone()))
+            }
+            Engine::Isolated(worker) => return worker.transcribe_samples(samples),
+        };
+        result.map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    /// Bias decoding towards the names and jargon in `prompt`. Only Whisper
+    /// supports this; other engines ignore it with a warning.
+    pub fn set_initial_prompt(&mut self, prompt: Option<String>) {
+        match self {
+            #[cfg(feature = "whisper")]
+            Engine::Whisper(_, params) => params.initial_prompt = prompt,
+            // The worker warns if its engine doesn't support them
+            Engine::Isolated(worker) => worker.set_initial_prompt(prompt),
+            _ => {
+                if prompt.is_some() {
+                    log::warn!("Only Whisper supports initial prompts, ignoring it");
+                }
+            }
+        }
+    }
+
+    pub fn unload_model(&mut self) {
+        match self {
+            Engine::Parakeet(engine) => engine.unload_model(),
+            #[cfg(feature = "moonshine")]
+            Engine::Moonshine(engine) => engine.unload_model(),
+            #[cfg(feature = "whisper")]
+            Engine::Whisper(engine, _) => engine.unload_model(),
+            Engine::Isolated(worker) => worker.unload_model(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hardware(total_gib: u64, cores: usize) -> Hardware {
+        Hardware {
+            total_memory: Some(total_gib * GIB),
+            cores,
+            providers: vec!["CPUExecutionProvider"],
+        }
+    }
+
+    #[test]
+    fn test_auto_prefers_parakeet_on_capable_machines() {
+        assert_eq!(
+            select_for_hardware(
+                &hardware(16, 8),
+                ParakeetVariant::Int8,
+                EngineKind::Moonshine
+            )
+            .0,
+            EngineKind::Parakeet(ParakeetVariant::Int8)
+        );
+        // Unknown memory shouldn't rule Parakeet out
+        let unknown = Hardware {
+            cores: 8,
+            ..Hardware::default()
+        };
+        assert_eq!(
+            select_for_hardware(&unknown, ParakeetVariant::Int8, EngineKind::Moonshine).0,
+            EngineKind::Parakeet(ParakeetVariant::Int8)
+        );
+    }
+
+    #[test]
+    fn test_auto_falls_back_to_a_lightweight_engine() {
+        assert_eq!(
+            select_for_hardware(
+                &hardware(2, 4),
+                ParakeetVariant::Int8,
+                EngineKind::Moonshine
+            )
+            .0,
+            EngineKind::Moonshine
+        );
+        let tiny = EngineKind::Whisper(WhisperSize::Tiny);
+        assert_eq!(
+            select_for_hardware(&hardware(8, 2), ParakeetVariant::Int8, tiny).0,
+            tiny
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use crate::i18n::{tr, trf, Msg};
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Failures that wrappers and service managers may want to handle specifically.
+/// Each maps to its own process exit code; anything else exits with 1.
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("{}", tr(Msg::NoMicrophone))]
+    NoMicrophone,
+    #[error("{}", trf(Msg::InputDeviceNotFound, &[.0]))]
+    InputDeviceNotFound(String),
+    #[error("{}", trf(Msg::NoPermission, &[.0]))]
+    NoPermission(String),
+    #[error("{}", trf(Msg::ModelMissing, &[&format!("{:?}", .0)]))]
+    ModelMissing(PathBuf),
+    #[error("{}", trf(Msg::OutputToolMissing, &[tool, package]))]
+    OutputToolMissing {
+        tool: &'static str,
+        package: &'static str,
+    },
+    #[error("{}", trf(Msg::FeatureMissing, &[name, feature]))]
+    FeatureMissing {
+        name: &'static str,
+        feature: &'static str,
+    },
+    #[error("{}", trf(Msg::HotkeyConflict, &[first, second, hotkey]))]
+    HotkeyConflict {
+        first: String,
+        second: String,
+        hotkey: String,
+    },
+}
+
+impl AppError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AppError::NoMicrophone | AppError::InputDeviceNotFound(_) => 3,
+            AppError::NoPermission(_) => 4,
+            AppError::ModelMissing(_) => 5,
+            AppError::OutputToolMissing { .. } => 6,
+            AppError::FeatureMissing { .. } => 7,
+            AppError::HotkeyConflict { .. } => 8,
+        }
+    }
+}
+
+/// Exit code for an error, based on the first [`AppError`] in its chain.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<AppError>())
+        .map(AppError::exit_code)
+        .unwrap_or(1)
+}
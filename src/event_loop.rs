@@ -1,133 +1,2085 @@
-use crate::audio::AudioRecorder;
-use crate::output::{output_text, OutputMode};
-use crate::post_process::PostProcessor;
-use anyhow::Result;
-use hotkey_listener::{HotkeyEvent, HotkeyListenerHandle};
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::audio::{self, AudioRecorder};
+use crate::compose;
+use crate::console;
+use crate::control::ControlCommand;
+use crate::correction::{self, Edit};
+use crate::cues::{self, Cue};
+use crate::daemon;
+use crate::dictionary::Dictionary;
+use crate::engine::{Engine, EngineKind};
+use crate::feedback::{self, Rating, TranscriptInfo};
+use crate::headset;
+use crate::history;
+use crate::hotkey_profiles::Profile;
+use crate::i18n::{tr, trf, Msg};
+use crate::input::HotkeyEvent;
+use crate::model;
+use crate::output::{self, output_text, AutoSubmit, OutputOptions};
+use crate::post_process::{self, PostProcessor};
+use crate::reminder::{self, Reminder, ReminderTarget};
+use crate::status_file::StatusFile;
+use crate::title;
+use crate::todo::{self, TodoTarget};
+use crate::worker;
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use transcribe_rs::engines::parakeet::ParakeetEngine;
-use transcribe_rs::TranscriptionEngine;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+/// Options controlling the record/transcribe loop.
+pub struct Settings {
+    pub output: OutputOptions,
+    /// Press Enter after typing a transcript
+    pub auto_submit: AutoSubmit,
+    /// Send transcripts through the post-processor before output
+    pub post_process: bool,
+    /// Have the post-processor write in this language whatever was spoken
+    pub output_language: Option<String>,
+    /// Pronunciation respellings applied before the dictionary
+    pub hints: Option<Dictionary>,
+    /// Replacement rules applied to transcripts before post-processing
+    pub dictionary: Option<Dictionary>,
+    /// Engine context for recordings without a profile prompt
+    pub initial_prompt: Option<String>,
+    /// Listener index of each profile's hotkey, and the profile
+    pub profiles: Vec<(usize, Profile)>,
+    /// Transcribe in chunks once a recording grows beyond this many seconds
+    pub split_after: Option<u64>,
+    /// Transcribe long recordings in the background at pauses and join the
+    /// chunks up on release
+    pub stream: bool,
+    /// Stop toggled (hands-free) recordings after this many seconds of silence
+    pub auto_stop: Option<f64>,
+    /// Stop any recording that runs this long, e.g. with a stuck hotkey
+    pub max_duration: Option<Duration>,
+    /// Give up on a transcription or output that takes longer than this
+    pub watchdog: Option<Duration>,
+    /// Presses shorter than this are discarded instead of transcribed
+    pub min_hold: Duration,
+    /// A press of the hotkey starts recording and the next one stops it, instead
+    /// of recording while it is held
+    pub latch: bool,
+    /// Toggle recording with headset play/pause buttons
+    pub headset_button: bool,
+    /// Accept ratings of transcriptions (hotkeys 1 and 2 rate good and bad)
+    pub feedback: bool,
+    /// Treat "replace X with Y" as a correction of the previous output
+    pub spoken_corrections: bool,
+    /// Collect transcripts in a buffer that is output on command
+    pub compose: bool,
+    /// Accept commands over the `ctl` socket
+    pub daemon: bool,
+    /// Input devices the device hotkey cycles through
+    pub devices: Vec<String>,
+    /// Listener index of the hotkey that switches to the next of `devices`
+    pub device_key: Option<usize>,
+    /// Append transcriptions to the history file
+    pub history: bool,
+    /// Listener index of the hotkey that outputs the last transcription again
+    pub repeat_key: Option<usize>,
+    /// Listener index of the `--urgent-key` hotkey, whose recordings skip
+    /// post-processing and anything waiting for it
+    pub urgent_key: Option<usize>,
+    /// Show the recording state in the terminal title
+    pub terminal_title: bool,
+    /// Keep the state in this JSON file for status bars
+    pub status_file: Option<PathBuf>,
+    /// Where "To-do:" dictations go instead of being output
+    pub todo: Option<TodoTarget>,
+    /// Where "Remind me ..." dictations go instead of being output
+    pub reminders: Option<ReminderTarget>,
+    /// Unload the model after this long without a recording, and load it
+    /// again on the next one
+    pub unload_after: Option<Duration>,
+    /// The engine and its model directory, for loading the model again
+    pub model: (EngineKind, PathBuf),
+    /// Load the model in an engine worker process
+    pub isolate_engine: bool,
+    /// Beep when recording starts and stops and when a transcription is done
+    pub beep: bool,
+    /// Show transcripts and failures as desktop notifications
+    pub notify: bool,
+}
+
+/// How often the loop wakes up without an event, e.g. to split long recordings.
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+/// With `--stream`, audio is handed off at the first pause after this many seconds
+const STREAM_MIN_SECS: f64 = 5.0;
+/// ... or after this many seconds without one
+const STREAM_MAX_SECS: f64 = 10.0;
+/// Silence that counts as a pause to cut a streamed chunk at
+const STREAM_PAUSE_SECS: f64 = 0.3;
+
+/// Listener indices of the `--feedback-keys` hotkeys.
+pub const GOOD_KEY: usize = 1;
+pub const BAD_KEY: usize = 2;
+
+/// How long `--auto-submit confirm` waits for the confirming hotkey tap.
+const SUBMIT_CONFIRM_WINDOW: Duration = Duration::from_secs(5);
+
+/// Anything the loop reacts to.
+#[derive(Debug, Clone)]
+enum Event {
+    Hotkey(HotkeyEvent),
+    Control(ControlCommand),
+    Tick,
+    /// A transcription running in the background finished
+    Transcribed(Result<String, String>),
+    /// Background post-processing finished, or its task failed (`None`)
+    PostProcessed(Option<Result<String, String>>),
+}
+
+/// What the loop does in response to an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    /// Start recording with the profile at this index of `Settings::profiles`
+    Start(Option<usize>),
+    /// Start a recording that skips post-processing (`--urgent-key`)
+    StartUrgent,
+    Stop,
+    /// Stop a recording that reached `--max-duration`
+    Cutoff,
+    /// Stop and discard a press that was too short
+    Cancel,
+    Split,
+    /// Transcribe the audio so far in the background while recording continues
+    StreamChunk,
+    /// Free the model's memory until the next recording (`--unload-after`)
+    UnloadModel,
+    /// Press Enter to submit the transcript that was just typed
+    Submit,
+    /// Give up waiting for a submit confirmation
+    DropSubmit,
+    Rate(Rating),
+    OutputCompose,
+    DiscardCompose,
+    TogglePostProcess,
+    /// Record from the device matching this selector from now on
+    SetDevice(String),
+    /// Switch to the next of the configured devices
+    CycleDevice,
+    /// Output the last transcription again
+    RepeatLast,
+    /// Output a transcript that was transcribed in the background
+    OutputTranscription(Result<String, String>),
+    /// Give up on a background transcription that exceeded `--watchdog`
+    TranscriptionStuck,
+    /// Output a transcript that was post-processed in the background
+    OutputPostProcessed(Option<Result<String, String>>),
+    Status,
+    Quit,
+    Ignore,
+}
+
+/// Snapshot of the recording state that decisions are based on.
+#[derive(Debug, Clone, Copy, Default)]
+struct RecordingState {
+    is_recording: bool,
+    /// Time since recording started
+    held: Duration,
+    /// Seconds of audio waiting to be transcribed
+    buffered_secs: f64,
+    /// Time since a transcript was typed that awaits a submit confirmation
+    submit_pending: Option<Duration>,
+    /// Whether the recording was toggled on rather than started by holding the hotkey
+    hands_free: bool,
+    /// Listener index of the hotkey whose release stops the recording
+    key: usize,
+    /// Seconds of silence at the end of the recording (only measured with
+    /// `--auto-stop` or `--stream`)
+    silence_secs: f64,
+    /// Time since the engine was last used, or `None` while its model is unloaded
+    engine_idle: Option<Duration>,
+    /// Time since a recording started being transcribed in the background
+    /// (with `--urgent-key`)
+    transcribing: Option<Duration>,
+}
+
+fn decide(event: Event, state: RecordingState, settings: &Settings) -> Action {
+    match event {
+        Event::Hotkey(HotkeyEvent::Pressed(key))
+            if !state.is_recording && records(key, settings) =>
+        {
+            match state.submit_pending {
+                Some(waited) if waited < SUBMIT_CONFIRM_WINDOW => Action::Submit,
+                _ => Action::Start(profile_for(key, settings)),
+            }
+        }
+        Event::Hotkey(HotkeyEvent::Pressed(key))
+            if !state.is_recording && Some(key) == settings.urgent_key =>
+        {
+            Action::StartUrgent
+        }
+        Event::Hotkey(HotkeyEvent::Pressed(key))
+            if state.is_recording && settings.latch && key == state.key =>
+        {
+            Action::Stop
+        }
+        Event::Hotkey(HotkeyEvent::Released(key))
+            if state.is_recording && !settings.latch && key == state.key =>
+        {
+            if state.held < settings.min_hold {
+                Action::Cancel
+            } else {
+                Action::Stop
+            }
+        }
+        Event::Hotkey(HotkeyEvent::Pressed(GOOD_KEY)) if settings.feedback => {
+            Action::Rate(Rating::Good)
+        }
+        Event::Hotkey(HotkeyEvent::Pressed(BAD_KEY)) if settings.feedback => {
+            Action::Rate(Rating::Bad)
+        }
+        Event::Hotkey(HotkeyEvent::Pressed(key)) if Some(key) == settings.device_key => {
+            Action::CycleDevice
+        }
+        Event::Hotkey(HotkeyEvent::Pressed(key)) if Some(key) == settings.repeat_key => {
+            Action::RepeatLast
+        }
+        Event::Hotkey(_) => Action::Ignore,
+        Event::Control(ControlCommand::ToggleRecording) if state.is_recording => Action::Stop,
+        Event::Control(ControlCommand::ToggleRecording) => Action::Start(None),
+        Event::Control(ControlCommand::StartRecording) if !state.is_recording => {
+            Action::Start(None)
+        }
+        Event::Control(ControlCommand::StopRecording) if state.is_recording => Action::Stop,
+        Event::Control(ControlCommand::StartRecording | ControlCommand::StopRecording) => {
+            Action::Ignore
+        }
+        Event::Control(ControlCommand::TogglePostProcess) => Action::TogglePostProcess,
+        Event::Control(ControlCommand::Rate(rating)) => Action::Rate(rating),
+        Event::Control(ControlCommand::OutputCompose) => Action::OutputCompose,
+        Event::Control(ControlCommand::DiscardCompose) => Action::DiscardCompose,
+        Event::Control(ControlCommand::SetDevice(selector)) => Action::SetDevice(selector),
+        Event::Control(ControlCommand::RepeatLast) => Action::RepeatLast,
+        Event::Control(ControlCommand::Status) => Action::Status,
+        Event::Control(ControlCommand::Quit) => Action::Quit,
+        Event::Transcribed(result) => Action::OutputTranscription(result),
+        Event::PostProcessed(result) => Action::OutputPostProcessed(result),
+        Event::Tick
+            if settings
+                .watchdog
+                .is_some_and(|limit| state.transcribing >= Some(limit)) =>
+        {
+            Action::TranscriptionStuck
+        }
+        Event::Tick if state.submit_pending >= Some(SUBMIT_CONFIRM_WINDOW) => Action::DropSubmit,
+        Event::Tick
+            if state.is_recording
+                && settings
+                    .max_duration
+                    .is_some_and(|limit| state.held >= limit) =>
+        {
+            Action::Cutoff
+        }
+        Event::Tick
+            if state.is_recording
+                && state.hands_free
+                && settings
+                    .auto_stop
+                    .is_some_and(|limit| state.silence_secs >= limit) =>
+        {
+            Action::Stop
+        }
+        Event::Tick
+            if state.is_recording
+                && settings.stream
+                && state.buffered_secs >= STREAM_MIN_SECS
+                && (state.silence_secs >= STREAM_PAUSE_SECS
+                    || state.buffered_secs >= STREAM_MAX_SECS) =>
+        {
+            Action::StreamChunk
+        }
+        Event::Tick
+            if !state.is_recording
+                && state.transcribing.is_none()
+                && settings
+                    .unload_after
+                    .is_some_and(|limit| state.engine_idle >= Some(limit)) =>
+        {
+            Action::UnloadModel
+        }
+        Event::Tick => match settings.split_after {
+            // Long recordings are transcribed in chunks to keep memory bounded
+            Some(limit) if state.is_recording && state.buffered_secs >= limit as f64 => {
+                Action::Split
+            }
+            _ => Action::Ignore,
+        },
+    }
+}
+
+/// Whether `key` starts recordings: the main hotkey or a profile's.
+fn records(key: usize, settings: &Settings) -> bool {
+    key == 0 || profile_for(key, settings).is_some()
+}
+
+/// The profile bound to the hotkey at listener index `key`.
+fn profile_for(key: usize, settings: &Settings) -> Option<usize> {
+    settings.profiles.iter().position(|(k, _)| *k == key)
+}
 
 pub async fn run(
-    engine: ParakeetEngine,
-    handle: HotkeyListenerHandle,
-    output_mode: OutputMode,
-    post_processor: Option<PostProcessor>,
+    engine: Engine,
+    hotkeys: std::sync::mpsc::Receiver<HotkeyEvent>,
+    recorder: AudioRecorder,
+    post_processor: PostProcessor,
+    settings: Settings,
 ) -> Result<()> {
-    let running = Arc::new(AtomicBool::new(true));
-    let r = Arc::clone(&running);
-    ctrlc::set_handler(move || {
-        r.store(false, Ordering::SeqCst);
-    })?;
+    let (tx, commands) = mpsc::unbounded_channel();
+    let status = daemon::SharedStatus::default();
+    if settings.daemon {
+        daemon::serve(tx.clone(), Arc::clone(&status)).await?;
+    }
+    console::spawn(tx.clone());
+    if settings.headset_button {
+        headset::spawn(tx)?;
+        println!("{}", tr(Msg::HeadsetHint));
+    }
 
-    run_event_loop(engine, handle, output_mode, post_processor, running).await
+    let status_file = settings.status_file.clone().map(StatusFile::new);
+    let profile_starts = vec![Vec::new(); settings.profiles.len()];
+    let mut pipeline = Pipeline {
+        engine: Arc::new(std::sync::Mutex::new(engine)),
+        recorder,
+        post_processor: Arc::new(post_processor),
+        settings,
+        is_recording: false,
+        hands_free: false,
+        urgent: false,
+        profile: None,
+        profile_starts,
+        pressed_at: Instant::now(),
+        typed: false,
+        submit_pending_since: None,
+        last_transcript: None,
+        last_output: None,
+        compose: compose::Buffer::default(),
+        status,
+        status_file,
+        stream_chunks: Vec::new(),
+        split_text: String::new(),
+        engine_used: Some(Instant::now()),
+        engine_loading: None,
+        transcription: None,
+        urgent_engine: None,
+        urgent_engine_loading: None,
+        prompt_profile: None,
+        post_processing: None,
+    };
+    let daemon = pipeline.settings.daemon;
+    let terminal_title = pipeline.settings.terminal_title;
+    let status_file = pipeline.settings.status_file.clone();
+    if terminal_title {
+        title::save();
+    }
+    pipeline.show_state(title::State::Idle);
+    let result = run_event_loop(pipeline, forward_hotkeys(hotkeys), commands).await;
+    if daemon {
+        daemon::cleanup();
+    }
+    if terminal_title {
+        title::restore();
+    }
+    // Bars shouldn't show a stale state once the loop is gone
+    if let Some(path) = status_file {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+/// Move hotkey events from the listener's blocking channel onto the runtime.
+fn forward_hotkeys(
+    hotkeys: std::sync::mpsc::Receiver<HotkeyEvent>,
+) -> UnboundedReceiver<HotkeyEvent> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = hotkeys.recv() {
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    rx
 }
 
 async fn run_event_loop(
-    engine: ParakeetEngine,
-    handle: HotkeyListenerHandle,
-    output_mode: OutputMode,
-    post_processor: Option<PostProcessor>,
-    running: Arc<AtomicBool>,
+    mut pipeline: Pipeline,
+    mut hotkeys: UnboundedReceiver<HotkeyEvent>,
+    mut commands: UnboundedReceiver<ControlCommand>,
 ) -> Result<()> {
-    let engine = Arc::new(std::sync::Mutex::new(engine));
-    let mut recorder = AudioRecorder::new();
-    let mut is_recording = false;
-
-    println!("Press Ctrl+C to exit.");
-
-    while running.load(Ordering::SeqCst) {
-        match handle.recv_timeout(Duration::from_millis(100)) {
-            Ok(event) => match event {
-                HotkeyEvent::Pressed(0) if !is_recording => {
-                    println!("Recording...");
-                    if let Err(e) = recorder.start() {
-                        log::error!("Failed to start recording: {}", e);
-                        continue;
-                    }
-                    is_recording = true;
-                }
-                HotkeyEvent::Released(0) if is_recording => {
-                    // Continue recording briefly to capture trailing audio
-                    tokio::time::sleep(Duration::from_millis(250)).await;
-                    println!("Transcribing...");
-                    is_recording = false;
-                    handle_transcription(
-                        &mut recorder,
-                        Arc::clone(&engine),
-                        output_mode,
-                        &post_processor,
-                    )
-                    .await;
+    println!("{}", tr(Msg::PressCtrlC));
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut ticker = tokio::time::interval(TICK_INTERVAL);
+
+    loop {
+        let event = tokio::select! {
+            _ = &mut ctrl_c => break,
+            event = hotkeys.recv() => match event {
+                Some(event) => Event::Hotkey(event),
+                None => {
+                    log::debug!("Keyboard listener disconnected");
+                    break;
                 }
-                _ => {}
             },
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
-                // No event, continue loop
+            Some(command) = commands.recv() => Event::Control(command),
+            _ = ticker.tick() => Event::Tick,
+            Some(result) = async {
+                match &mut pipeline.transcription {
+                    Some(pending) => Some(transcribed(&mut pending.task).await),
+                    None => None,
+                }
+            }, if pipeline.transcription.is_some() => Event::Transcribed(result),
+            Some(processed) = async {
+                match &mut pipeline.post_processing {
+                    Some((task, _)) => Some(post_processed(task).await),
+                    None => None,
+                }
+            }, if pipeline.post_processing.is_some() => Event::PostProcessed(processed),
+        };
+
+        // Latched recordings run on their own like toggled ones, e.g. for --auto-stop
+        let hands_free = matches!(event, Event::Control(_)) || pipeline.settings.latch;
+        match decide(event, pipeline.state(), &pipeline.settings) {
+            Action::Start(profile) => pipeline.start_recording(hands_free, profile, false),
+            Action::StartUrgent => pipeline.start_recording(hands_free, None, true),
+            Action::Stop => pipeline.stop_recording().await,
+            Action::Cutoff => {
+                println!(
+                    "{}",
+                    trf(
+                        Msg::MaxDurationReached,
+                        &[&format!("{:.0?}", pipeline.state().held)]
+                    )
+                );
+                pipeline.stop_recording().await;
             }
-            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
-                log::debug!("Keyboard listener disconnected");
-                break;
+            Action::Cancel => {
+                println!(
+                    "{}",
+                    trf(
+                        Msg::ReleasedEarly,
+                        &[&format!("{:.0?}", pipeline.state().held)]
+                    )
+                );
+                pipeline.cancel_recording();
+            }
+            Action::Split => pipeline.split_recording().await,
+            Action::StreamChunk => pipeline.stream_chunk().await,
+            Action::UnloadModel => pipeline.unload_engine().await,
+            Action::Submit => pipeline.submit().await,
+            Action::DropSubmit => {
+                println!("{}", tr(Msg::SubmitDropped));
+                pipeline.submit_pending_since = None;
+            }
+            Action::Rate(rating) => pipeline.rate(rating),
+            Action::OutputCompose => pipeline.output_compose().await,
+            Action::DiscardCompose => pipeline.discard_compose(),
+            Action::TogglePostProcess => pipeline.toggle_post_process(),
+            Action::SetDevice(selector) => {
+                if let Err(e) = pipeline.set_device(selector) {
+                    log::error!("Failed to switch input device: {:#}", e);
+                }
             }
+            Action::CycleDevice => pipeline.cycle_device(),
+            Action::RepeatLast => pipeline.repeat_last().await,
+            Action::OutputTranscription(result) => pipeline.finish_transcription(result).await,
+            Action::TranscriptionStuck => pipeline.give_up_transcription(),
+            Action::OutputPostProcessed(result) => pipeline.finish_post_processing(result).await,
+            Action::Status => pipeline.print_status(),
+            Action::Quit => break,
+            Action::Ignore => {}
         }
     }
 
-    engine.lock().unwrap().unload_model();
-    println!("\nExiting.");
+    pipeline.flush_transcription().await;
+    pipeline.flush_post_processing().await;
+    // Skipped if the engine is stuck in a call the watchdog gave up on
+    if let Ok(mut engine) = pipeline.engine.try_lock() {
+        engine.unload_model();
+    }
+    println!("\n{}", tr(Msg::Exiting));
     Ok(())
 }
 
-async fn handle_transcription(
-    recorder: &mut AudioRecorder,
-    engine: Arc<std::sync::Mutex<ParakeetEngine>>,
-    output_mode: OutputMode,
-    post_processor: &Option<PostProcessor>,
-) {
-    match recorder.stop().await {
-        Ok(wav_path) => {
-            let start = Instant::now();
-            let path = wav_path.clone();
-
-            // Run sync transcription in blocking task
-            let result = tokio::task::spawn_blocking(move || {
-                let mut engine = engine.lock().unwrap();
-                engine
-                    .transcribe_file(&path, None)
-                    .map_err(|e| e.to_string())
+/// Recording state plus everything needed to turn a recording into output.
+struct Pipeline {
+    engine: Arc<std::sync::Mutex<Engine>>,
+    recorder: AudioRecorder,
+    post_processor: Arc<PostProcessor>,
+    settings: Settings,
+    is_recording: bool,
+    /// Whether the current recording was toggled on (console, headset button)
+    hands_free: bool,
+    /// Whether the current recording was started with the `--urgent-key` hotkey
+    urgent: bool,
+    /// Index into `Settings::profiles` of the current or last recording's profile
+    profile: Option<usize>,
+    /// When each profile's recent recordings started, for its rate limits
+    profile_starts: Vec<Vec<Instant>>,
+    pressed_at: Instant,
+    /// Whether any text of the current recording has been typed yet
+    typed: bool,
+    submit_pending_since: Option<Instant>,
+    /// Conditions of the last transcription output, for rating it
+    last_transcript: Option<TranscriptInfo>,
+    /// The last text output, which spoken corrections apply to
+    last_output: Option<String>,
+    /// Transcripts waiting to be output (with `--compose`)
+    compose: compose::Buffer,
+    /// What `ctl status` and `ctl last-text` report
+    status: daemon::SharedStatus,
+    /// With `--status-file`
+    status_file: Option<StatusFile>,
+    /// Chunks of the current recording being transcribed in the background
+    /// (with `--stream`), in order
+    stream_chunks: Vec<StreamChunk>,
+    /// Text of the current recording's chunks output so far (with
+    /// `--split-after`), which the clipboard gets all of
+    split_text: String,
+    /// When the engine was last used, or `None` while its model is unloaded
+    /// (with `--unload-after`)
+    engine_used: Option<Instant>,
+    /// The model being loaded again for the current recording
+    engine_loading: Option<tokio::task::JoinHandle<Result<Engine>>>,
+    /// A recording being transcribed while urgent dictations can go ahead
+    transcription: Option<PendingTranscription>,
+    /// Another engine for an urgent dictation recorded meanwhile, freed once
+    /// it is transcribed
+    urgent_engine: Option<Arc<std::sync::Mutex<Engine>>>,
+    urgent_engine_loading: Option<tokio::task::JoinHandle<Result<Engine>>>,
+    /// The profile whose initial prompt the engine has
+    prompt_profile: Option<usize>,
+    /// A transcript being post-processed while urgent dictations can go ahead
+    post_processing: Option<PostProcessing>,
+}
+
+/// Seconds of audio in a streamed chunk, and its transcription.
+type StreamChunk = (f64, tokio::task::JoinHandle<Result<String, String>>);
+
+impl Pipeline {
+    fn state(&self) -> RecordingState {
+        RecordingState {
+            is_recording: self.is_recording,
+            held: self.pressed_at.elapsed(),
+            buffered_secs: self.recorder.buffered_secs(),
+            submit_pending: self.submit_pending_since.map(|since| since.elapsed()),
+            hands_free: self.hands_free,
+            key: match self.settings.urgent_key {
+                Some(key) if self.urgent => key,
+                _ => self.profile().map_or(0, |(key, _)| *key),
+            },
+            silence_secs: {
+                let lookback = [
+                    self.settings.auto_stop.filter(|_| self.hands_free),
+                    self.settings.stream.then_some(STREAM_PAUSE_SECS),
+                ]
+                .into_iter()
+                .flatten()
+                .fold(0.0, f64::max);
+                if self.is_recording && lookback > 0.0 {
+                    self.recorder.trailing_silence_secs(lookback)
+                } else {
+                    0.0
+                }
+            },
+            engine_idle: self.engine_used.map(|used| used.elapsed()),
+            transcribing: self
+                .transcription
+                .as_ref()
+                .map(|pending| pending.start.elapsed()),
+        }
+    }
+
+    fn start_recording(&mut self, hands_free: bool, profile: Option<usize>, urgent: bool) {
+        let now = Instant::now();
+        if let Some(i) = profile {
+            let (_, settings) = &self.settings.profiles[i];
+            let starts = &mut self.profile_starts[i];
+            starts.retain(|start| settings.counts(*start, now));
+            // Ignored rather than queued, e.g. on a shared demo machine
+            if let Some(wait) = settings.wait(starts, now) {
+                let secs = format!("{:.0}", wait.as_secs_f64().ceil());
+                let message = trf(Msg::RateLimited, &[&settings.name, &secs]);
+                println!("{}", message);
+                self.cue(Cue::Failed, Some(&message));
+                return;
+            }
+        }
+        match profile {
+            Some(i) => println!(
+                "{}",
+                trf(Msg::RecordingProfile, &[&self.settings.profiles[i].1.name])
+            ),
+            None if urgent => println!("{}", tr(Msg::RecordingUrgent)),
+            None => println!("{}", tr(Msg::Recording)),
+        }
+        if let Err(e) = self.recorder.start() {
+            log::error!("Failed to start recording: {}", e);
+            return;
+        }
+        if let Some(i) = profile {
+            self.profile_starts[i].push(now);
+        }
+        if self.engine_used.is_none() && self.engine_loading.is_none() {
+            self.load_engine();
+        }
+        if self.transcription.is_some() && urgent {
+            // The engine is busy with the background transcription, so this
+            // one gets its own, loaded while it is being recorded
+            self.urgent_engine_loading = Some(self.spawn_engine());
+        }
+        self.profile = profile;
+        if self.transcription.is_none() {
+            self.sync_prompt();
+        }
+        self.urgent = urgent;
+        self.is_recording = true;
+        self.show_state(title::State::Recording);
+        self.cue(Cue::RecordingStarted, None);
+        self.hands_free = hands_free;
+        self.pressed_at = Instant::now();
+        self.status.lock().unwrap().recording_since = Some(self.pressed_at);
+        self.typed = false;
+        self.submit_pending_since = None;
+        self.stream_chunks.clear();
+        self.split_text.clear();
+    }
+
+    async fn stop_recording(&mut self) {
+        // Continue recording briefly to capture trailing audio
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        println!("{}", tr(Msg::Transcribing));
+        self.is_recording = false;
+        self.status.lock().unwrap().recording_since = None;
+        self.show_state(title::State::Transcribing);
+        self.cue(Cue::RecordingStopped, None);
+        let recording = self.recorder.stop().await;
+        if !self.urgent {
+            // Earlier dictations go out first
+            self.flush_transcription().await;
+        }
+        self.typed |= self.handle_transcription(recording, false).await;
+        self.recorder.finish();
+        self.show_state(self.background_state());
+        if self.typed {
+            self.offer_submit().await;
+        }
+    }
+
+    /// The state to show once nothing is recorded: what is still going on in
+    /// the background, if anything.
+    fn background_state(&self) -> title::State {
+        if self.transcription.is_some() {
+            title::State::Transcribing
+        } else if self.post_processing.is_some() {
+            title::State::PostProcessing
+        } else {
+            title::State::Idle
+        }
+    }
+
+    fn profile(&self) -> Option<&(usize, Profile)> {
+        self.profile.map(|i| &self.settings.profiles[i])
+    }
+
+    /// The engine context recordings with `profile` are transcribed with.
+    fn initial_prompt(&self, profile: Option<usize>) -> Option<&String> {
+        profile
+            .and_then(|i| self.settings.profiles[i].1.initial_prompt.as_ref())
+            .or(self.settings.initial_prompt.as_ref())
+    }
+
+    /// Output options with the current profile's output mode.
+    fn output_options(&self) -> OutputOptions {
+        let mut options = self.settings.output;
+        if let Some(mode) = self.profile().and_then(|(_, profile)| profile.output) {
+            options.mode = mode;
+        }
+        options
+    }
+
+    /// Press Enter after typed output, or wait for confirmation, per
+    /// `--auto-submit` or the current profile.
+    async fn offer_submit(&mut self) {
+        if self.output_options().types() {
+            let auto_submit = match self.profile() {
+                Some((_, profile)) => profile.auto_submit(self.settings.auto_submit),
+                None => self.settings.auto_submit,
+            };
+            match auto_submit {
+                AutoSubmit::Off => {}
+                AutoSubmit::On => self.submit().await,
+                AutoSubmit::Confirm => {
+                    let secs = SUBMIT_CONFIRM_WINDOW.as_secs();
+                    println!("{}", trf(Msg::SubmitConfirm, &[&secs]));
+                    self.submit_pending_since = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    async fn submit(&mut self) {
+        self.submit_pending_since = None;
+        // Once submitted the text can no longer be edited in place
+        self.last_output = None;
+        if let Err(e) = output::press_enter(&self.output_options()).await {
+            log::error!("Failed to submit: {}", e);
+        }
+    }
+
+    fn cancel_recording(&mut self) {
+        self.is_recording = false;
+        self.status.lock().unwrap().recording_since = None;
+        self.recorder.cancel();
+        self.stream_chunks.clear();
+        self.urgent_engine = None;
+        self.urgent_engine_loading = None;
+        self.show_state(self.background_state());
+    }
+
+    /// Load the model again on the blocking pool, while recording goes ahead.
+    fn load_engine(&mut self) {
+        self.engine_loading = Some(self.spawn_engine());
+    }
+
+    /// Load another engine in the background.
+    fn spawn_engine(&self) -> tokio::task::JoinHandle<Result<Engine>> {
+        let (kind, path) = self.settings.model.clone();
+        let isolate = self.settings.isolate_engine;
+        tokio::task::spawn_blocking(move || {
+            if isolate {
+                worker::load_engine(kind, &path, false)
+            } else {
+                model::load_engine(kind, &path, false)
+            }
+        })
+    }
+
+    /// Wait for a model that is being loaded again and put it in place.
+    async fn ensure_engine(&mut self) -> Result<()> {
+        let Some(loading) = self.engine_loading.take() else {
+            return Ok(());
+        };
+        let waited = !loading.is_finished();
+        if waited {
+            self.show_state(title::State::LoadingModel);
+        }
+        let mut engine = loading.await.context("Model loading task failed")??;
+        engine.set_initial_prompt(self.initial_prompt(self.profile).cloned());
+        self.prompt_profile = self.profile;
+        // A new mutex, as the old one may be held by a call the watchdog gave up on
+        self.engine = Arc::new(std::sync::Mutex::new(engine));
+        self.engine_used = Some(Instant::now());
+        if waited {
+            self.show_state(title::State::Transcribing);
+        }
+        Ok(())
+    }
+
+    /// Wait for `task`, giving up once it takes longer than `--watchdog`. A
+    /// `stage` that is given up on is logged and reported with `stuck`, and
+    /// the pipeline goes back to idle.
+    async fn watch<T>(
+        &mut self,
+        stage: &str,
+        stuck: Msg,
+        task: impl Future<Output = T>,
+    ) -> Option<T> {
+        let Some(deadline) = self.settings.watchdog else {
+            return Some(task.await);
+        };
+        match tokio::time::timeout(deadline, task).await {
+            Ok(result) => Some(result),
+            Err(_) => {
+                self.gave_up(stage, stuck, deadline);
+                None
+            }
+        }
+    }
+
+    /// Log and report that the watchdog gave up on `stage` after `deadline`,
+    /// and go back to idle.
+    fn gave_up(&mut self, stage: &str, stuck: Msg, deadline: Duration) {
+        log::error!(
+            "Watchdog gave up: stage={} deadline_secs={} profile={}",
+            stage,
+            deadline.as_secs(),
+            self.profile().map_or("none", |(_, profile)| &profile.name)
+        );
+        let message = trf(stuck, &[&deadline.as_secs()]);
+        println!("{}", message);
+        self.cue(Cue::Failed, Some(&message));
+        self.stream_chunks.clear();
+        self.submit_pending_since = None;
+        if !self.is_recording {
+            self.show_state(title::State::Idle);
+        }
+    }
+
+    /// Whether the engine can be used without waiting for its model to load.
+    /// Give the engine the current profile's initial prompt, unless it is
+    /// still loading (it gets the prompt once it is ready).
+    fn sync_prompt(&mut self) {
+        if self.engine_loading.is_none()
+            && self.initial_prompt(self.profile) != self.initial_prompt(self.prompt_profile)
+        {
+            let prompt = self.initial_prompt(self.profile).cloned();
+            self.engine.lock().unwrap().set_initial_prompt(prompt);
+            self.prompt_profile = self.profile;
+        }
+    }
+
+    fn engine_ready(&self) -> bool {
+        self.engine_loading
+            .as_ref()
+            .is_none_or(|loading| loading.is_finished())
+    }
+
+    async fn unload_engine(&mut self) {
+        if !self.engine_ready() || self.transcription.is_some() {
+            return;
+        }
+        if let Err(e) = self.ensure_engine().await {
+            log::error!("Failed to load model: {:#}", e);
+        }
+        let idle = self.engine_used.map(|used| used.elapsed());
+        self.engine.lock().unwrap().unload_model();
+        self.engine_used = None;
+        log::info!(
+            "Model unloaded after {:.0?} without use",
+            idle.unwrap_or_default()
+        );
+    }
+
+    /// Hand the audio so far to the engine in the background. The text is
+    /// joined up with the rest when the recording stops.
+    async fn stream_chunk(&mut self) {
+        // The audio stays buffered for the next tick
+        if !self.engine_ready() || self.transcription.is_some() {
+            return;
+        }
+        if let Err(e) = self.ensure_engine().await {
+            log::error!("Failed to load model: {:#}", e);
+            return;
+        }
+        let samples = match self.recorder.split().await {
+            Ok(samples) => samples,
+            Err(e) => {
+                log::error!("Failed to hand off streamed audio: {}", e);
+                return;
+            }
+        };
+        let secs = samples.len() as f64 / audio::TARGET_OUTPUT_SAMPLE_RATE as f64;
+        log::debug!("Transcribing a {:.1}s chunk in the background", secs);
+        let engine = Arc::clone(&self.engine);
+        let transcription = tokio::task::spawn_blocking(move || {
+            let mut engine = engine.lock().unwrap();
+            engine
+                .transcribe_samples(samples)
+                .map(|transcription| transcription.text)
+                .map_err(|e| e.to_string())
+        });
+        self.stream_chunks.push((secs, transcription));
+    }
+
+    async fn split_recording(&mut self) {
+        if !self.engine_ready() {
+            return;
+        }
+        if !self.urgent {
+            self.flush_transcription().await;
+        }
+        println!("{}", tr(Msg::TranscribingChunk));
+        self.show_state(title::State::Transcribing);
+        let recording = self.recorder.split().await;
+        self.typed |= self.handle_transcription(recording, true).await;
+        self.recorder.finish();
+        // Recording carries on while the chunk is transcribed
+        self.show_state(title::State::Recording);
+    }
+
+    /// Beep and/or notify, as configured. Notifications are only shown for
+    /// results, with `message` as their text.
+    fn cue(&self, cue: Cue, message: Option<&str>) {
+        if self.settings.beep {
+            cues::beep(cue);
+        }
+        if let Some(message) = message.filter(|_| self.settings.notify) {
+            let summary = tr(if cue == Cue::Failed {
+                Msg::NotifyFailed
+            } else {
+                Msg::NotifyDone
+            });
+            cues::notify(summary, message);
+        }
+    }
+
+    /// Show `state` in the terminal title and status file, as configured.
+    fn show_state(&mut self, state: title::State) {
+        if self.settings.terminal_title {
+            title::set(state);
+        }
+        if let Some(status_file) = &mut self.status_file {
+            status_file.set(state);
+        }
+    }
+
+    fn rate(&mut self, rating: Rating) {
+        if !self.settings.feedback {
+            println!("{}", tr(Msg::FeedbackDisabled));
+            return;
+        }
+        let Some(info) = self.last_transcript.take() else {
+            println!("{}", tr(Msg::NothingToRate));
+            return;
+        };
+        match feedback::record(&info, rating) {
+            Ok(()) => {
+                let path = format!("{:?}", feedback::log_path());
+                println!("{}", trf(Msg::RatingSaved, &[&path]));
+            }
+            Err(e) => log::error!("Failed to save rating: {}", e),
+        }
+    }
+
+    /// The edit for `transcript` if it is a spoken correction of the last output.
+    fn correction(&self, transcript: &str) -> Option<Edit> {
+        if !self.settings.spoken_corrections {
+            return None;
+        }
+        let correction = correction::parse(transcript)?;
+        let previous = if self.settings.compose {
+            Some(self.compose.text())
+        } else {
+            self.last_output.as_deref()
+        };
+        correction::apply(previous?, &correction)
+    }
+
+    /// Apply the `--hints` and then the `--dictionary` rules, picking up
+    /// changes to the files.
+    fn apply_dictionary(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+        for dictionary in [&mut self.settings.hints, &mut self.settings.dictionary]
+            .into_iter()
+            .flatten()
+        {
+            dictionary.reload_if_changed();
+            text = dictionary.apply(&text);
+        }
+        text
+    }
+
+    /// The reminder in `transcript` and where it goes: the profile's target,
+    /// or `--reminders`.
+    fn reminder(&self, transcript: &str) -> Option<(ReminderTarget, Reminder)> {
+        let target = self
+            .profile()
+            .and_then(|(_, profile)| profile.reminders.as_ref())
+            .or(self.settings.reminders.as_ref())?;
+        let reminder = reminder::parse(transcript, chrono::Local::now().naive_local())?;
+        Some((target.clone(), reminder))
+    }
+
+    async fn add_reminder(&mut self, target: &ReminderTarget, mut reminder: Reminder) {
+        reminder.what = self.apply_dictionary(&reminder.what);
+        match target.add(&reminder).await {
+            Ok(()) => {
+                let when = reminder.when.format("%a %Y-%m-%d %H:%M");
+                println!("{}", trf(Msg::ReminderAdded, &[&when, &reminder.what]));
+                self.cue(Cue::Done, Some(&reminder.what));
+            }
+            Err(e) => {
+                log::error!("Failed to add reminder: {:#}", e);
+                self.cue(Cue::Failed, Some(&e.to_string()));
+            }
+        }
+    }
+
+    /// The to-do in `transcript`: all of it with a to-do profile, otherwise
+    /// whatever follows a "To-do:" prefix.
+    fn todo_task(&self, transcript: &str) -> Option<String> {
+        self.settings.todo.as_ref()?;
+        if self.profile().is_some_and(|(_, profile)| profile.todo) {
+            let task = transcript.trim().trim_end_matches('.');
+            return (!task.is_empty()).then(|| task.to_string());
+        }
+        todo::parse(transcript)
+    }
+
+    async fn add_todo(&mut self, task: String) {
+        let task = self.apply_dictionary(&task);
+        let Some(target) = &self.settings.todo else {
+            return;
+        };
+        match target.add(&task).await {
+            Ok(()) => {
+                println!("{}", trf(Msg::TodoAdded, &[&task]));
+                self.cue(Cue::Done, Some(&task));
+            }
+            Err(e) => {
+                log::error!("Failed to add to-do: {:#}", e);
+                self.cue(Cue::Failed, Some(&e.to_string()));
+            }
+        }
+    }
+
+    async fn apply_correction(&mut self, edit: Edit) {
+        if self.settings.compose {
+            self.compose.replace(edit.result);
+            self.show_compose();
+            return;
+        }
+        match output::correct(&edit, &self.output_options()).await {
+            Ok(()) => self.remember_output(edit.result),
+            Err(e) => log::error!("Failed to apply correction: {}", e),
+        }
+    }
+
+    async fn output_compose(&mut self) {
+        if !self.settings.compose {
+            println!("{}", tr(Msg::ComposeDisabled));
+            return;
+        }
+        if self.compose.is_empty() {
+            println!("{}", tr(Msg::ComposeEmpty));
+            return;
+        }
+        let text = self.compose.take();
+        match output_text(&text, None, &self.output_options()).await {
+            Ok(()) => {
+                self.remember_output(text);
+                self.offer_submit().await;
+            }
+            Err(e) => {
+                log::error!("Failed to output text: {}", e);
+                self.compose.replace(text);
+            }
+        }
+    }
+
+    /// Keep `text` for spoken corrections and `ctl last-text`.
+    fn remember_output(&mut self, text: String) {
+        self.status.lock().unwrap().last_text = Some(text.clone());
+        self.last_output = Some(text);
+    }
+
+    fn discard_compose(&mut self) {
+        if !self.settings.compose {
+            println!("{}", tr(Msg::ComposeDisabled));
+            return;
+        }
+        self.compose.take();
+        println!("{}", tr(Msg::ComposeDiscarded));
+    }
+
+    fn show_compose(&self) {
+        if self.compose.is_empty() {
+            println!("{}", tr(Msg::ComposeEmpty));
+        } else {
+            println!("{}", trf(Msg::ComposeBuffer, &[&self.compose.text()]));
+        }
+    }
+
+    fn toggle_post_process(&mut self) {
+        if !post_process::AVAILABLE {
+            println!("{}", post_process::not_compiled_in());
+            return;
+        }
+        self.settings.post_process = !self.settings.post_process;
+        println!(
+            "{}",
+            tr(if self.settings.post_process {
+                Msg::PostProcessingOn
+            } else {
+                Msg::PostProcessingOff
             })
-            .await;
+        );
+    }
+
+    /// Record from the device matching `selector` from the next recording on;
+    /// a recording in progress carries on with the current device.
+    fn set_device(&mut self, selector: String) -> Result<()> {
+        let name = self.recorder.set_device(selector)?;
+        println!("{}", trf(Msg::DeviceSwitched, &[&name]));
+        Ok(())
+    }
 
-            match result {
-                Ok(Ok(transcription)) => {
-                    log::debug!("Transcribed in {:.2?}", start.elapsed());
-                    let text = transcription.text.trim();
-                    if !text.is_empty() {
-                        let final_text = if let Some(processor) = post_processor {
-                            println!("Post-processing...");
-                            match processor.process(text).await {
-                                Ok(processed) => processed,
-                                Err(e) => {
-                                    log::error!("Post-processing failed: {}", e);
-                                    text.to_string()
-                                }
-                            }
-                        } else {
-                            text.to_string()
-                        };
-
-                        if let Err(e) = output_text(&final_text, output_mode).await {
-                            log::error!("Failed to output text: {}", e);
-                        }
-                    } else {
-                        println!("(no speech detected)");
+    /// Switch to the next configured device, skipping any that are unplugged.
+    fn cycle_device(&mut self) {
+        let count = self.settings.devices.len();
+        let first = next_device(&self.settings.devices, self.recorder.device());
+        for i in 0..count {
+            let selector = self.settings.devices[(first + i) % count].clone();
+            match self.set_device(selector) {
+                Ok(()) => return,
+                Err(e) => log::warn!("Skipping input device: {:#}", e),
+            }
+        }
+    }
+
+    /// Output the last transcription again, e.g. after it went to the wrong
+    /// window. Falls back to the history after a restart.
+    async fn repeat_last(&mut self) {
+        let text = self
+            .last_output
+            .clone()
+            .or_else(|| self.settings.history.then(history::last_text).flatten());
+        let Some(text) = text else {
+            println!("{}", tr(Msg::NothingToRepeat));
+            return;
+        };
+        if let Err(e) = output_text(&text, None, &self.output_options()).await {
+            log::error!("Failed to output text: {}", e);
+        }
+    }
+
+    fn print_status(&self) {
+        if self.is_recording {
+            let secs = format!("{:.1}", self.pressed_at.elapsed().as_secs_f32());
+            println!("{}", trf(Msg::StatusRecording, &[&secs]));
+        } else {
+            println!("{}", tr(Msg::StatusIdle));
+        }
+        let post_process = tr(if self.settings.post_process {
+            Msg::On
+        } else {
+            Msg::Off
+        });
+        let output_mode = format!("{:?}", self.settings.output.mode);
+        println!("{}", trf(Msg::StatusOutput, &[&output_mode, &post_process]));
+    }
+
+    /// Transcribe recorded samples and output the text. `continues` marks a chunk of
+    /// an ongoing recording, whose output gets a trailing space so the next chunk
+    /// joins up with it. Returns whether any text was output.
+    async fn handle_transcription(&mut self, recording: Result<Vec<f32>>, continues: bool) -> bool {
+        let mut output = false;
+        if let Err(e) = self.ensure_engine().await {
+            log::error!("Failed to load model: {:#}", e);
+            self.cue(Cue::Failed, Some(&format!("{:#}", e)));
+            return false;
+        }
+        if let Some(loading) = self.urgent_engine_loading.take() {
+            match loading.await.context("Model loading task failed") {
+                Ok(Ok(mut engine)) => {
+                    engine.set_initial_prompt(self.initial_prompt(None).cloned());
+                    self.urgent_engine = Some(Arc::new(std::sync::Mutex::new(engine)));
+                }
+                Ok(Err(e)) | Err(e) => {
+                    log::error!("Failed to load model: {:#}", e);
+                    self.cue(Cue::Failed, Some(&format!("{:#}", e)));
+                    return false;
+                }
+            }
+        }
+        let engine = match &self.urgent_engine {
+            Some(engine) => Arc::clone(engine),
+            None => {
+                self.sync_prompt();
+                Arc::clone(&self.engine)
+            }
+        };
+        if !continues {
+            self.urgent_engine = None;
+        }
+        self.engine_used = Some(Instant::now());
+        match recording {
+            Ok(samples) => {
+                let start = Instant::now();
+                let chunks = std::mem::take(&mut self.stream_chunks);
+                let audio_secs = chunks.iter().map(|(secs, _)| secs).sum::<f64>()
+                    + samples.len() as f64 / audio::TARGET_OUTPUT_SAMPLE_RATE as f64;
+
+                // Run sync transcription in blocking task
+                let transcription = tokio::task::spawn_blocking(move || {
+                    let mut engine = engine.lock().unwrap();
+                    engine
+                        .transcribe_samples(samples)
+                        .map(|transcription| transcription.text)
+                        .map_err(|e| e.to_string())
+                });
+                let transcription = async move {
+                    match transcription.await {
+                        Ok(Ok(text)) if !chunks.is_empty() => Ok(stitch(chunks, text).await),
+                        Ok(result) => result,
+                        Err(e) => Err(format!("Transcription task failed: {}", e)),
                     }
+                };
+                if self.settings.urgent_key.is_some() && !self.urgent && !continues {
+                    // In the background, so an urgent dictation can be recorded meanwhile
+                    self.transcription = Some(PendingTranscription {
+                        task: tokio::spawn(transcription),
+                        start,
+                        audio_secs,
+                        profile: self.profile,
+                    });
+                    return false;
+                }
+                let Some(result) = self
+                    .watch("transcription", Msg::TranscriptionStuck, transcription)
+                    .await
+                else {
+                    // The stuck call can't be cancelled and keeps the engine
+                    // locked, so carry on with a fresh one
+                    self.load_engine();
+                    return false;
+                };
+                output = self
+                    .transcribed(result, audio_secs, start.elapsed(), continues)
+                    .await;
+            }
+            Err(e) => log::error!("Failed to stop recording: {}", e),
+        }
+        output
+    }
+
+    /// Output the result of a transcription. Returns whether any text was output.
+    async fn transcribed(
+        &mut self,
+        result: Result<String, String>,
+        audio_secs: f64,
+        transcribe_time: Duration,
+        continues: bool,
+    ) -> bool {
+        let text = match result {
+            Ok(text) => text,
+            Err(e) => {
+                log::error!("Transcription failed: {}", e);
+                self.cue(Cue::Failed, Some(&e));
+                return false;
+            }
+        };
+        log::debug!("Transcribed in {:.2?}", transcribe_time);
+        let text = text.trim();
+        if text.is_empty() {
+            println!("{}", tr(Msg::NoSpeech));
+            self.cue(Cue::Failed, Some(tr(Msg::NoSpeech)));
+            false
+        } else if self.urgent {
+            // Straight out, ahead of anything still being transcribed or post-processed
+            let delivery = Delivery {
+                raw_text: text.to_string(),
+                text: self.apply_dictionary(text),
+                audio_secs,
+                transcribe_time,
+                post_processed: false,
+                options: self.settings.output,
+                compose: false,
+            };
+            self.deliver(delivery, continues).await
+        } else {
+            // Earlier dictations go out first
+            self.flush_post_processing().await;
+            self.route(text, audio_secs, transcribe_time, continues)
+                .await
+        }
+    }
+
+    /// Output the recording that was transcribed in the background, with the
+    /// profile it was recorded with.
+    async fn finish_transcription(&mut self, result: Result<String, String>) {
+        let Some(pending) = self.transcription.take() else {
+            return;
+        };
+        self.engine_used = Some(Instant::now());
+        let current = (self.profile, self.urgent);
+        (self.profile, self.urgent) = (pending.profile, false);
+        let output = self
+            .transcribed(result, pending.audio_secs, pending.start.elapsed(), false)
+            .await;
+        if !self.is_recording {
+            self.show_state(self.background_state());
+            if output {
+                self.offer_submit().await;
+            }
+        }
+        (self.profile, self.urgent) = current;
+    }
+
+    /// Wait for a recording being transcribed in the background, and output it.
+    async fn flush_transcription(&mut self) {
+        if let Some(pending) = &mut self.transcription {
+            let result = transcribed(&mut pending.task).await;
+            self.finish_transcription(result).await;
+        }
+    }
+
+    /// Stop waiting for a background transcription that exceeded `--watchdog`.
+    fn give_up_transcription(&mut self) {
+        let Some(pending) = self.transcription.take() else {
+            return;
+        };
+        pending.task.abort();
+        let deadline = self.settings.watchdog.unwrap_or_default();
+        self.gave_up("transcription", Msg::TranscriptionStuck, deadline);
+        // The stuck call keeps the engine locked, so carry on with a fresh one
+        self.load_engine();
+    }
+
+    /// Handle a transcript by what it says: a compose command, a spoken
+    /// correction, a reminder or a to-do, or otherwise text to output after
+    /// the dictionary and post-processing. Returns whether any text was output.
+    async fn route(
+        &mut self,
+        text: &str,
+        audio_secs: f64,
+        transcribe_time: Duration,
+        continues: bool,
+    ) -> bool {
+        if self.settings.compose && compose::is_delete_last_sentence(text) {
+            self.compose.delete_last_sentence();
+            self.show_compose();
+            return false;
+        }
+        if let Some(edit) = self.correction(text) {
+            self.apply_correction(edit).await;
+            return false;
+        }
+        if let Some((target, reminder)) = self.reminder(text) {
+            self.add_reminder(&target, reminder).await;
+            return false;
+        }
+        if let Some(task) = self.todo_task(text) {
+            self.add_todo(task).await;
+            return false;
+        }
+        let replaced = self.apply_dictionary(text);
+        let output_language = self.settings.output_language.clone();
+        let (post_process, prompt, language) = match self.profile() {
+            Some((_, profile)) => (
+                profile.post_process(self.settings.post_process),
+                profile.prompt.clone(),
+                profile.output_language.clone().or(output_language),
+            ),
+            None => (self.settings.post_process, None, output_language),
+        };
+        let mut delivery = Delivery {
+            raw_text: text.to_string(),
+            text: replaced,
+            audio_secs,
+            transcribe_time,
+            post_processed: post_process,
+            options: self.output_options(),
+            compose: self.settings.compose,
+        };
+        if post_process {
+            println!("{}", tr(Msg::PostProcessing));
+            self.show_state(title::State::PostProcessing);
+            if self.settings.urgent_key.is_some() && !continues {
+                // In the background, so urgent dictations can go out meanwhile
+                let post_processor = Arc::clone(&self.post_processor);
+                let text = delivery.text.clone();
+                let task = tokio::spawn(async move {
+                    post_processor
+                        .process(&text, prompt.as_deref(), language.as_deref())
+                        .await
+                });
+                self.post_processing = Some((task, delivery));
+                return false;
+            }
+            match self
+                .post_processor
+                .process(&delivery.text, prompt.as_deref(), language.as_deref())
+                .await
+            {
+                Ok(processed) => delivery.text = processed,
+                Err(e) => log::error!("Post-processing failed: {}", e),
+            }
+        }
+        self.deliver(delivery, continues).await
+    }
+
+    /// Record a finished transcript in the history and output it, or add it
+    /// to the compose buffer. Returns whether it was output.
+    async fn deliver(&mut self, delivery: Delivery, continues: bool) -> bool {
+        let Delivery {
+            raw_text,
+            text: final_text,
+            audio_secs,
+            transcribe_time,
+            post_processed,
+            options,
+            compose,
+        } = delivery;
+        let entry = history::Entry {
+            timestamp: TranscriptInfo::now(),
+            audio_secs,
+            raw_text,
+            text: final_text.clone(),
+        };
+        if self.settings.history {
+            if let Err(e) = history::append(&entry) {
+                log::warn!("Failed to save history: {:#}", e);
+            }
+        }
+        let final_text = if continues {
+            final_text + " "
+        } else {
+            final_text
+        };
+
+        if compose {
+            self.compose.push(&final_text);
+            self.show_compose();
+            self.cue(Cue::Done, Some(&final_text));
+            return false;
+        }
+        let metadata = options
+            .clipboard
+            .metadata
+            .then(|| serde_json::to_string(&entry).ok())
+            .flatten();
+        // The chunks of a split recording build up on the clipboard rather
+        // than replacing each other
+        let split_text = std::mem::take(&mut self.split_text);
+        let clipboard_text =
+            (continues || !split_text.is_empty()).then(|| split_text + &final_text);
+        let result = match &clipboard_text {
+            Some(clipboard_text) => {
+                let output = output::output_chunk(
+                    &final_text,
+                    clipboard_text,
+                    metadata.as_deref(),
+                    &options,
+                );
+                self.watch("output", Msg::OutputStuck, output).await
+            }
+            None => {
+                let output = output_text(&final_text, metadata.as_deref(), &options);
+                self.watch("output", Msg::OutputStuck, output).await
+            }
+        };
+        if continues {
+            self.split_text = clipboard_text.unwrap_or_default();
+        }
+        match result {
+            None => false,
+            Some(Ok(())) => {
+                self.cue(Cue::Done, Some(&final_text));
+                if let Some(status_file) = &mut self.status_file {
+                    status_file.transcribed();
                 }
-                Ok(Err(e)) => log::error!("Transcription failed: {}", e),
-                Err(e) => log::error!("Transcription task failed: {}", e),
+                self.last_transcript = Some(TranscriptInfo {
+                    timestamp: TranscriptInfo::now(),
+                    input: self.recorder.input_name().to_string(),
+                    audio_secs,
+                    transcribe_ms: transcribe_time.as_secs_f64() * 1000.0,
+                    words: final_text.split_whitespace().count(),
+                    post_processed,
+                    output_mode: format!("{:?}", options.mode),
+                });
+                self.remember_output(final_text);
+                true
             }
-            let _ = std::fs::remove_file(wav_path);
+            Some(Err(e)) => {
+                log::error!("Failed to output text: {}", e);
+                self.cue(Cue::Failed, Some(&e.to_string()));
+                false
+            }
+        }
+    }
+
+    /// Output the transcript that was post-processed in the background, with
+    /// its original text if post-processing failed.
+    async fn finish_post_processing(&mut self, processed: Option<Result<String, String>>) {
+        let Some((_, mut delivery)) = self.post_processing.take() else {
+            return;
+        };
+        match processed {
+            Some(Ok(processed)) => delivery.text = processed,
+            Some(Err(e)) => log::error!("Post-processing failed: {}", e),
+            None => log::error!("Post-processing task failed"),
+        }
+        let output = self.deliver(delivery, false).await;
+        if !self.is_recording {
+            self.show_state(self.background_state());
+            if output {
+                self.offer_submit().await;
+            }
+        }
+    }
+
+    /// Wait for a transcript being post-processed in the background, and output it.
+    async fn flush_post_processing(&mut self) {
+        if let Some((task, _)) = &mut self.post_processing {
+            let processed = post_processed(task).await;
+            self.finish_post_processing(processed).await;
+        }
+    }
+}
+
+/// A transcript on its way to the output.
+struct Delivery {
+    /// As the engine produced it
+    raw_text: String,
+    text: String,
+    audio_secs: f64,
+    transcribe_time: Duration,
+    post_processed: bool,
+    /// With the options of the profile it was recorded with
+    options: OutputOptions,
+    /// Whether it goes to the compose buffer rather than out
+    compose: bool,
+}
+
+/// A recording being transcribed in the background (with `--urgent-key`).
+struct PendingTranscription {
+    task: tokio::task::JoinHandle<Result<String, String>>,
+    start: Instant,
+    audio_secs: f64,
+    /// Index into `Settings::profiles` of the profile it was recorded with
+    profile: Option<usize>,
+}
+
+/// The result of a background transcription.
+async fn transcribed(
+    task: &mut tokio::task::JoinHandle<Result<String, String>>,
+) -> Result<String, String> {
+    task.await
+        .unwrap_or_else(|e| Err(format!("Transcription task failed: {}", e)))
+}
+
+/// Post-processing running in the background (with `--urgent-key`), and the
+/// transcript to output once it is done.
+type PostProcessing = (tokio::task::JoinHandle<Result<String>>, Delivery);
+
+/// The result of background post-processing, or `None` if the task failed.
+async fn post_processed(
+    task: &mut tokio::task::JoinHandle<Result<String>>,
+) -> Option<Result<String, String>> {
+    task.await
+        .ok()
+        .map(|result| result.map_err(|e| e.to_string()))
+}
+
+/// Put the text of the chunks streamed during a recording in front of the
+/// text of its last part. A chunk that failed is left out.
+async fn stitch(chunks: Vec<StreamChunk>, last: String) -> String {
+    let mut parts = Vec::new();
+    for (_, chunk) in chunks {
+        match chunk.await {
+            Ok(Ok(text)) => parts.push(text),
+            Ok(Err(e)) => log::error!("Transcribing a streamed chunk failed: {}", e),
+            Err(e) => log::error!("Streamed transcription task failed: {}", e),
+        }
+    }
+    parts.push(last);
+    parts
+        .iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Index of the device after `current` in `devices`, wrapping around, or the
+/// first one if `current` isn't among them.
+fn next_device(devices: &[String], current: Option<&str>) -> usize {
+    devices
+        .iter()
+        .position(|device| Some(device.as_str()) == current)
+        .map_or(0, |i| (i + 1) % devices.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(min_hold_ms: u64, split_after: Option<u64>) -> Settings {
+        Settings {
+            output: OutputOptions::default(),
+            auto_submit: AutoSubmit::Off,
+            post_process: false,
+            output_language: None,
+            hints: None,
+            dictionary: None,
+            initial_prompt: None,
+            profiles: Vec::new(),
+            split_after,
+            stream: false,
+            auto_stop: None,
+            max_duration: None,
+            watchdog: None,
+            min_hold: Duration::from_millis(min_hold_ms),
+            latch: false,
+            headset_button: false,
+            feedback: false,
+            spoken_corrections: false,
+            compose: false,
+            daemon: false,
+            devices: Vec::new(),
+            device_key: None,
+            history: false,
+            repeat_key: None,
+            urgent_key: None,
+            terminal_title: false,
+            status_file: None,
+            todo: None,
+            reminders: None,
+            unload_after: None,
+            model: (EngineKind::Parakeet(Default::default()), PathBuf::new()),
+            isolate_engine: false,
+            beep: false,
+            notify: false,
+        }
+    }
+
+    fn recording(held_ms: u64, buffered_secs: f64) -> RecordingState {
+        RecordingState {
+            is_recording: true,
+            held: Duration::from_millis(held_ms),
+            buffered_secs,
+            submit_pending: None,
+            hands_free: false,
+            key: 0,
+            silence_secs: 0.0,
+            engine_idle: Some(Duration::ZERO),
+            transcribing: None,
         }
-        Err(e) => log::error!("Failed to stop recording: {}", e),
+    }
+
+    #[test]
+    fn test_press_starts_recording() {
+        let event = Event::Hotkey(HotkeyEvent::Pressed(0));
+        assert_eq!(
+            decide(event.clone(), RecordingState::default(), &settings(0, None)),
+            Action::Start(None)
+        );
+        assert_eq!(
+            decide(event, recording(10, 0.0), &settings(0, None)),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_release_respects_min_hold() {
+        let event = Event::Hotkey(HotkeyEvent::Released(0));
+        let settings = settings(300, None);
+        assert_eq!(
+            decide(event.clone(), recording(100, 0.1), &settings),
+            Action::Cancel
+        );
+        assert_eq!(
+            decide(event.clone(), recording(500, 0.5), &settings),
+            Action::Stop
+        );
+        assert_eq!(
+            decide(event, RecordingState::default(), &settings),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_other_hotkeys_ignored() {
+        let settings = settings(0, None);
+        assert_eq!(
+            decide(
+                Event::Hotkey(HotkeyEvent::Pressed(1)),
+                RecordingState::default(),
+                &settings
+            ),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_toggle_recording() {
+        let event = Event::Control(ControlCommand::ToggleRecording);
+        let settings = settings(300, None);
+        assert_eq!(
+            decide(event.clone(), RecordingState::default(), &settings),
+            Action::Start(None)
+        );
+        // Toggles aren't subject to the minimum hold time
+        assert_eq!(decide(event, recording(10, 0.0), &settings), Action::Stop);
+    }
+
+    #[test]
+    fn test_start_and_stop_commands() {
+        let settings = settings(0, None);
+        let start = Event::Control(ControlCommand::StartRecording);
+        let stop = Event::Control(ControlCommand::StopRecording);
+        let idle = RecordingState::default();
+        assert_eq!(decide(start.clone(), idle, &settings), Action::Start(None));
+        assert_eq!(decide(start, recording(10, 0.0), &settings), Action::Ignore);
+        assert_eq!(
+            decide(stop.clone(), recording(10, 0.0), &settings),
+            Action::Stop
+        );
+        assert_eq!(decide(stop, idle, &settings), Action::Ignore);
+    }
+
+    #[test]
+    fn test_tick_splits_long_recordings() {
+        let settings = settings(0, Some(30));
+        assert_eq!(
+            decide(Event::Tick, recording(10_000, 10.0), &settings),
+            Action::Ignore
+        );
+        assert_eq!(
+            decide(Event::Tick, recording(31_000, 31.0), &settings),
+            Action::Split
+        );
+        assert_eq!(
+            decide(Event::Tick, RecordingState::default(), &settings),
+            Action::Ignore
+        );
+        assert_eq!(
+            decide(
+                Event::Tick,
+                recording(31_000, 31.0),
+                &self::settings(0, None)
+            ),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_tick_streams_at_pauses() {
+        let mut settings = settings(0, None);
+        settings.stream = true;
+        let paused = |buffered_secs, silence_secs| RecordingState {
+            silence_secs,
+            ..recording(20_000, buffered_secs)
+        };
+        assert_eq!(
+            decide(Event::Tick, paused(6.0, 0.5), &settings),
+            Action::StreamChunk
+        );
+        // Too short to be worth a chunk, or still talking
+        assert_eq!(
+            decide(Event::Tick, paused(3.0, 0.5), &settings),
+            Action::Ignore
+        );
+        assert_eq!(
+            decide(Event::Tick, paused(6.0, 0.0), &settings),
+            Action::Ignore
+        );
+        // Long stretches without a pause are cut anyway
+        assert_eq!(
+            decide(Event::Tick, paused(10.5, 0.0), &settings),
+            Action::StreamChunk
+        );
+        settings.stream = false;
+        assert_eq!(
+            decide(Event::Tick, paused(6.0, 0.5), &settings),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_tick_cuts_off_long_recordings() {
+        let mut settings = settings(0, None);
+        settings.max_duration = Some(Duration::from_secs(600));
+        assert_eq!(
+            decide(Event::Tick, recording(601_000, 1.0), &settings),
+            Action::Cutoff
+        );
+        assert_eq!(
+            decide(Event::Tick, recording(599_000, 1.0), &settings),
+            Action::Ignore
+        );
+        settings.max_duration = None;
+        assert_eq!(
+            decide(Event::Tick, recording(601_000, 1.0), &settings),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_tick_unloads_idle_model() {
+        let mut settings = settings(0, None);
+        settings.unload_after = Some(Duration::from_secs(300));
+        let idle = |is_recording, engine_idle| RecordingState {
+            is_recording,
+            engine_idle,
+            ..recording(0, 0.0)
+        };
+        let long = Some(Duration::from_secs(400));
+        assert_eq!(
+            decide(Event::Tick, idle(false, long), &settings),
+            Action::UnloadModel
+        );
+        // Not while recording, not too soon and not when already unloaded
+        assert_eq!(
+            decide(Event::Tick, idle(true, long), &settings),
+            Action::Ignore
+        );
+        let short = Some(Duration::from_secs(60));
+        assert_eq!(
+            decide(Event::Tick, idle(false, short), &settings),
+            Action::Ignore
+        );
+        assert_eq!(
+            decide(Event::Tick, idle(false, None), &settings),
+            Action::Ignore
+        );
+        settings.unload_after = None;
+        assert_eq!(
+            decide(Event::Tick, idle(false, long), &settings),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_tick_auto_stops_hands_free_recordings() {
+        let mut settings = settings(0, None);
+        settings.auto_stop = Some(2.0);
+        let silent = |hands_free, silence_secs| RecordingState {
+            hands_free,
+            silence_secs,
+            ..recording(10_000, 10.0)
+        };
+        assert_eq!(
+            decide(Event::Tick, silent(true, 2.5), &settings),
+            Action::Stop
+        );
+        assert_eq!(
+            decide(Event::Tick, silent(true, 1.0), &settings),
+            Action::Ignore
+        );
+        // Held recordings stop on release
+        assert_eq!(
+            decide(Event::Tick, silent(false, 2.5), &settings),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_control_commands() {
+        let settings = settings(0, None);
+        let idle = RecordingState::default();
+        assert_eq!(
+            decide(
+                Event::Control(ControlCommand::TogglePostProcess),
+                idle,
+                &settings
+            ),
+            Action::TogglePostProcess
+        );
+        assert_eq!(
+            decide(Event::Control(ControlCommand::Status), idle, &settings),
+            Action::Status
+        );
+        assert_eq!(
+            decide(Event::Control(ControlCommand::Quit), idle, &settings),
+            Action::Quit
+        );
+        assert_eq!(
+            decide(Event::Control(ControlCommand::RepeatLast), idle, &settings),
+            Action::RepeatLast
+        );
+    }
+
+    #[test]
+    fn test_press_confirms_pending_submit() {
+        let event = Event::Hotkey(HotkeyEvent::Pressed(0));
+        let settings = settings(0, None);
+        let pending = |ms| RecordingState {
+            submit_pending: Some(Duration::from_millis(ms)),
+            ..RecordingState::default()
+        };
+        assert_eq!(
+            decide(event.clone(), pending(1_000), &settings),
+            Action::Submit
+        );
+        assert_eq!(
+            decide(event, pending(6_000), &settings),
+            Action::Start(None)
+        );
+        assert_eq!(
+            decide(Event::Tick, pending(1_000), &settings),
+            Action::Ignore
+        );
+        assert_eq!(
+            decide(Event::Tick, pending(6_000), &settings),
+            Action::DropSubmit
+        );
+    }
+
+    #[test]
+    fn test_feedback_keys_rate() {
+        let idle = RecordingState::default();
+        let mut settings = settings(0, None);
+        settings.feedback = true;
+        assert_eq!(
+            decide(
+                Event::Hotkey(HotkeyEvent::Pressed(GOOD_KEY)),
+                idle,
+                &settings
+            ),
+            Action::Rate(Rating::Good)
+        );
+        assert_eq!(
+            decide(
+                Event::Hotkey(HotkeyEvent::Pressed(BAD_KEY)),
+                idle,
+                &settings
+            ),
+            Action::Rate(Rating::Bad)
+        );
+        settings.feedback = false;
+        assert_eq!(
+            decide(
+                Event::Hotkey(HotkeyEvent::Pressed(GOOD_KEY)),
+                idle,
+                &settings
+            ),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_device_key_cycles_devices() {
+        let idle = RecordingState::default();
+        let mut settings = settings(0, None);
+        settings.device_key = Some(1);
+        assert_eq!(
+            decide(Event::Hotkey(HotkeyEvent::Pressed(1)), idle, &settings),
+            Action::CycleDevice
+        );
+
+        let devices = ["USB".to_string(), "Headset".to_string()];
+        assert_eq!(next_device(&devices, None), 0);
+        assert_eq!(next_device(&devices, Some("USB")), 1);
+        assert_eq!(next_device(&devices, Some("Headset")), 0);
+        assert_eq!(next_device(&devices, Some("Webcam")), 0);
+    }
+
+    #[test]
+    fn test_profile_keys_record_with_their_profile() {
+        let mut settings = settings(0, None);
+        let profile = |name: &str| Profile {
+            name: name.into(),
+            key: String::new(),
+            post_process: None,
+            prompt: None,
+            output_language: None,
+            output: None,
+            auto_submit: None,
+            initial_prompt: None,
+            todo: false,
+            reminders: None,
+            cooldown: None,
+            max_per_minute: None,
+        };
+        settings.profiles = vec![(3, profile("code")), (4, profile("raw"))];
+        let idle = RecordingState::default();
+        assert_eq!(
+            decide(Event::Hotkey(HotkeyEvent::Pressed(4)), idle, &settings),
+            Action::Start(Some(1))
+        );
+        assert_eq!(
+            decide(Event::Hotkey(HotkeyEvent::Pressed(0)), idle, &settings),
+            Action::Start(None)
+        );
+        assert_eq!(
+            decide(Event::Hotkey(HotkeyEvent::Pressed(5)), idle, &settings),
+            Action::Ignore
+        );
+
+        // Only releasing the profile's own hotkey stops its recording
+        let state = RecordingState {
+            key: 4,
+            ..recording(1000, 1.0)
+        };
+        assert_eq!(
+            decide(Event::Hotkey(HotkeyEvent::Released(0)), state, &settings),
+            Action::Ignore
+        );
+        assert_eq!(
+            decide(Event::Hotkey(HotkeyEvent::Released(4)), state, &settings),
+            Action::Stop
+        );
+    }
+
+    #[test]
+    fn test_urgent_key_records_without_profile() {
+        let mut settings = settings(0, None);
+        settings.urgent_key = Some(3);
+        let idle = RecordingState::default();
+        assert_eq!(
+            decide(Event::Hotkey(HotkeyEvent::Pressed(3)), idle, &settings),
+            Action::StartUrgent
+        );
+        // Not while another recording is going on
+        assert_eq!(
+            decide(
+                Event::Hotkey(HotkeyEvent::Pressed(3)),
+                recording(1000, 1.0),
+                &settings
+            ),
+            Action::Ignore
+        );
+        let state = RecordingState {
+            key: 3,
+            ..recording(1000, 1.0)
+        };
+        assert_eq!(
+            decide(Event::Hotkey(HotkeyEvent::Released(3)), state, &settings),
+            Action::Stop
+        );
+
+        let done = Event::PostProcessed(Some(Ok("Hello.".into())));
+        assert_eq!(
+            decide(done, state, &settings),
+            Action::OutputPostProcessed(Some(Ok("Hello.".into())))
+        );
+    }
+
+    #[test]
+    fn test_urgent_key_records_during_transcription() {
+        let mut settings = settings(0, None);
+        settings.urgent_key = Some(3);
+        settings.unload_after = Some(Duration::from_secs(60));
+        let transcribing = RecordingState {
+            transcribing: Some(Duration::from_secs(30)),
+            engine_idle: Some(Duration::from_secs(120)),
+            ..RecordingState::default()
+        };
+        assert_eq!(
+            decide(
+                Event::Hotkey(HotkeyEvent::Pressed(3)),
+                transcribing,
+                &settings
+            ),
+            Action::StartUrgent
+        );
+        // The model stays loaded for the transcription
+        assert_eq!(decide(Event::Tick, transcribing, &settings), Action::Ignore);
+        let done = Event::Transcribed(Ok("Hello.".into()));
+        assert_eq!(
+            decide(done, transcribing, &settings),
+            Action::OutputTranscription(Ok("Hello.".into()))
+        );
+
+        settings.watchdog = Some(Duration::from_secs(30));
+        assert_eq!(
+            decide(Event::Tick, transcribing, &settings),
+            Action::TranscriptionStuck
+        );
+    }
+
+    #[test]
+    fn test_latch_toggles_on_presses() {
+        let mut settings = settings(500, None);
+        settings.latch = true;
+        let press = Event::Hotkey(HotkeyEvent::Pressed(0));
+        let release = Event::Hotkey(HotkeyEvent::Released(0));
+        let idle = RecordingState::default();
+        assert_eq!(decide(press.clone(), idle, &settings), Action::Start(None));
+        // Letting go doesn't stop, not even before --min-hold
+        assert_eq!(
+            decide(release.clone(), recording(100, 0.1), &settings),
+            Action::Ignore
+        );
+        assert_eq!(decide(press, recording(3000, 3.0), &settings), Action::Stop);
+        assert_eq!(decide(release, idle, &settings), Action::Ignore);
     }
 }
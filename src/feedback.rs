@@ -0,0 +1,69 @@
+//! Local-only transcription ratings. Each rating is appended as a JSON line
+//! together with the conditions of the rated transcription, so bad results can
+//! later be correlated with microphone, length or settings. Transcript text is
+//! never stored.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    Good,
+    Bad,
+}
+
+/// Conditions of a transcription, kept until it is rated or replaced.
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptInfo {
+    /// Unix time when the transcript was output
+    pub timestamp: u64,
+    pub input: String,
+    pub audio_secs: f64,
+    pub transcribe_ms: f64,
+    pub words: usize,
+    pub post_processed: bool,
+    pub output_mode: String,
+}
+
+impl TranscriptInfo {
+    pub fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    rating: Rating,
+    #[serde(flatten)]
+    info: &'a TranscriptInfo,
+}
+
+pub fn log_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("parakeet-writer")
+        .join("feedback.jsonl")
+}
+
+/// Append a rating for `info` to the feedback log.
+pub fn record(info: &TranscriptInfo, rating: Rating) -> Result<()> {
+    let path = log_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create data directory")?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    let line = serde_json::to_string(&Entry { rating, info })?;
+    writeln!(file, "{}", line).context("Failed to write feedback")?;
+    Ok(())
+}
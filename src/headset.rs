@@ -0,0 +1,101 @@
+use crate::control::ControlCommand;
+use anyhow::Result;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Listen for play/pause buttons on headsets and media remotes and treat each
+/// press as a record toggle. Headset buttons send press and release together,
+/// so holding them for push-to-talk isn't possible.
+#[cfg(target_os = "linux")]
+pub fn spawn(tx: UnboundedSender<ControlCommand>) -> Result<()> {
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+    let open: Arc<Mutex<HashSet<PathBuf>>> = Arc::new(Mutex::new(HashSet::new()));
+    std::thread::spawn(move || loop {
+        for (path, device) in evdev::enumerate() {
+            if !is_headset(&device) || open.lock().unwrap().contains(&path) {
+                continue;
+            }
+            log::info!(
+                "Using headset button on {:?} ({})",
+                path,
+                device.name().unwrap_or("unknown")
+            );
+            open.lock().unwrap().insert(path.clone());
+            let open = Arc::clone(&open);
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                listen(device, tx);
+                log::info!("Headset {:?} disconnected", path);
+                open.lock().unwrap().remove(&path);
+            });
+        }
+        std::thread::sleep(RESCAN_INTERVAL);
+    });
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn spawn(_tx: UnboundedSender<ControlCommand>) -> Result<()> {
+    anyhow::bail!("Headset button triggers are only supported on Linux")
+}
+
+/// Devices with media buttons but no letter keys: Bluetooth AVRCP inputs,
+/// wired headset remotes and the like.
+#[cfg(target_os = "linux")]
+fn is_headset(device: &evdev::Device) -> bool {
+    use evdev::Key;
+
+    device.supported_keys().is_some_and(|keys| {
+        !keys.contains(Key::KEY_A)
+            && (keys.contains(Key::KEY_PLAYPAUSE)
+                || keys.contains(Key::KEY_PLAYCD)
+                || keys.contains(Key::KEY_PAUSECD))
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn listen(mut device: evdev::Device, tx: UnboundedSender<ControlCommand>) {
+    use evdev::{InputEventKind, Key};
+    use std::time::{Duration, Instant};
+
+    // Some headsets report a single tap more than once
+    const DEBOUNCE: Duration = Duration::from_millis(300);
+
+    // Keep the media player from also seeing the button
+    if let Err(e) = device.grab() {
+        log::warn!("Failed to grab headset device: {}", e);
+    }
+
+    let mut last_press: Option<Instant> = None;
+    loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) => {
+                log::debug!("Headset read error: {}", e);
+                return;
+            }
+        };
+        for event in events {
+            let InputEventKind::Key(key) = event.kind() else {
+                continue;
+            };
+            if event.value() != 1
+                || !matches!(key, Key::KEY_PLAYPAUSE | Key::KEY_PLAYCD | Key::KEY_PAUSECD)
+            {
+                continue;
+            }
+            if last_press.is_some_and(|t| t.elapsed() < DEBOUNCE) {
+                continue;
+            }
+            last_press = Some(Instant::now());
+            if tx.send(ControlCommand::ToggleRecording).is_err() {
+                return;
+            }
+        }
+    }
+}
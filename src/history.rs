@@ -0,0 +1,217 @@
+//! With `--history`, every transcription is appended to a local JSON lines file,
+//! so text that was typed into the wrong window can be found and output again.
+
+use crate::feedback::TranscriptInfo;
+use crate::output::{self, OutputOptions};
+use crate::report;
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// How much of a transcript `history list` shows.
+const PREVIEW_CHARS: usize = 60;
+
+#[derive(Subcommand, Debug, Clone, Copy)]
+pub enum HistoryCommand {
+    /// List recent transcriptions, newest first
+    List {
+        /// How many to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Print a transcription in full
+    Show {
+        /// Position in `history list` (1 is the newest)
+        #[arg(default_value_t = 1)]
+        n: usize,
+    },
+    /// Copy a transcription to the clipboard
+    Copy {
+        /// Position in `history list` (1 is the newest)
+        #[arg(default_value_t = 1)]
+        n: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix time when the transcription finished
+    pub timestamp: u64,
+    pub audio_secs: f64,
+    /// The transcript as the engine produced it
+    pub raw_text: String,
+    /// The transcript as output, after post-processing
+    pub text: String,
+}
+
+#[derive(Serialize)]
+struct ListReport<'a> {
+    path: PathBuf,
+    entries: Vec<Numbered<'a>>,
+}
+
+#[derive(Serialize)]
+struct Numbered<'a> {
+    n: usize,
+    #[serde(flatten)]
+    entry: &'a Entry,
+}
+
+pub fn history_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("parakeet-writer")
+        .join("history.jsonl")
+}
+
+/// Append `entry` to the history file.
+pub fn append(entry: &Entry) -> Result<()> {
+    let path = history_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create data directory")?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    let line = serde_json::to_string(entry)?;
+    writeln!(file, "{}", line).context("Failed to write history")?;
+    Ok(())
+}
+
+/// All entries, oldest first. Lines that don't parse, e.g. one cut short by a
+/// crash, are skipped.
+pub fn load() -> Result<Vec<Entry>> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file = std::fs::File::open(&path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.with_context(|| format!("Failed to read {:?}", path))?;
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => log::debug!("Skipping history line: {}", e),
+        }
+    }
+    Ok(entries)
+}
+
+/// The `n`th newest of `entries`, counting from 1.
+fn nth_newest(entries: &[Entry], n: usize) -> Option<&Entry> {
+    n.checked_sub(1)
+        .and_then(|i| entries.len().checked_sub(i + 1))
+        .map(|i| &entries[i])
+}
+
+/// The most recent transcript, to output again after a restart.
+pub fn last_text() -> Option<String> {
+    match load() {
+        Ok(entries) => entries.last().map(|entry| entry.text.clone()),
+        Err(e) => {
+            log::warn!("Failed to read history: {:#}", e);
+            None
+        }
+    }
+}
+
+pub async fn run(command: HistoryCommand, output: &OutputOptions) -> Result<()> {
+    let entries = load()?;
+    let find = |n: usize| {
+        nth_newest(&entries, n).with_context(|| format!("No transcription {} in the history", n))
+    };
+    match command {
+        HistoryCommand::List { limit } => {
+            let report = ListReport {
+                path: history_path(),
+                entries: entries
+                    .iter()
+                    .rev()
+                    .take(limit)
+                    .enumerate()
+                    .map(|(i, entry)| Numbered { n: i + 1, entry })
+                    .collect(),
+            };
+            if report::json() {
+                return report::emit(&report);
+            }
+            if report.entries.is_empty() {
+                println!("No transcriptions in {:?}", report.path);
+            }
+            let now = TranscriptInfo::now();
+            for Numbered { n, entry } in &report.entries {
+                println!(
+                    "{:>3}  {:>8}  {:>5.1}s  {}",
+                    n,
+                    age(now.saturating_sub(entry.timestamp)),
+                    entry.audio_secs,
+                    preview(&entry.text)
+                );
+            }
+        }
+        HistoryCommand::Show { n } => {
+            let entry = find(n)?;
+            if report::json() {
+                return report::emit(entry);
+            }
+            println!("{}", entry.text);
+        }
+        HistoryCommand::Copy { n } => {
+            let entry = find(n)?;
+            let metadata = serde_json::to_string(entry)?;
+            output::copy_to_clipboard(&entry.text, Some(&metadata), output).await?;
+        }
+    }
+    Ok(())
+}
+
+/// `secs` ago, in the largest whole unit.
+fn age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// The start of `text` on one line.
+fn preview(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nth_newest() {
+        let entry = |text: &str| Entry {
+            timestamp: 0,
+            audio_secs: 1.0,
+            raw_text: text.into(),
+            text: text.into(),
+        };
+        let entries = [entry("first"), entry("second")];
+        assert_eq!(nth_newest(&entries, 1).unwrap().text, "second");
+        assert_eq!(nth_newest(&entries, 2).unwrap().text, "first");
+        assert_eq!(nth_newest(&entries, 3), None);
+        assert_eq!(nth_newest(&entries, 0), None);
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("Short\nnote."), "Short note.");
+        assert_eq!(preview(&"a".repeat(70)), format!("{}…", "a".repeat(60)));
+        assert_eq!(age(59), "59s ago");
+        assert_eq!(age(7200), "2h ago");
+    }
+}
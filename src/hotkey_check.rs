@@ -0,0 +1,238 @@
+use crate::error::AppError;
+use crate::input::{parse_hotkey, Hotkey, Key};
+
+/// Keys suggested as alternatives when the chosen hotkey is taken.
+const CANDIDATES: [Key; 15] = [
+    Key::F(1),
+    Key::F(2),
+    Key::F(3),
+    Key::F(4),
+    Key::F(5),
+    Key::F(6),
+    Key::F(7),
+    Key::F(8),
+    Key::F(9),
+    Key::F(10),
+    Key::F(11),
+    Key::F(12),
+    Key::ScrollLock,
+    Key::Pause,
+    Key::Insert,
+];
+
+/// Fail if two of the `(option, hotkey)` pairs use the same hotkey, as only
+/// one of them would ever react.
+pub fn check_duplicates(hotkeys: &[(String, Hotkey)]) -> Result<(), AppError> {
+    for (i, (second, hotkey)) in hotkeys.iter().enumerate() {
+        if let Some((first, _)) = hotkeys[..i].iter().find(|(_, h)| h == hotkey) {
+            return Err(AppError::HotkeyConflict {
+                first: first.clone(),
+                second: second.clone(),
+                hotkey: hotkey.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Warn if the desktop environment appears to have `hotkey` bound already, since
+/// the desktop keeps reacting to it while we record.
+pub fn warn_conflicts(hotkey: &Hotkey) {
+    let Some((source, bound)) = desktop_bindings() else {
+        log::debug!("No known desktop environment to check for hotkey conflicts");
+        return;
+    };
+    log::debug!("Found {} bindings in {}", bound.len(), source);
+
+    if !bound.contains(hotkey) {
+        return;
+    }
+
+    let free: Vec<String> = CANDIDATES
+        .iter()
+        .map(|&key| Hotkey::new(key))
+        .filter(|candidate| !bound.contains(candidate))
+        .take(3)
+        .map(|candidate| candidate.to_string())
+        .collect();
+
+    log::warn!(
+        "{} appears to be bound in {}, so the desktop may react to it as well",
+        hotkey,
+        source
+    );
+    if !free.is_empty() {
+        log::warn!("Keys that look free: {}", free.join(", "));
+    }
+}
+
+/// Build a hotkey from modifier and key names as written in desktop configs.
+fn to_hotkey(modifiers: &[&str], key: &str) -> Option<Hotkey> {
+    let mut parts = Vec::new();
+    for modifier in modifiers {
+        match modifier.to_lowercase().as_str() {
+            "shift" => parts.push("Shift"),
+            "ctrl" | "control" | "primary" => parts.push("Ctrl"),
+            "alt" | "mod1" => parts.push("Alt"),
+            "super" | "meta" | "mod4" | "logo" => parts.push("Super"),
+            _ => return None,
+        }
+    }
+    let key = match key {
+        "Ins" => "Insert",
+        "Scroll_Lock" => "ScrollLock",
+        other => other,
+    };
+    parts.push(key);
+    parse_hotkey(&parts.join("+")).ok()
+}
+
+#[cfg(target_os = "linux")]
+fn desktop_bindings() -> Option<(&'static str, Vec<Hotkey>)> {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_lowercase();
+    let config = dirs::config_dir()?;
+
+    if desktop.contains("gnome") || desktop.contains("unity") || desktop.contains("budgie") {
+        Some(("GNOME keybindings", gnome_bindings()))
+    } else if desktop.contains("kde") {
+        let text = std::fs::read_to_string(config.join("kglobalshortcutsrc")).ok()?;
+        Some(("KDE global shortcuts", kde_bindings(&text)))
+    } else if desktop.contains("sway") || desktop.contains("i3") {
+        let path = if desktop.contains("sway") {
+            config.join("sway/config")
+        } else {
+            config.join("i3/config")
+        };
+        let text = std::fs::read_to_string(path).ok()?;
+        Some(("sway/i3 config", bindsym_bindings(&text)))
+    } else if desktop.contains("hyprland") {
+        let text = std::fs::read_to_string(config.join("hypr/hyprland.conf")).ok()?;
+        Some(("Hyprland config", hyprland_bindings(&text)))
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn gnome_bindings() -> Vec<Hotkey> {
+    const SCHEMAS: [&str; 4] = [
+        "org.gnome.settings-daemon.plugins.media-keys",
+        "org.gnome.desktop.wm.keybindings",
+        "org.gnome.shell.keybindings",
+        "org.gnome.mutter.keybindings",
+    ];
+
+    let mut bound = Vec::new();
+    for schema in SCHEMAS {
+        let Ok(output) = std::process::Command::new("gsettings")
+            .args(["list-recursively", schema])
+            .output()
+        else {
+            continue;
+        };
+        let text = String::from_utf8_lossy(&output.stdout);
+        // Values look like ['<Shift><Control>F9', 'Pause']
+        for accel in text.split('\'').skip(1).step_by(2) {
+            let key = accel.rsplit('>').next().unwrap_or(accel);
+            let modifiers: Vec<&str> = accel
+                .split('>')
+                .filter_map(|part| part.strip_prefix('<'))
+                .collect();
+            bound.extend(to_hotkey(&modifiers, key));
+        }
+    }
+    bound
+}
+
+/// Lines look like `action=Ctrl+F9\tMeta+F9,default,Friendly name`.
+#[cfg(target_os = "linux")]
+fn kde_bindings(text: &str) -> Vec<Hotkey> {
+    text.lines()
+        .filter(|line| !line.starts_with('[') && !line.starts_with('_'))
+        .filter_map(|line| line.split_once('='))
+        .filter_map(|(_, value)| value.split(',').next())
+        .flat_map(|shortcuts| shortcuts.split('\t'))
+        .filter_map(|shortcut| {
+            let parts: Vec<&str> = shortcut.split('+').collect();
+            let (key, modifiers) = parts.split_last()?;
+            to_hotkey(modifiers, key)
+        })
+        .collect()
+}
+
+/// sway/i3 lines look like `bindsym --release Shift+F9 exec ...`.
+#[cfg(target_os = "linux")]
+fn bindsym_bindings(text: &str) -> Vec<Hotkey> {
+    text.lines()
+        .filter_map(|line| line.trim().strip_prefix("bindsym "))
+        .filter_map(|rest| rest.split_whitespace().find(|t| !t.starts_with("--")))
+        .filter_map(|combo| {
+            let parts: Vec<&str> = combo.split('+').collect();
+            let (key, modifiers) = parts.split_last()?;
+            to_hotkey(modifiers, key)
+        })
+        .collect()
+}
+
+/// Hyprland lines look like `bind = SHIFT CTRL, F9, exec, ...`.
+#[cfg(target_os = "linux")]
+fn hyprland_bindings(text: &str) -> Vec<Hotkey> {
+    text.lines()
+        .filter(|line| line.trim_start().starts_with("bind"))
+        .filter_map(|line| line.split_once('=').map(|(_, value)| value))
+        .filter_map(|value| {
+            let mut fields = value.split(',');
+            let modifiers: Vec<&str> = fields.next()?.split_whitespace().collect();
+            to_hotkey(&modifiers, fields.next()?.trim())
+        })
+        .collect()
+}
+
+/// Unless "Use F1, F2, etc. keys as standard function keys" is enabled, the top
+/// row on Mac keyboards controls brightness, media and volume.
+#[cfg(target_os = "macos")]
+fn desktop_bindings() -> Option<(&'static str, Vec<Hotkey>)> {
+    let output = std::process::Command::new("defaults")
+        .args(["read", "-g", "com.apple.keyboard.fnState"])
+        .output()
+        .ok()?;
+    if String::from_utf8_lossy(&output.stdout).trim() == "1" {
+        return None;
+    }
+    let media_keys = CANDIDATES[..12]
+        .iter()
+        .map(|&key| Hotkey::new(key))
+        .collect();
+    Some(("macOS media keys (F1-F12)", media_keys))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn desktop_bindings() -> Option<(&'static str, Vec<Hotkey>)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_duplicates() {
+        let key = |name: &str, key| (name.to_string(), parse_hotkey(key).unwrap());
+        let mut hotkeys = vec![
+            key("--key", "F9"),
+            key("--cycle-key", "F10"),
+            key("Profile \"code\"", "ctrl+F9"),
+        ];
+        assert!(check_duplicates(&hotkeys).is_ok());
+        hotkeys.push(key("--repeat-key", "F10"));
+        let err = check_duplicates(&hotkeys).unwrap_err();
+        assert!(matches!(
+            err,
+            AppError::HotkeyConflict { ref first, ref second, .. }
+                if first == "--cycle-key" && second == "--repeat-key"
+        ));
+        assert_eq!(err.exit_code(), 8);
+    }
+}
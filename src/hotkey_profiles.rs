@@ -0,0 +1,189 @@
+//! `--profiles`: extra hotkeys that each record with their own post-processing
+//! prompt and output mode, e.g. one for prose, one for coding prompts and one
+//! for raw text on the clipboard.
+
+use crate::output::{AutoSubmit, OutputMode};
+use crate::reminder::ReminderTarget;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfilesFile {
+    #[serde(default)]
+    profile: Vec<Profile>,
+}
+
+/// A `[[profile]]` table. Whatever it leaves out is taken from the command line.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String,
+    /// Hotkey in `--key` syntax; the `--key` hotkey itself can be given a profile
+    pub key: String,
+    /// Whether to post-process (default: when `prompt` or `output_language` is
+    /// set, or `--post-process`)
+    pub post_process: Option<bool>,
+    /// System prompt for post-processing instead of the built-in one
+    pub prompt: Option<String>,
+    /// Replaces `--output-language`
+    pub output_language: Option<String>,
+    pub output: Option<OutputMode>,
+    /// Replaces `--auto-submit`
+    pub auto_submit: Option<AutoSubmit>,
+    /// Replaces `--initial-prompt`
+    pub initial_prompt: Option<String>,
+    /// Add every dictation as a to-do (see `--todo`), without the "To-do:" prefix
+    #[serde(default)]
+    pub todo: bool,
+    /// Replaces `--reminders`
+    pub reminders: Option<ReminderTarget>,
+    /// Seconds after a recording starts before the hotkey records again
+    pub cooldown: Option<u64>,
+    /// Most recordings started in any minute
+    pub max_per_minute: Option<usize>,
+}
+
+impl Profile {
+    /// Whether transcripts of this profile are post-processed, given `--post-process`.
+    pub fn post_process(&self, default: bool) -> bool {
+        self.post_process
+            .unwrap_or(default || self.prompt.is_some() || self.output_language.is_some())
+    }
+
+    /// Whether Enter is pressed after typing, given `--auto-submit`.
+    pub fn auto_submit(&self, default: AutoSubmit) -> AutoSubmit {
+        self.auto_submit.unwrap_or(default)
+    }
+
+    /// How long until the hotkey records again under `cooldown` and
+    /// `max_per_minute`, given when its recordings started (oldest first), or
+    /// `None` if it can now.
+    pub fn wait(&self, starts: &[Instant], now: Instant) -> Option<Duration> {
+        let mut wait = Duration::ZERO;
+        if let (Some(cooldown), Some(last)) = (self.cooldown, starts.last()) {
+            wait = Duration::from_secs(cooldown).saturating_sub(now - *last);
+        }
+        if let Some(max) = self.max_per_minute {
+            let recent: Vec<_> = starts.iter().filter(|s| now - **s < MINUTE).collect();
+            if recent.len() >= max {
+                // Once the oldest that counts is a minute old
+                let oldest = *recent[recent.len() - max];
+                wait = wait.max(MINUTE - (now - oldest));
+            }
+        }
+        (!wait.is_zero()).then_some(wait)
+    }
+
+    /// Whether a recording that started at `start` still counts towards the limits.
+    pub fn counts(&self, start: Instant, now: Instant) -> bool {
+        let window = self.cooldown.map_or(Duration::ZERO, Duration::from_secs);
+        let window = if self.max_per_minute.is_some() {
+            window.max(MINUTE)
+        } else {
+            window
+        };
+        now - start < window
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<Profile>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    parse(&contents).with_context(|| format!("Invalid profiles {:?}", path))
+}
+
+fn parse(contents: &str) -> Result<Vec<Profile>> {
+    let file: ProfilesFile = toml::from_str(contents)?;
+    for (i, profile) in file.profile.iter().enumerate() {
+        if file.profile[..i].iter().any(|p| p.name == profile.name) {
+            anyhow::bail!("Profile {:?} is defined twice", profile.name);
+        }
+        if profile.max_per_minute == Some(0) {
+            anyhow::bail!(
+                "Profile {:?}: max_per_minute must be at least 1",
+                profile.name
+            );
+        }
+    }
+    Ok(file.profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profiles() {
+        let profiles = parse(
+            r#"
+            [[profile]]
+            name = "code"
+            key = "F10"
+            prompt = "Turn this into a coding prompt."
+
+            [[profile]]
+            name = "raw"
+            key = "F8"
+            output = "clipboard"
+            auto_submit = "on"
+
+            [[profile]]
+            name = "terminal"
+            key = "F7"
+            auto_submit = "confirm"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profiles.len(), 3);
+        // A prompt implies post-processing, otherwise --post-process decides
+        assert!(profiles[0].post_process(false));
+        assert!(!profiles[1].post_process(false));
+        assert!(profiles[1].post_process(true));
+        assert!(matches!(profiles[1].output, Some(OutputMode::Clipboard)));
+        assert_eq!(profiles[0].auto_submit(AutoSubmit::On), AutoSubmit::On);
+        assert_eq!(profiles[1].auto_submit(AutoSubmit::Off), AutoSubmit::On);
+        assert_eq!(profiles[1].auto_submit(AutoSubmit::Confirm), AutoSubmit::On);
+        // Asks first, even with --auto-submit on
+        assert_eq!(profiles[2].auto_submit(AutoSubmit::On), AutoSubmit::Confirm);
+        let yes = "[[profile]]\nname = \"a\"\nkey = \"F8\"\nauto_submit = true";
+        assert!(parse(yes).is_err());
+
+        let twice =
+            "[[profile]]\nname = \"a\"\nkey = \"F8\"\n[[profile]]\nname = \"a\"\nkey = \"F9\"";
+        assert!(parse(twice).is_err());
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        let profiles = parse(
+            r#"
+            [[profile]]
+            name = "demo"
+            key = "F8"
+            cooldown = 5
+            max_per_minute = 3
+            "#,
+        )
+        .unwrap();
+        let demo = &profiles[0];
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(demo.wait(&[], start), None);
+        assert_eq!(demo.wait(&[at(0)], at(2)), Some(Duration::from_secs(3)));
+        assert_eq!(demo.wait(&[at(0)], at(5)), None);
+        // The fourth in a minute waits for the first to drop out
+        let starts = [at(0), at(10), at(20)];
+        assert_eq!(demo.wait(&starts, at(30)), Some(Duration::from_secs(30)));
+        assert_eq!(demo.wait(&starts, at(60)), None);
+        assert!(demo.counts(at(0), at(59)));
+        assert!(!demo.counts(at(0), at(60)));
+
+        let zero = "[[profile]]\nname = \"a\"\nkey = \"F8\"\nmax_per_minute = 0";
+        assert!(parse(zero).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+//! Translations for user-facing messages. The language comes from the usual
+//! locale variables (`LC_ALL`, `LC_MESSAGES`, `LANG`) and falls back to English.
+
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    De,
+    Es,
+    Fr,
+}
+
+impl Lang {
+    /// Parse a locale such as `de_DE.UTF-8`; `None` for unsupported languages.
+    fn from_locale(locale: &str) -> Option<Self> {
+        match locale.get(..2)? {
+            "en" => Some(Lang::En),
+            "de" => Some(Lang::De),
+            "es" => Some(Lang::Es),
+            "fr" => Some(Lang::Fr),
+            _ => None,
+        }
+    }
+
+    fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|locale| Lang::from_locale(&locale))
+            .unwrap_or(Lang::En)
+    }
+}
+
+fn current() -> Lang {
+    static LANG: OnceLock<Lang> = OnceLock::new();
+    *LANG.get_or_init(Lang::detect)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Msg {
+    Error,
+    Listening,
+    HoldToRecord,
+    ProfileKey,
+    UrgentKey,
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    AccessibilityNote,
+    HeadsetHint,
+    PressCtrlC,
+    ConsoleHelp,
+    Exiting,
+    Recording,
+    RecordingUrgent,
+    RateLimited,
+    RecordingProfile,
+    TodoAdded,
+    ReminderAdded,
+    Transcribing,
+    TranscribingChunk,
+    PostProcessing,
+    NoSpeech,
+    ReleasedEarly,
+    MaxDurationReached,
+    TranscriptionStuck,
+    OutputStuck,
+    PostProcessingOn,
+    PostProcessingOff,
+    StatusIdle,
+    StatusRecording,
+    StatusOutput,
+    On,
+    Off,
+    CopiedToClipboard,
+    SubmitConfirm,
+    SubmitDropped,
+    RatingSaved,
+    NothingToRate,
+    FeedbackDisabled,
+    ComposeBuffer,
+    ComposeEmpty,
+    ComposeDiscarded,
+    ComposeDisabled,
+    DeviceSwitched,
+    NothingToRepeat,
+    TitleRecording,
+    TitleTranscribing,
+    TitlePostProcessing,
+    TitleLoadingModel,
+    NotifyDone,
+    NotifyFailed,
+    NoMicrophone,
+    InputDeviceNotFound,
+    NoPermission,
+    ModelMissing,
+    OutputToolMissing,
+    FeatureMissing,
+    HotkeyConflict,
+}
+
+/// The message in the current language. Placeholders are written `{}`; use
+/// [`trf`] to fill them in.
+pub fn tr(msg: Msg) -> &'static str {
+    text(current(), msg)
+}
+
+/// The message in the current language with each `{}` replaced by the next
+/// argument.
+pub fn trf(msg: Msg, args: &[&dyn Display]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut parts = tr(msg).split("{}").peekable();
+    while let Some(part) = parts.next() {
+        out.push_str(part);
+        if parts.peek().is_some() {
+            if let Some(arg) = args.next() {
+                out.push_str(&arg.to_string());
+            }
+        }
+    }
+    out
+}
+
+fn text(lang: Lang, msg: Msg) -> &'static str {
+    match lang {
+        Lang::En => en(msg),
+        Lang::De => de(msg),
+        Lang::Es => es(msg),
+        Lang::Fr => fr(msg),
+    }
+}
+
+fn en(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Error => "Error",
+        Msg::Listening => "Listening for {}...",
+        Msg::HoldToRecord => "Hold the key to record, release to transcribe.",
+        Msg::ProfileKey => "{} records with profile '{}'.",
+        Msg::UrgentKey => "{} records urgent dictations, output without post-processing.",
+        Msg::AccessibilityNote => "Note: You may need to grant Accessibility permissions.",
+        Msg::HeadsetHint => "Press the headset play/pause button to start/stop recording.",
+        Msg::PressCtrlC => "Press Ctrl+C to exit.",
+        Msg::ConsoleHelp => {
+            "Commands: r = start/stop recording, p = toggle post-processing, +/- = rate last transcription, o/c = output/discard compose buffer, s = status, q = quit"
+        }
+        Msg::Exiting => "Exiting.",
+        Msg::Recording => "Recording...",
+        Msg::RecordingUrgent => "Recording (urgent)...",
+        Msg::RateLimited => "Profile '{}' is on cooldown, try again in {}s.",
+        Msg::RecordingProfile => "Recording ({})...",
+        Msg::TodoAdded => "Added to-do: {}",
+        Msg::ReminderAdded => "Reminder for {}: {}",
+        Msg::Transcribing => "Transcribing...",
+        Msg::TranscribingChunk => "Transcribing chunk...",
+        Msg::PostProcessing => "Post-processing...",
+        Msg::NoSpeech => "(no speech detected)",
+        Msg::ReleasedEarly => "(released after {}, ignoring)",
+        Msg::MaxDurationReached => "Recording stopped after {} (--max-duration)",
+        Msg::TranscriptionStuck => "Transcription took longer than {}s, gave up and reloading the model",
+        Msg::OutputStuck => "Output took longer than {}s, gave up",
+        Msg::PostProcessingOn => "Post-processing on",
+        Msg::PostProcessingOff => "Post-processing off",
+        Msg::StatusIdle => "Status: idle",
+        Msg::StatusRecording => "Status: recording ({}s)",
+        Msg::StatusOutput => "Output: {}, post-processing: {}",
+        Msg::On => "on",
+        Msg::Off => "off",
+        Msg::CopiedToClipboard => "Copied to clipboard: {}",
+        Msg::SubmitConfirm => "Tap the hotkey within {}s to press Enter",
+        Msg::SubmitDropped => "(not submitted)",
+        Msg::RatingSaved => "Rating saved to {}",
+        Msg::NothingToRate => "(nothing to rate)",
+        Msg::FeedbackDisabled => "(ratings are disabled, start with --feedback)",
+        Msg::ComposeBuffer => "Compose buffer: {}",
+        Msg::ComposeEmpty => "(compose buffer is empty)",
+        Msg::ComposeDiscarded => "(compose buffer discarded)",
+        Msg::ComposeDisabled => "(compose mode is off, start with --compose)",
+        Msg::DeviceSwitched => "Microphone: {}",
+        Msg::NothingToRepeat => "(nothing to repeat)",
+        Msg::TitleRecording => "● REC",
+        Msg::TitleTranscribing => "… TRANSCRIBING",
+        Msg::TitlePostProcessing => "… POST-PROCESSING",
+        Msg::TitleLoadingModel => "… LOADING MODEL",
+        Msg::NotifyDone => "Transcribed",
+        Msg::NotifyFailed => "Transcription failed",
+        Msg::NoMicrophone => "No audio input device available",
+        Msg::InputDeviceNotFound => "Input device not found: {} (see `list-devices`, or /proc/bus/input/devices for --switch)",
+        Msg::NoPermission => "Cannot access keyboard input: {}",
+        Msg::ModelMissing => "Model not found at {}",
+        Msg::OutputToolMissing => "Output tool `{}` not found (install {})",
+        Msg::FeatureMissing => "{} is not compiled in (rebuild with `--features {}`)",
+        Msg::HotkeyConflict => "{} and {} use the same hotkey {}",
+    }
+}
+
+fn de(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Error => "Fehler",
+        Msg::Listening => "Warte auf {}...",
+        Msg::HoldToRecord => "Taste halten zum Aufnehmen, loslassen zum Transkribieren.",
+        Msg::ProfileKey => "{} nimmt mit dem Profil '{}' auf.",
+        Msg::UrgentKey => "{} nimmt dringende Diktate auf, ausgegeben ohne Nachbearbeitung.",
+        Msg::AccessibilityNote => {
+            "Hinweis: Eventuell müssen Bedienungshilfen-Berechtigungen erteilt werden."
+        }
+        Msg::HeadsetHint => {
+            "Play/Pause-Taste am Headset drücken, um die Aufnahme zu starten/stoppen."
+        }
+        Msg::PressCtrlC => "Strg+C zum Beenden.",
+        Msg::ConsoleHelp => {
+            "Befehle: r = Aufnahme starten/stoppen, p = Nachbearbeitung umschalten, +/- = letzte Transkription bewerten, o/c = Entwurf ausgeben/verwerfen, s = Status, q = Beenden"
+        }
+        Msg::Exiting => "Beende.",
+        Msg::Recording => "Aufnahme läuft...",
+        Msg::RecordingUrgent => "Aufnahme läuft (dringend)...",
+        Msg::RateLimited => "Profil '{}' pausiert, erneut versuchen in {} s.",
+        Msg::RecordingProfile => "Aufnahme läuft ({})...",
+        Msg::TodoAdded => "Aufgabe hinzugefügt: {}",
+        Msg::ReminderAdded => "Erinnerung für {}: {}",
+        Msg::Transcribing => "Transkribiere...",
+        Msg::TranscribingChunk => "Transkribiere Abschnitt...",
+        Msg::PostProcessing => "Nachbearbeitung...",
+        Msg::NoSpeech => "(keine Sprache erkannt)",
+        Msg::ReleasedEarly => "(nach {} losgelassen, ignoriert)",
+        Msg::MaxDurationReached => "Aufnahme nach {} beendet (--max-duration)",
+        Msg::TranscriptionStuck => "Transkription dauerte länger als {}s, abgebrochen, Modell wird neu geladen",
+        Msg::OutputStuck => "Ausgabe dauerte länger als {}s, abgebrochen",
+        Msg::PostProcessingOn => "Nachbearbeitung an",
+        Msg::PostProcessingOff => "Nachbearbeitung aus",
+        Msg::StatusIdle => "Status: bereit",
+        Msg::StatusRecording => "Status: Aufnahme ({}s)",
+        Msg::StatusOutput => "Ausgabe: {}, Nachbearbeitung: {}",
+        Msg::On => "an",
+        Msg::Off => "aus",
+        Msg::CopiedToClipboard => "In die Zwischenablage kopiert: {}",
+        Msg::SubmitConfirm => "Taste innerhalb von {}s antippen, um Enter zu drücken",
+        Msg::SubmitDropped => "(nicht abgeschickt)",
+        Msg::RatingSaved => "Bewertung gespeichert in {}",
+        Msg::NothingToRate => "(nichts zu bewerten)",
+        Msg::FeedbackDisabled => "(Bewertungen sind deaktiviert, mit --feedback starten)",
+        Msg::ComposeBuffer => "Entwurf: {}",
+        Msg::ComposeEmpty => "(Entwurf ist leer)",
+        Msg::ComposeDiscarded => "(Entwurf verworfen)",
+        Msg::ComposeDisabled => "(Entwurfsmodus ist aus, mit --compose starten)",
+        Msg::DeviceSwitched => "Mikrofon: {}",
+        Msg::NothingToRepeat => "(nichts zu wiederholen)",
+        Msg::TitleRecording => "● REC",
+        Msg::TitleTranscribing => "… TRANSKRIBIERE",
+        Msg::TitlePostProcessing => "… NACHBEARBEITUNG",
+        Msg::TitleLoadingModel => "… LADE MODELL",
+        Msg::NotifyDone => "Transkribiert",
+        Msg::NotifyFailed => "Transkription fehlgeschlagen",
+        Msg::NoMicrophone => "Kein Audio-Eingabegerät verfügbar",
+        Msg::InputDeviceNotFound => "Eingabegerät nicht gefunden: {} (siehe `list-devices` bzw. /proc/bus/input/devices für --switch)",
+        Msg::NoPermission => "Kein Zugriff auf Tastatureingaben: {}",
+        Msg::ModelMissing => "Modell nicht gefunden unter {}",
+        Msg::OutputToolMissing => "Ausgabeprogramm `{}` nicht gefunden ({} installieren)",
+        Msg::FeatureMissing => "{} ist nicht einkompiliert (mit `--features {}` neu bauen)",
+        Msg::HotkeyConflict => "{} und {} verwenden dieselbe Taste {}",
+    }
+}
+
+fn es(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Error => "Error",
+        Msg::Listening => "Escuchando {}...",
+        Msg::HoldToRecord => "Mantén la tecla para grabar, suéltala para transcribir.",
+        Msg::ProfileKey => "{} graba con el perfil '{}'.",
+        Msg::UrgentKey => "{} graba dictados urgentes, escritos sin posprocesado.",
+        Msg::AccessibilityNote => "Nota: Puede que tengas que conceder permisos de Accesibilidad.",
+        Msg::HeadsetHint => {
+            "Pulsa el botón de reproducir/pausa del auricular para iniciar/detener la grabación."
+        }
+        Msg::PressCtrlC => "Pulsa Ctrl+C para salir.",
+        Msg::ConsoleHelp => {
+            "Comandos: r = iniciar/detener grabación, p = activar/desactivar posprocesado, +/- = valorar la última transcripción, o/c = enviar/descartar el borrador, s = estado, q = salir"
+        }
+        Msg::Exiting => "Saliendo.",
+        Msg::Recording => "Grabando...",
+        Msg::RecordingUrgent => "Grabando (urgente)...",
+        Msg::RateLimited => "El perfil '{}' está en pausa, inténtalo de nuevo en {} s.",
+        Msg::RecordingProfile => "Grabando ({})...",
+        Msg::TodoAdded => "Tarea añadida: {}",
+        Msg::ReminderAdded => "Recordatorio para {}: {}",
+        Msg::Transcribing => "Transcribiendo...",
+        Msg::TranscribingChunk => "Transcribiendo fragmento...",
+        Msg::PostProcessing => "Posprocesando...",
+        Msg::NoSpeech => "(no se detectó voz)",
+        Msg::ReleasedEarly => "(soltada tras {}, se ignora)",
+        Msg::MaxDurationReached => "Grabación detenida tras {} (--max-duration)",
+        Msg::TranscriptionStuck => "La transcripción tardó más de {}s, se abandona y se recarga el modelo",
+        Msg::OutputStuck => "La salida tardó más de {}s, se abandona",
+        Msg::PostProcessingOn => "Posprocesado activado",
+        Msg::PostProcessingOff => "Posprocesado desactivado",
+        Msg::StatusIdle => "Estado: inactivo",
+        Msg::StatusRecording => "Estado: grabando ({}s)",
+        Msg::StatusOutput => "Salida: {}, posprocesado: {}",
+        Msg::On => "activado",
+        Msg::Off => "desactivado",
+        Msg::CopiedToClipboard => "Copiado al portapapeles: {}",
+        Msg::SubmitConfirm => "Pulsa la tecla antes de {}s para enviar con Intro",
+        Msg::SubmitDropped => "(no enviado)",
+        Msg::RatingSaved => "Valoración guardada en {}",
+        Msg::NothingToRate => "(nada que valorar)",
+        Msg::FeedbackDisabled => "(las valoraciones están desactivadas, inicia con --feedback)",
+        Msg::ComposeBuffer => "Borrador: {}",
+        Msg::ComposeEmpty => "(el borrador está vacío)",
+        Msg::ComposeDiscarded => "(borrador descartado)",
+        Msg::ComposeDisabled => "(el modo borrador está desactivado, inicia con --compose)",
+        Msg::DeviceSwitched => "Micrófono: {}",
+        Msg::NothingToRepeat => "(nada que repetir)",
+        Msg::TitleRecording => "● REC",
+        Msg::TitleTranscribing => "… TRANSCRIBIENDO",
+        Msg::TitlePostProcessing => "… POSPROCESANDO",
+        Msg::TitleLoadingModel => "… CARGANDO MODELO",
+        Msg::NotifyDone => "Transcrito",
+        Msg::NotifyFailed => "Transcripción fallida",
+        Msg::NoMicrophone => "No hay ningún dispositivo de entrada de audio",
+        Msg::InputDeviceNotFound => "Dispositivo de entrada no encontrado: {} (ver `list-devices`, o /proc/bus/input/devices para --switch)",
+        Msg::NoPermission => "No se puede acceder a la entrada del teclado: {}",
+        Msg::ModelMissing => "Modelo no encontrado en {}",
+        Msg::OutputToolMissing => "Herramienta de salida `{}` no encontrada (instala {})",
+        Msg::FeatureMissing => "{} no está compilado (recompila con `--features {}`)",
+        Msg::HotkeyConflict => "{} y {} usan la misma tecla {}",
+    }
+}
+
+fn fr(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Error => "Erreur",
+        Msg::Listening => "En attente de {}...",
+        Msg::HoldToRecord => {
+            "Maintenez la touche pour enregistrer, relâchez-la pour transcrire."
+        }
+        Msg::ProfileKey => "{} enregistre avec le profil « {} ».",
+        Msg::UrgentKey => "{} enregistre les dictées urgentes, écrites sans post-traitement.",
+        Msg::AccessibilityNote => {
+            "Remarque : il peut être nécessaire d'accorder les autorisations d'Accessibilité."
+        }
+        Msg::HeadsetHint => {
+            "Appuyez sur le bouton lecture/pause du casque pour démarrer/arrêter l'enregistrement."
+        }
+        Msg::PressCtrlC => "Appuyez sur Ctrl+C pour quitter.",
+        Msg::ConsoleHelp => {
+            "Commandes : r = démarrer/arrêter l'enregistrement, p = activer/désactiver le post-traitement, +/- = noter la dernière transcription, o/c = envoyer/effacer le brouillon, s = état, q = quitter"
+        }
+        Msg::Exiting => "Fermeture.",
+        Msg::Recording => "Enregistrement...",
+        Msg::RecordingUrgent => "Enregistrement (urgent)...",
+        Msg::RateLimited => "Le profil « {} » est en pause, réessayez dans {} s.",
+        Msg::RecordingProfile => "Enregistrement ({})...",
+        Msg::TodoAdded => "Tâche ajoutée : {}",
+        Msg::ReminderAdded => "Rappel pour {} : {}",
+        Msg::Transcribing => "Transcription...",
+        Msg::TranscribingChunk => "Transcription du segment...",
+        Msg::PostProcessing => "Post-traitement...",
+        Msg::NoSpeech => "(aucune parole détectée)",
+        Msg::ReleasedEarly => "(relâchée après {}, ignorée)",
+        Msg::MaxDurationReached => "Enregistrement arrêté après {} (--max-duration)",
+        Msg::TranscriptionStuck => "La transcription a pris plus de {}s, abandonnée, rechargement du modèle",
+        Msg::OutputStuck => "La sortie a pris plus de {}s, abandonnée",
+        Msg::PostProcessingOn => "Post-traitement activé",
+        Msg::PostProcessingOff => "Post-traitement désactivé",
+        Msg::StatusIdle => "État : inactif",
+        Msg::StatusRecording => "État : enregistrement ({}s)",
+        Msg::StatusOutput => "Sortie : {}, post-traitement : {}",
+        Msg::On => "activé",
+        Msg::Off => "désactivé",
+        Msg::CopiedToClipboard => "Copié dans le presse-papiers : {}",
+        Msg::SubmitConfirm => "Appuyez sur la touche dans les {}s pour valider avec Entrée",
+        Msg::SubmitDropped => "(non envoyé)",
+        Msg::RatingSaved => "Note enregistrée dans {}",
+        Msg::NothingToRate => "(rien à noter)",
+        Msg::FeedbackDisabled => "(notes désactivées, lancez avec --feedback)",
+        Msg::ComposeBuffer => "Brouillon : {}",
+        Msg::ComposeEmpty => "(le brouillon est vide)",
+        Msg::ComposeDiscarded => "(brouillon effacé)",
+        Msg::ComposeDisabled => "(mode brouillon désactivé, lancez avec --compose)",
+        Msg::DeviceSwitched => "Microphone : {}",
+        Msg::NothingToRepeat => "(rien à répéter)",
+        Msg::TitleRecording => "● REC",
+        Msg::TitleTranscribing => "… TRANSCRIPTION",
+        Msg::TitlePostProcessing => "… POST-TRAITEMENT",
+        Msg::TitleLoadingModel => "… CHARGEMENT DU MODÈLE",
+        Msg::NotifyDone => "Transcrit",
+        Msg::NotifyFailed => "Échec de la transcription",
+        Msg::NoMicrophone => "Aucun périphérique d'entrée audio disponible",
+        Msg::InputDeviceNotFound => "Périphérique d'entrée introuvable : {} (voir `list-devices`, ou /proc/bus/input/devices pour --switch)",
+        Msg::NoPermission => "Impossible d'accéder au clavier : {}",
+        Msg::ModelMissing => "Modèle introuvable dans {}",
+        Msg::OutputToolMissing => "Outil de sortie `{}` introuvable (installez {})",
+        Msg::FeatureMissing => "{} n'est pas compilé (recompilez avec `--features {}`)",
+        Msg::HotkeyConflict => "{} et {} utilisent la même touche {}",
+    }
+}
@@ -0,0 +1,720 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// Emitted when a registered hotkey is pressed or released. The index is the
+/// hotkey's position in the slice passed to [`listen`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyEvent {
+    Pressed(usize),
+    Released(usize),
+}
+
+/// A key (or mouse button) that can trigger a hotkey.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// A-Z, stored uppercase
+    Letter(char),
+    /// 0-9 on the main row
+    Digit(u8),
+    /// F1-F24
+    F(u8),
+    Space,
+    Tab,
+    Escape,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    ScrollLock,
+    Pause,
+    /// Side ("back") mouse button, BTN_SIDE (Linux)
+    MouseSide,
+    /// Extra ("forward") mouse button, BTN_EXTRA (Linux)
+    MouseExtra,
+}
+
+impl Key {
+    /// Parse a key name like "F9", "Space", "A" or "MouseSide", ignoring case and underscores.
+    pub fn parse(s: &str) -> Result<Self> {
+        let name = s.to_uppercase().replace('_', "");
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c.is_ascii_uppercase() {
+                return Ok(Key::Letter(c));
+            }
+            if let Some(digit) = c.to_digit(10) {
+                return Ok(Key::Digit(digit as u8));
+            }
+        }
+        if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+            if (1..=24).contains(&n) {
+                return Ok(Key::F(n));
+            }
+        }
+        match name.as_str() {
+            "SPACE" => Ok(Key::Space),
+            "TAB" => Ok(Key::Tab),
+            "ESC" | "ESCAPE" => Ok(Key::Escape),
+            "INSERT" | "INS" => Ok(Key::Insert),
+            "DELETE" | "DEL" => Ok(Key::Delete),
+            "HOME" => Ok(Key::Home),
+            "END" => Ok(Key::End),
+            "PAGEUP" | "PGUP" | "PRIOR" => Ok(Key::PageUp),
+            "PAGEDOWN" | "PGDN" | "NEXT" => Ok(Key::PageDown),
+            "SCROLLLOCK" => Ok(Key::ScrollLock),
+            "PAUSE" => Ok(Key::Pause),
+            "MOUSESIDE" | "BTNSIDE" | "MOUSE4" => Ok(Key::MouseSide),
+            "MOUSEEXTRA" | "BTNEXTRA" | "MOUSE5" => Ok(Key::MouseExtra),
+            _ => Err(anyhow!("Unknown key: {}", s)),
+        }
+    }
+
+    fn is_mouse_button(self) -> bool {
+        matches!(self, Key::MouseSide | Key::MouseExtra)
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Letter(c) => write!(f, "{}", c),
+            Key::Digit(d) => write!(f, "{}", d),
+            Key::F(n) => write!(f, "F{}", n),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Modifier keys that must be held together with a hotkey's key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// The Super/Windows key, or Command on macOS
+    pub meta: bool,
+}
+
+/// A key or mouse button plus the modifiers held with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl Hotkey {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            modifiers: Modifiers::default(),
+        }
+    }
+}
+
+impl std::fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let modifiers = [
+            (self.modifiers.ctrl, "Ctrl+"),
+            (self.modifiers.alt, "Alt+"),
+            (self.modifiers.shift, "Shift+"),
+            (self.modifiers.meta, "Super+"),
+        ];
+        for (held, name) in modifiers {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Parse a hotkey like "F9", "Ctrl+Alt+Space" or "Super+MouseSide".
+pub fn parse_hotkey(s: &str) -> Result<Hotkey> {
+    let parts: Vec<&str> = s.split('+').map(str::trim).collect();
+    let (key, modifier_names) = parts.split_last().expect("split yields at least one part");
+    if key.is_empty() {
+        bail!("Missing key in hotkey: {:?}", s);
+    }
+
+    let mut modifiers = Modifiers::default();
+    for name in modifier_names {
+        let flag = match name.to_uppercase().as_str() {
+            "SHIFT" => &mut modifiers.shift,
+            "CTRL" | "CONTROL" => &mut modifiers.ctrl,
+            "ALT" | "OPTION" => &mut modifiers.alt,
+            "SUPER" | "META" | "WIN" | "LOGO" | "CMD" | "COMMAND" => &mut modifiers.meta,
+            _ => bail!("Unknown modifier: {}", name),
+        };
+        *flag = true;
+    }
+
+    let key = Key::parse(key)?;
+    if key.is_mouse_button() && !cfg!(target_os = "linux") {
+        bail!("Mouse buttons can only be used as hotkeys on Linux");
+    }
+    Ok(Hotkey { key, modifiers })
+}
+
+/// Tracks modifier state and which hotkeys are held, for keys of the platform type `K`.
+struct Matcher<K> {
+    hotkeys: Vec<(K, Modifiers)>,
+    modifiers: Modifiers,
+    held: Vec<bool>,
+}
+
+impl<K: PartialEq> Matcher<K> {
+    fn new(hotkeys: Vec<(K, Modifiers)>) -> Self {
+        let held = vec![false; hotkeys.len()];
+        Self {
+            hotkeys,
+            modifiers: Modifiers::default(),
+            held,
+        }
+    }
+
+    /// A hotkey fires only if exactly its modifiers are held, so F9 and Shift+F9
+    /// can be bound to different things.
+    fn press(&mut self, key: &K) -> Vec<HotkeyEvent> {
+        let mut events = Vec::new();
+        for (idx, (hotkey, modifiers)) in self.hotkeys.iter().enumerate() {
+            if hotkey == key && *modifiers == self.modifiers && !self.held[idx] {
+                self.held[idx] = true;
+                events.push(HotkeyEvent::Pressed(idx));
+            }
+        }
+        events
+    }
+
+    /// Releases don't check modifiers: they are often let go before the key itself.
+    fn release(&mut self, key: &K) -> Vec<HotkeyEvent> {
+        let mut events = Vec::new();
+        for (idx, (hotkey, _)) in self.hotkeys.iter().enumerate() {
+            if hotkey == key && self.held[idx] {
+                self.held[idx] = false;
+                events.push(HotkeyEvent::Released(idx));
+            }
+        }
+        events
+    }
+}
+
+/// Listen for `hotkeys` in a background thread. The thread stops once the
+/// receiver is dropped. Keys on the `switch` device, if given, act as the
+/// first hotkey.
+pub fn listen(hotkeys: &[Hotkey], switch: Option<&str>) -> Result<Receiver<HotkeyEvent>> {
+    let (tx, rx) = mpsc::channel();
+    if let Some(selector) = switch {
+        crate::switch::spawn(selector, tx.clone())?;
+    }
+    spawn_listener(hotkeys, tx)?;
+    Ok(rx)
+}
+
+/// Debounce `events` in a background thread, see [`Debouncer`].
+pub fn debounce(events: Receiver<HotkeyEvent>, window: Duration) -> Receiver<HotkeyEvent> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut debouncer = Debouncer::new(window);
+        loop {
+            let received = match debouncer.deadline() {
+                Some(deadline) => {
+                    events.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let now = Instant::now();
+            let mut settled = debouncer.settle(now);
+            match received {
+                Ok(event) => settled.extend(debouncer.event(event, now)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            for event in settled {
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Passes on a hotkey's press or release only once `window` has passed since
+/// its last one, for switches that bounce and hands that tremble. Whatever
+/// the key ends up as within the window takes effect when the window ends,
+/// so a bounce is dropped but a short tap still comes through.
+struct Debouncer {
+    window: Duration,
+    keys: BTreeMap<usize, DebouncedKey>,
+}
+
+#[derive(Default)]
+struct DebouncedKey {
+    held: bool,
+    changed: Option<Instant>,
+    /// The latest state reported within the window
+    pending: Option<bool>,
+}
+
+impl Debouncer {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            keys: BTreeMap::new(),
+        }
+    }
+
+    fn event(&mut self, event: HotkeyEvent, now: Instant) -> Option<HotkeyEvent> {
+        let (idx, pressed) = match event {
+            HotkeyEvent::Pressed(idx) => (idx, true),
+            HotkeyEvent::Released(idx) => (idx, false),
+        };
+        let window = self.window;
+        let key = self.keys.entry(idx).or_default();
+        if key
+            .changed
+            .is_some_and(|changed| now.saturating_duration_since(changed) < window)
+        {
+            key.pending = Some(pressed);
+            return None;
+        }
+        key.pending = None;
+        (key.held != pressed).then(|| {
+            key.held = pressed;
+            key.changed = Some(now);
+            event
+        })
+    }
+
+    /// Changes that are due because their key's window has passed.
+    fn settle(&mut self, now: Instant) -> Vec<HotkeyEvent> {
+        let mut events = Vec::new();
+        for (&idx, key) in &mut self.keys {
+            let due = key
+                .changed
+                .is_some_and(|changed| now.saturating_duration_since(changed) >= self.window);
+            let Some(pressed) = key.pending.filter(|_| due) else {
+                continue;
+            };
+            key.pending = None;
+            if pressed != key.held {
+                key.held = pressed;
+                key.changed = Some(now);
+                events.push(if pressed {
+                    HotkeyEvent::Pressed(idx)
+                } else {
+                    HotkeyEvent::Released(idx)
+                });
+            }
+        }
+        events
+    }
+
+    /// When the next pending change is due.
+    fn deadline(&self) -> Option<Instant> {
+        self.keys
+            .values()
+            .filter(|key| key.pending.is_some())
+            .filter_map(|key| key.changed)
+            .min()
+            .map(|changed| changed + self.window)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn to_evdev_key(key: Key) -> evdev::Key {
+    use evdev::Key as K;
+
+    match key {
+        Key::Letter(c) => {
+            // Scan codes follow the QWERTY rows, not the alphabet
+            let rows = [("QWERTYUIOP", 16), ("ASDFGHJKL", 30), ("ZXCVBNM", 44)];
+            rows.iter()
+                .find_map(|(row, first)| row.find(c).map(|i| K::new(first + i as u16)))
+                .expect("letters are A-Z")
+        }
+        Key::Digit(0) => K::KEY_0,
+        Key::Digit(d) => K::new(K::KEY_1.code() + d as u16 - 1),
+        Key::F(n @ 1..=10) => K::new(K::KEY_F1.code() + n as u16 - 1),
+        Key::F(11) => K::KEY_F11,
+        Key::F(12) => K::KEY_F12,
+        Key::F(n) => K::new(K::KEY_F13.code() + n as u16 - 13),
+        Key::Space => K::KEY_SPACE,
+        Key::Tab => K::KEY_TAB,
+        Key::Escape => K::KEY_ESC,
+        Key::Insert => K::KEY_INSERT,
+        Key::Delete => K::KEY_DELETE,
+        Key::Home => K::KEY_HOME,
+        Key::End => K::KEY_END,
+        Key::PageUp => K::KEY_PAGEUP,
+        Key::PageDown => K::KEY_PAGEDOWN,
+        Key::ScrollLock => K::KEY_SCROLLLOCK,
+        Key::Pause => K::KEY_PAUSE,
+        Key::MouseSide => K::BTN_SIDE,
+        Key::MouseExtra => K::BTN_EXTRA,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn evdev_modifier(modifiers: &mut Modifiers, key: evdev::Key) -> Option<&mut bool> {
+    use evdev::Key as K;
+
+    match key {
+        K::KEY_LEFTSHIFT | K::KEY_RIGHTSHIFT => Some(&mut modifiers.shift),
+        K::KEY_LEFTCTRL | K::KEY_RIGHTCTRL => Some(&mut modifiers.ctrl),
+        K::KEY_LEFTALT | K::KEY_RIGHTALT => Some(&mut modifiers.alt),
+        K::KEY_LEFTMETA | K::KEY_RIGHTMETA => Some(&mut modifiers.meta),
+        _ => None,
+    }
+}
+
+/// Keyboards (for modifiers) plus any other device that has one of the hotkey
+/// keys, such as a mouse with side buttons.
+#[cfg(target_os = "linux")]
+fn find_devices(keys: &[evdev::Key]) -> Result<Vec<evdev::Device>> {
+    let mut devices = Vec::new();
+    for (path, device) in evdev::enumerate() {
+        let Some(supported) = device.supported_keys() else {
+            continue;
+        };
+        // Our own output device (`--output-backend uinput`)
+        if device.name() == Some(crate::uinput::DEVICE_NAME) {
+            continue;
+        }
+        if supported.contains(evdev::Key::KEY_A) || keys.iter().any(|&k| supported.contains(k)) {
+            log::debug!("Listening on {:?} at {:?}", device.name(), path);
+            devices.push(device);
+        }
+    }
+
+    if devices.is_empty() {
+        // Devices that can't be opened aren't enumerated, so this is usually permissions
+        return Err(crate::error::AppError::NoPermission(
+            "no keyboards found. Make sure you're in the 'input' group or running as root.".into(),
+        )
+        .into());
+    }
+    Ok(devices)
+}
+
+#[cfg(target_os = "linux")]
+fn set_nonblocking(devices: &[evdev::Device]) -> Result<()> {
+    use anyhow::Context;
+    use nix::fcntl::{fcntl, FcntlArg, OFlag};
+    use std::os::fd::AsRawFd;
+
+    for device in devices {
+        let fd = device.as_raw_fd();
+        let flags = fcntl(fd, FcntlArg::F_GETFL).context("Failed to get fd flags")?;
+        let flags = OFlag::from_bits_truncate(flags) | OFlag::O_NONBLOCK;
+        fcntl(fd, FcntlArg::F_SETFL(flags)).context("Failed to set non-blocking")?;
+    }
+    Ok(())
+}
+
+/// Poll all input devices from one thread so modifiers held on a keyboard
+/// combine with buttons on a mouse. Devices are rescanned after read errors,
+/// e.g. when a USB keyboard is unplugged and plugged back in.
+#[cfg(target_os = "linux")]
+fn spawn_listener(hotkeys: &[Hotkey], tx: Sender<HotkeyEvent>) -> Result<()> {
+    const RESCAN_INTERVAL: Duration = Duration::from_secs(10);
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    let keys: Vec<evdev::Key> = hotkeys.iter().map(|h| to_evdev_key(h.key)).collect();
+    let mut matcher = Matcher::new(
+        hotkeys
+            .iter()
+            .map(|h| (to_evdev_key(h.key), h.modifiers))
+            .collect(),
+    );
+    let mut devices = find_devices(&keys)?;
+    set_nonblocking(&devices)?;
+
+    std::thread::spawn(move || {
+        let mut had_error = false;
+        let mut last_rescan = Instant::now();
+
+        loop {
+            if had_error && last_rescan.elapsed() >= RESCAN_INTERVAL {
+                log::info!("Input device error, rescanning devices...");
+                match find_devices(&keys) {
+                    Ok(found) if set_nonblocking(&found).is_ok() => {
+                        log::info!("Input devices reconnected: {} device(s)", found.len());
+                        devices = found;
+                        matcher.modifiers = Modifiers::default();
+                        had_error = false;
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Failed to rescan input devices: {}", e),
+                }
+                last_rescan = Instant::now();
+            }
+
+            for device in devices.iter_mut() {
+                let events = match device.fetch_events() {
+                    Ok(events) => events,
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                    Err(e) => {
+                        log::debug!("Input device read error: {}", e);
+                        had_error = true;
+                        continue;
+                    }
+                };
+                for event in events {
+                    let evdev::InputEventKind::Key(key) = event.kind() else {
+                        continue;
+                    };
+                    // 0 is release, 1 press and 2 autorepeat
+                    let pressed = match event.value() {
+                        0 => false,
+                        1 => true,
+                        _ => continue,
+                    };
+                    if let Some(flag) = evdev_modifier(&mut matcher.modifiers, key) {
+                        *flag = pressed;
+                    }
+                    let hotkey_events = if pressed {
+                        matcher.press(&key)
+                    } else {
+                        matcher.release(&key)
+                    };
+                    for hotkey_event in hotkey_events {
+                        if tx.send(hotkey_event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn to_rdev_key(key: Key) -> Option<rdev::Key> {
+    use rdev::Key as K;
+
+    let key = match key {
+        Key::Letter(c) => {
+            const LETTERS: [K; 26] = [
+                K::KeyA,
+                K::KeyB,
+                K::KeyC,
+                K::KeyD,
+                K::KeyE,
+                K::KeyF,
+                K::KeyG,
+                K::KeyH,
+                K::KeyI,
+                K::KeyJ,
+                K::KeyK,
+                K::KeyL,
+                K::KeyM,
+                K::KeyN,
+                K::KeyO,
+                K::KeyP,
+                K::KeyQ,
+                K::KeyR,
+                K::KeyS,
+                K::KeyT,
+                K::KeyU,
+                K::KeyV,
+                K::KeyW,
+                K::KeyX,
+                K::KeyY,
+                K::KeyZ,
+            ];
+            LETTERS[(c as u8 - b'A') as usize]
+        }
+        Key::Digit(d) => [
+            K::Num0,
+            K::Num1,
+            K::Num2,
+            K::Num3,
+            K::Num4,
+            K::Num5,
+            K::Num6,
+            K::Num7,
+            K::Num8,
+            K::Num9,
+        ][d as usize],
+        Key::F(n @ 1..=12) => [
+            K::F1,
+            K::F2,
+            K::F3,
+            K::F4,
+            K::F5,
+            K::F6,
+            K::F7,
+            K::F8,
+            K::F9,
+            K::F10,
+            K::F11,
+            K::F12,
+        ][n as usize - 1],
+        Key::Space => K::Space,
+        Key::Tab => K::Tab,
+        Key::Escape => K::Escape,
+        Key::Insert => K::Insert,
+        Key::Delete => K::Delete,
+        Key::Home => K::Home,
+        Key::End => K::End,
+        Key::PageUp => K::PageUp,
+        Key::PageDown => K::PageDown,
+        Key::ScrollLock => K::ScrollLock,
+        Key::Pause => K::Pause,
+        Key::F(_) | Key::MouseSide | Key::MouseExtra => return None,
+    };
+    Some(key)
+}
+
+#[cfg(target_os = "macos")]
+fn rdev_modifier(modifiers: &mut Modifiers, key: rdev::Key) -> Option<&mut bool> {
+    use rdev::Key as K;
+
+    match key {
+        K::ShiftLeft | K::ShiftRight => Some(&mut modifiers.shift),
+        K::ControlLeft | K::ControlRight => Some(&mut modifiers.ctrl),
+        K::Alt | K::AltGr => Some(&mut modifiers.alt),
+        K::MetaLeft | K::MetaRight => Some(&mut modifiers.meta),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn spawn_listener(hotkeys: &[Hotkey], tx: Sender<HotkeyEvent>) -> Result<()> {
+    use rdev::{Event, EventType};
+
+    let mut rdev_hotkeys = Vec::new();
+    for hotkey in hotkeys {
+        let key = to_rdev_key(hotkey.key)
+            .ok_or_else(|| anyhow!("{} can't be used as a hotkey on macOS", hotkey.key))?;
+        rdev_hotkeys.push((key, hotkey.modifiers));
+    }
+    let mut matcher = Matcher::new(rdev_hotkeys);
+
+    std::thread::spawn(move || {
+        let callback = move |event: Event| {
+            let (key, pressed) = match event.event_type {
+                EventType::KeyPress(key) => (key, true),
+                EventType::KeyRelease(key) => (key, false),
+                _ => return,
+            };
+            if let Some(flag) = rdev_modifier(&mut matcher.modifiers, key) {
+                *flag = pressed;
+            }
+            let events = if pressed {
+                matcher.press(&key)
+            } else {
+                matcher.release(&key)
+            };
+            for event in events {
+                let _ = tx.send(event);
+            }
+        };
+
+        if let Err(e) = rdev::listen(callback) {
+            log::error!("Error listening to keyboard events: {:?}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn spawn_listener(_hotkeys: &[Hotkey], _tx: Sender<HotkeyEvent>) -> Result<()> {
+    bail!("Hotkey listening is not supported on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_combo() {
+        let hotkey = parse_hotkey("Ctrl+Alt+Space").unwrap();
+        assert_eq!(hotkey.key, Key::Space);
+        assert!(hotkey.modifiers.ctrl && hotkey.modifiers.alt);
+        assert!(!hotkey.modifiers.shift && !hotkey.modifiers.meta);
+        assert_eq!(hotkey.to_string(), "Ctrl+Alt+Space");
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_hotkey("f9").unwrap(), Hotkey::new(Key::F(9)));
+        assert_eq!(parse_hotkey("F24").unwrap().key, Key::F(24));
+        assert_eq!(parse_hotkey("Scroll_Lock").unwrap().key, Key::ScrollLock);
+        assert_eq!(parse_hotkey("Super+d").unwrap().key, Key::Letter('D'));
+        assert!(parse_hotkey("Meta+F9").unwrap().modifiers.meta);
+        assert!(parse_hotkey("F25").is_err());
+        assert!(parse_hotkey("Hyper+F9").is_err());
+        assert!(parse_hotkey("Ctrl+").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_mouse_buttons() {
+        assert_eq!(parse_hotkey("BTN_SIDE").unwrap().key, Key::MouseSide);
+        assert_eq!(
+            parse_hotkey("Shift+MouseExtra").unwrap().key,
+            Key::MouseExtra
+        );
+    }
+
+    #[test]
+    fn test_modifiers_must_match_exactly() {
+        let shift = Modifiers {
+            shift: true,
+            ..Modifiers::default()
+        };
+        let mut matcher = Matcher::new(vec![("F9", Modifiers::default()), ("F9", shift)]);
+
+        matcher.modifiers.shift = true;
+        assert_eq!(matcher.press(&"F9"), vec![HotkeyEvent::Pressed(1)]);
+    }
+
+    #[test]
+    fn test_debounce() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut debouncer = Debouncer::new(Duration::from_millis(100));
+        let pressed = HotkeyEvent::Pressed(0);
+        let released = HotkeyEvent::Released(0);
+
+        // A bouncing press counts once
+        assert_eq!(debouncer.event(pressed, at(0)), Some(pressed));
+        assert_eq!(debouncer.event(released, at(5)), None);
+        assert_eq!(debouncer.event(pressed, at(8)), None);
+        assert_eq!(debouncer.deadline(), Some(at(100)));
+        assert!(debouncer.settle(at(100)).is_empty());
+        assert_eq!(debouncer.event(released, at(2000)), Some(released));
+
+        // A tap shorter than the window is released once the window ends
+        assert_eq!(debouncer.event(pressed, at(3000)), Some(pressed));
+        assert_eq!(debouncer.event(released, at(3050)), None);
+        assert!(debouncer.settle(at(3090)).is_empty());
+        assert_eq!(debouncer.settle(at(3100)), vec![released]);
+        assert_eq!(debouncer.deadline(), None);
+    }
+
+    #[test]
+    fn test_release_after_modifier_let_go() {
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::default()
+        };
+        let mut matcher = Matcher::new(vec![("Space", ctrl)]);
+
+        assert!(matcher.press(&"Space").is_empty());
+        matcher.modifiers.ctrl = true;
+        assert_eq!(matcher.press(&"Space"), vec![HotkeyEvent::Pressed(0)]);
+        matcher.modifiers.ctrl = false;
+        assert_eq!(matcher.release(&"Space"), vec![HotkeyEvent::Released(0)]);
+        assert!(matcher.release(&"Space").is_empty());
+    }
+}
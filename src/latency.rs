@@ -0,0 +1,263 @@
+use crate::audio;
+use crate::report;
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, Sample, SizedSample, SupportedStreamConfig};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+const TONE_HZ: f32 = 1000.0;
+const TONE_AMPLITUDE: f32 = 0.5;
+const TONE_DURATION: Duration = Duration::from_millis(300);
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+const LISTEN_TIME: Duration = Duration::from_secs(1);
+const DETECT_WINDOW: Duration = Duration::from_millis(5);
+
+/// Shared state between the input callback, the tone generator and the analysis.
+#[derive(Default)]
+struct Probe {
+    /// Mono input samples since the input stream started
+    samples: Mutex<Vec<f32>>,
+    first_callback: OnceLock<Instant>,
+    callback_frames: OnceLock<usize>,
+    /// Input sample index at the moment the tone started playing
+    tone_start: OnceLock<usize>,
+}
+
+#[derive(Serialize)]
+struct LatencyReport {
+    input: DeviceInfo,
+    output: DeviceInfo,
+    stream_open_ms: f64,
+    first_audio_ms: f64,
+    callback_frames: Option<usize>,
+    callback_ms: Option<f64>,
+    /// `None` when the tone wasn't picked up by the microphone
+    round_trip_ms: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct DeviceInfo {
+    name: String,
+    sample_rate: u32,
+    channels: u16,
+    sample_format: String,
+    buffer_size: String,
+}
+
+impl DeviceInfo {
+    fn new(device: &cpal::Device, config: &SupportedStreamConfig) -> Result<Self> {
+        Ok(Self {
+            name: device.name()?,
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+            sample_format: format!("{:?}", config.sample_format()),
+            buffer_size: format!("{:?}", config.buffer_size()),
+        })
+    }
+}
+
+/// Play a tone through the default output, record it through the selected input
+/// (the default unless `--audio-device` is given) and report how long audio
+/// takes to start flowing and to make the round trip.
+pub fn run(input_device: Option<&str>) -> Result<()> {
+    let host = cpal::default_host();
+    let input = audio::input_device(input_device)?;
+    let output = host
+        .default_output_device()
+        .context("No output device available")?;
+    let input_config = input
+        .default_input_config()
+        .context("No default input config")?;
+    let output_config = output
+        .default_output_config()
+        .context("No default output config")?;
+
+    let input_info = DeviceInfo::new(&input, &input_config)?;
+    let output_info = DeviceInfo::new(&output, &output_config)?;
+    if !report::json() {
+        print_device("Input", &input_info);
+        print_device("Output", &output_info);
+    }
+
+    let probe = Arc::new(Probe::default());
+    let input_rate = input_config.sample_rate().0;
+
+    let open_start = Instant::now();
+    let input_stream = build_input_stream(&input, &input_config, Arc::clone(&probe))?;
+    input_stream.play()?;
+    let opened = open_start.elapsed();
+
+    std::thread::sleep(SETTLE_TIME);
+
+    let output_stream = build_output_stream(&output, &output_config, Arc::clone(&probe))?;
+    output_stream.play()?;
+    std::thread::sleep(LISTEN_TIME);
+    drop(output_stream);
+    drop(input_stream);
+
+    let first_audio = probe
+        .first_callback
+        .get()
+        .map(|first| first.duration_since(open_start))
+        .context("No audio received from the input device")?;
+    let callback_frames = probe.callback_frames.get().copied();
+
+    let samples = probe.samples.lock().unwrap();
+    let tone_start = *probe
+        .tone_start
+        .get()
+        .context("Tone was never played by the output device")?;
+    let round_trip_ms = detect_onset(&samples, tone_start, input_rate)
+        .map(|onset| (onset - tone_start) as f64 * 1000.0 / input_rate as f64);
+
+    let report = LatencyReport {
+        input: input_info,
+        output: output_info,
+        stream_open_ms: opened.as_secs_f64() * 1000.0,
+        first_audio_ms: first_audio.as_secs_f64() * 1000.0,
+        callback_frames,
+        callback_ms: callback_frames.map(|frames| frames as f64 * 1000.0 / input_rate as f64),
+        round_trip_ms,
+    };
+    if report::json() {
+        return report::emit(&report);
+    }
+
+    println!();
+    println!("Input stream opened in {:.1?}", opened);
+    println!(
+        "First audio arrived {:.1?} after opening the stream",
+        first_audio
+    );
+    if let (Some(frames), Some(ms)) = (report.callback_frames, report.callback_ms) {
+        println!("Input callback buffer: {} frames ({:.1} ms)", frames, ms);
+    }
+    match report.round_trip_ms {
+        Some(ms) => println!("Round-trip latency (output + input): {:.1} ms", ms),
+        None => println!("Tone not detected, make sure the speakers are audible to the microphone"),
+    }
+
+    println!();
+    println!(
+        "The input device is reopened on every key press, so up to {:.0?} of speech can be clipped.",
+        first_audio
+    );
+    Ok(())
+}
+
+fn print_device(label: &str, info: &DeviceInfo) {
+    println!(
+        "{} device: {} ({} Hz, {} ch, {}, buffer size {})",
+        label, info.name, info.sample_rate, info.channels, info.sample_format, info.buffer_size
+    );
+}
+
+fn build_input_stream(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    probe: Arc<Probe>,
+) -> Result<cpal::Stream> {
+    match config.sample_format() {
+        cpal::SampleFormat::F32 => input_stream::<f32>(device, config, probe),
+        cpal::SampleFormat::I16 => input_stream::<i16>(device, config, probe),
+        cpal::SampleFormat::I32 => input_stream::<i32>(device, config, probe),
+        format => anyhow::bail!("Unsupported sample format: {:?}", format),
+    }
+}
+
+fn input_stream<T>(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    probe: Arc<Probe>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels() as usize;
+    let stream = device.build_input_stream(
+        &config.config(),
+        move |data: &[T], _| {
+            probe.first_callback.get_or_init(Instant::now);
+            probe.callback_frames.get_or_init(|| data.len() / channels);
+            let mut samples = probe.samples.lock().unwrap();
+            for frame in data.chunks(channels) {
+                let sum: f32 = frame.iter().map(|&s| f32::from_sample(s)).sum();
+                samples.push(sum / channels as f32);
+            }
+        },
+        |err| log::error!("Audio input error: {}", err),
+        None,
+    )?;
+    Ok(stream)
+}
+
+fn build_output_stream(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    probe: Arc<Probe>,
+) -> Result<cpal::Stream> {
+    match config.sample_format() {
+        cpal::SampleFormat::F32 => output_stream::<f32>(device, config, probe),
+        cpal::SampleFormat::I16 => output_stream::<i16>(device, config, probe),
+        cpal::SampleFormat::I32 => output_stream::<i32>(device, config, probe),
+        format => anyhow::bail!("Unsupported sample format: {:?}", format),
+    }
+}
+
+fn output_stream<T>(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    probe: Arc<Probe>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels() as usize;
+    let rate = config.sample_rate().0 as f32;
+    let tone_frames = (TONE_DURATION.as_secs_f32() * rate) as usize;
+    let mut frame = 0usize;
+    let stream = device.build_output_stream(
+        &config.config(),
+        move |data: &mut [T], _| {
+            probe
+                .tone_start
+                .get_or_init(|| probe.samples.lock().unwrap().len());
+            for out in data.chunks_mut(channels) {
+                let value = if frame < tone_frames {
+                    (frame as f32 * TONE_HZ * 2.0 * std::f32::consts::PI / rate).sin()
+                        * TONE_AMPLITUDE
+                } else {
+                    0.0
+                };
+                frame += 1;
+                for sample in out.iter_mut() {
+                    *sample = T::from_sample(value);
+                }
+            }
+        },
+        |err| log::error!("Audio output error: {}", err),
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// Index of the first window after `from` that is clearly louder than the
+/// noise floor measured before it.
+fn detect_onset(samples: &[f32], from: usize, rate: u32) -> Option<usize> {
+    let window = (DETECT_WINDOW.as_secs_f32() * rate as f32) as usize;
+    let noise = rms(&samples[..from.min(samples.len())]);
+    let threshold = (noise * 4.0).max(0.01);
+    (from..samples.len().saturating_sub(window))
+        .step_by(window)
+        .find(|&i| rms(&samples[i..i + window]) > threshold)
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
@@ -1,25 +1,84 @@
 mod audio;
+mod calibrate;
+mod compose;
+mod console;
+mod control;
+mod correction;
+mod cues;
+mod daemon;
+mod debug_bundle;
+mod devices;
+mod dictionary;
+mod engine;
+mod error;
 mod event_loop;
+mod feedback;
+mod headset;
+mod history;
+mod hotkey_check;
+mod hotkey_profiles;
+mod i18n;
+mod input;
+mod latency;
 mod model;
 mod output;
 mod post_process;
+mod profiles;
+mod recover;
+mod reminder;
+mod report;
+mod retry;
+mod status_file;
+mod switch;
+mod title;
+mod todo;
+mod transcribe;
+#[cfg(target_os = "linux")]
+mod uinput;
+mod vad;
+mod worker;
 
-use anyhow::Result;
-use clap::Parser;
-use hotkey_listener::{parse_hotkey, HotkeyListenerBuilder};
-use output::OutputMode;
+use anyhow::{Context, Result};
+use audio::AudioRecorder;
+use clap::{Parser, Subcommand};
+use dictionary::Dictionary;
+use engine::{EngineChoice, ParakeetVariant, WhisperSize};
+use event_loop::Settings;
+use i18n::{tr, trf, Msg};
+use input::{parse_hotkey, Hotkey};
+use output::{
+    AutoSubmit, ClipboardHistory, ClipboardOptions, NewlineMode, OutputBackend, OutputMode,
+    OutputOptions,
+};
 use post_process::PostProcessor;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(name = "parakeet-writer")]
 #[command(about = "Push-to-talk transcriber using Parakeet v3")]
 struct Args {
-    /// Path to the parakeet model directory (auto-downloads if not specified)
-    #[arg(short, long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Speech recognition engine; auto picks one that suits this machine
+    #[arg(short, long, value_enum, global = true, default_value_t = EngineChoice::Parakeet)]
+    engine: EngineChoice,
+
+    /// Model size for --engine whisper
+    #[arg(long, value_enum, global = true, default_value_t = WhisperSize::Small)]
+    model_size: WhisperSize,
+
+    /// Model precision for --engine parakeet
+    #[arg(long, value_enum, global = true, default_value_t = ParakeetVariant::Int8)]
+    model_variant: ParakeetVariant,
+
+    /// Path to the model directory for the engine (auto-downloads if not specified)
+    #[arg(short, long, global = true)]
     model: Option<PathBuf>,
 
-    /// Hotkey to trigger recording (e.g., F9, ScrollLock)
+    /// Hotkey to trigger recording (e.g., F9, Ctrl+Alt+Space, Super+F9, MouseSide)
     #[arg(short, long, default_value = "F9")]
     key: String,
 
@@ -27,65 +86,686 @@ struct Args {
     #[arg(short, long, value_enum, default_value_t = OutputMode::Both)]
     output: OutputMode,
 
+    /// Tools to type and copy with on Linux: auto (by session type), wayland, x11, or uinput
+    #[arg(long, value_enum, default_value_t = OutputBackend::Auto)]
+    output_backend: OutputBackend,
+
+    /// Pause between key events with --output-backend uinput
+    #[arg(long, value_name = "MS", default_value_t = 2)]
+    key_delay: u64,
+
+    /// How line breaks in transcripts are typed: enter, shift-enter, or paste
+    #[arg(long, value_enum, default_value_t = NewlineMode::Enter)]
+    newline: NewlineMode,
+
+    /// Treat "replace X with Y" as a correction of the previous transcript
+    #[arg(long)]
+    spoken_corrections: bool,
+
+    /// Collect transcripts in a buffer shown in the console; output it with `o`
+    #[arg(long)]
+    compose: bool,
+
+    /// Press Enter after typing a transcript: off, on, or confirm (tap the hotkey again)
+    #[arg(long, value_enum, default_value_t = AutoSubmit::Off)]
+    auto_submit: AutoSubmit,
+
+    /// Clear the clipboard after the transcript has been pasted once (Linux)
+    #[arg(long)]
+    clipboard_paste_once: bool,
+
+    /// Also add transcripts to this clipboard manager's history (Linux)
+    #[arg(long, value_enum, value_name = "MANAGER")]
+    clipboard_history: Option<ClipboardHistory>,
+
+    /// Also offer each transcript's timestamp, duration and raw text as JSON on the clipboard (CopyQ)
+    #[arg(
+        long,
+        requires = "clipboard_history",
+        conflicts_with = "clipboard_paste_once"
+    )]
+    clipboard_metadata: bool,
+
+    /// Input device to record from: a number or (part of a) name from `list-devices`
+    #[arg(long, value_name = "NAME|INDEX", global = true)]
+    audio_device: Option<String>,
+
+    /// Input devices that --cycle-key switches between, as for --audio-device
+    #[arg(long, value_name = "DEVICES", value_delimiter = ',')]
+    cycle_devices: Vec<String>,
+
+    /// Hotkey that switches to the next of --cycle-devices between recordings
+    #[arg(long, value_name = "KEY", requires = "cycle_devices")]
+    cycle_key: Option<String>,
+
+    /// Read audio from a named pipe (raw s16le mono PCM) instead of the microphone
+    #[arg(long, value_name = "PATH")]
+    input_fifo: Option<PathBuf>,
+
+    /// Sample rate of the PCM data written to --input-fifo
+    #[arg(
+        long,
+        value_name = "HZ",
+        default_value_t = 16000,
+        value_parser = clap::value_parser!(u32).range(1000..=384_000)
+    )]
+    input_fifo_rate: u32,
+
+    /// Also toggle recording with headset/media remote play-pause buttons (Linux)
+    #[arg(long)]
+    headset_button: bool,
+
+    /// Stop recordings toggled on with `r` or a headset button after this many seconds of silence
+    #[arg(long, value_name = "SECS")]
+    auto_stop: Option<f64>,
+
+    /// Cut leading and trailing silence from recordings before transcribing them
+    #[arg(long)]
+    trim_silence: bool,
+
+    /// Voice activity detector for --auto-stop and --trim-silence
+    #[arg(long, value_enum, default_value_t = vad::VadKind::Energy)]
+    vad: vad::VadKind,
+
+    /// Ignore key presses shorter than this many milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    min_hold: u64,
+
+    /// Stop any recording after this many seconds, e.g. when the hotkey is stuck (0 for no limit)
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    max_duration: u64,
+
+    /// Give up on a transcription or output that takes longer than this many seconds (0 to wait forever)
+    #[arg(long, value_name = "SECS", default_value_t = 180)]
+    watchdog: u64,
+
+    /// Press the hotkey once to start recording and again to stop, instead of holding it
+    #[arg(long)]
+    latch: bool,
+
+    /// Ignore hotkey presses and releases this many milliseconds after the previous one
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    debounce: u64,
+
+    /// Use a single-switch assistive device as the hotkey: its path or part of its name (Linux)
+    #[arg(long, value_name = "DEVICE")]
+    switch: Option<String>,
+
+    /// Stream audio to disk while recording so it can be recovered after a crash
+    #[arg(long)]
+    spill: bool,
+
+    /// Keep a WAV of the audio of every transcription in this directory, for debugging
+    #[arg(long, value_name = "DIR")]
+    save_audio: Option<PathBuf>,
+
+    /// Transcribe long recordings in chunks of this many seconds while the key is held
+    #[arg(long, value_name = "SECS")]
+    split_after: Option<u64>,
+
+    /// Transcribe long recordings in the background at pauses, so the text is ready soon after release
+    #[arg(long, conflicts_with = "split_after")]
+    stream: bool,
+
+    /// Unload the model after this many seconds without a recording, and load it again on the next one
+    #[arg(long, value_name = "SECS")]
+    unload_after: Option<u64>,
+
+    /// Run the engine in a child process that is restarted if it crashes
+    #[arg(long)]
+    isolate_engine: bool,
+
+    /// Hotkey for quick dictations that are output right away, skipping post-processing
+    #[arg(long, value_name = "KEY")]
+    urgent_key: Option<String>,
+
+    /// Rate transcriptions (console +/- or --feedback-keys) into a local log
+    #[arg(long)]
+    feedback: bool,
+
+    /// Keep a local history of transcriptions (see the `history` command)
+    #[arg(long)]
+    history: bool,
+
+    /// Hotkey that outputs the last transcription again
+    #[arg(long, value_name = "KEY")]
+    repeat_key: Option<String>,
+
+    /// Show recording and transcribing in the terminal (and tmux pane) title
+    #[arg(long)]
+    terminal_title: bool,
+
+    /// Keep the current state and last transcription time in this JSON file, e.g. for Waybar
+    #[arg(long, value_name = "PATH")]
+    status_file: Option<PathBuf>,
+
+    /// Beep when recording starts and stops, and when a transcription is done or failed
+    #[arg(long)]
+    beep: bool,
+
+    /// Show each transcript (or failure) as a desktop notification
+    #[arg(long)]
+    notify: bool,
+
+    /// Hotkeys that rate the last transcription good and bad (with --feedback)
+    #[arg(
+        long,
+        value_name = "GOOD,BAD",
+        value_delimiter = ',',
+        num_args = 2,
+        default_value = "F10,F11"
+    )]
+    feedback_keys: Vec<String>,
+
     /// Enable post-processing via Ollama to clean up transcripts
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     post_process: bool,
 
+    /// Names and jargon to bias recognition towards (Whisper only)
+    #[arg(long, global = true, value_name = "TEXT")]
+    initial_prompt: Option<String>,
+
+    /// Profiles file binding extra hotkeys to their own prompt and output mode
+    #[arg(long, value_name = "PATH")]
+    profiles: Option<PathBuf>,
+
+    /// Add dictations starting with "To-do:" to this todo.txt file, or to `taskwarrior`
+    #[arg(long, value_name = "TARGET")]
+    todo: Option<todo::TodoTarget>,
+
+    /// Add "Remind me ..." dictations to an .ics or .rem file, `khal` or `remind`
+    #[arg(long, value_name = "TARGET")]
+    reminders: Option<reminder::ReminderTarget>,
+
+    /// Rules file of replacements applied to transcripts before post-processing
+    #[arg(long, global = true, value_name = "PATH")]
+    dictionary: Option<PathBuf>,
+
+    /// File of `"spoken" = "Written"` pairs, applied before the dictionary
+    #[arg(long, global = true, value_name = "PATH")]
+    hints: Option<PathBuf>,
+
+    /// Have post-processing always write in this language, e.g. English, whatever was spoken
+    #[arg(
+        long,
+        global = true,
+        value_name = "LANGUAGE",
+        requires = "post_process"
+    )]
+    output_language: Option<String>,
+
     /// Ollama host
-    #[arg(long, default_value = "http://localhost")]
+    #[arg(long, global = true, default_value = "http://localhost")]
     ollama_host: String,
 
     /// Ollama port
-    #[arg(long, default_value_t = 11434)]
+    #[arg(long, global = true, default_value_t = 11434)]
     ollama_port: u16,
 
     /// Ollama model for post-processing
-    #[arg(long, default_value = "qwen3:1.7b")]
+    #[arg(long, global = true, default_value = "qwen3:1.7b")]
     ollama_model: String,
 
+    /// Retry policy for network calls, e.g. `attempts=5,backoff=2` or `ollama:attempts=1`
+    #[arg(long, global = true, value_name = "[BACKEND:]POLICY")]
+    retry: Vec<retry::RetryOverride>,
+
     /// Enable verbose (debug) logging
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     verbose: bool,
+
+    /// Print subcommand results as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Print per-file load times, memory usage and ONNX Runtime info when loading the model
+    #[arg(long, global = true)]
+    verbose_load: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the transcription model
+    Model {
+        #[command(subcommand)]
+        action: ModelCommand,
+    },
+    /// Play a tone and record it to measure audio capture latency
+    Latency,
+    /// List audio input devices for --audio-device
+    ListDevices,
+    /// Measure the microphone's level and noise floor, and apply them whenever it's used
+    Calibrate,
+    /// Print the transcript of audio files, or of remote audio (podcast episode, voicemail link, video)
+    Transcribe {
+        /// Local audio files (WAV, FLAC, MP3, M4A/AAC or Ogg Vorbis)
+        files: Vec<PathBuf>,
+        /// URL of an audio file (MP3, M4A/AAC, Ogg Vorbis, FLAC or WAV), or of a
+        /// video page if yt-dlp is installed
+        #[arg(long, required_unless_present = "files")]
+        url: Option<String>,
+        /// Also write subtitles with timestamps to this SRT file
+        #[arg(long, value_name = "PATH")]
+        srt: Option<PathBuf>,
+        /// Print the transcript as markdown, in chapters split at long pauses
+        #[arg(long)]
+        chapters: bool,
+        /// Write transcripts next to each file instead of printing them
+        #[arg(long, value_name = "FORMAT", value_delimiter = ',')]
+        write: Vec<transcribe::TranscriptFormat>,
+        /// Keep the downloaded file in the cache after transcribing it
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Run like the default mode, also accepting commands from `ctl` over a Unix socket
+    Daemon,
+    /// Control a running daemon
+    Ctl {
+        #[command(subcommand)]
+        request: daemon::Request,
+    },
+    /// Show, or copy to the clipboard, transcriptions saved with --history
+    History {
+        #[command(subcommand)]
+        action: history::HistoryCommand,
+    },
+    /// Transcribe recordings left behind by a crash (see --spill)
+    Recover {
+        /// Keep the recordings after transcribing them
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Collect system details, config and recent errors into a tarball for a bug report
+    DebugBundle {
+        /// Where to write the tarball [default: parakeet-writer-debug-<time>.tar.gz]
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Also include this config file, e.g. --profiles; --dictionary and --hints are included
+        #[arg(long, value_name = "PATH")]
+        include: Vec<PathBuf>,
+    },
+    /// The child process of --isolate-engine
+    #[command(hide = true)]
+    EngineWorker,
+}
+
+#[derive(Subcommand, Debug)]
+enum ModelCommand {
+    /// List the models of every engine, and which are downloaded
+    Status,
+    /// Download the model, e.g. while provisioning a machine
+    Download,
+    /// Delete the cached model
+    Remove,
+    /// Print where the model is (or would be downloaded to)
+    Path,
+    /// Check that the model files are present and intact
+    Verify {
+        /// Also verify file checksums and run a short test inference
+        #[arg(long)]
+        deep: bool,
+    },
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let args = Args::parse();
 
     let log_level = if args.verbose { "debug" } else { "info" };
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level)).init();
 
     log::debug!("Args: {:?}", args);
+    report::set_json(args.json);
+    retry::configure(&args.retry);
+    debug_bundle::record_panics();
+
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            debug_bundle::record_error(&format!("{:?}", e));
+            let code = error::exit_code(&e);
+            if report::json() {
+                report::emit_error(&e, code);
+            } else {
+                eprintln!("{}: {:?}", tr(Msg::Error), e);
+            }
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn output_options(args: &Args) -> OutputOptions {
+    OutputOptions {
+        mode: args.output,
+        backend: args.output_backend.resolve(),
+        clipboard: ClipboardOptions {
+            paste_once: args.clipboard_paste_once,
+            history: args.clipboard_history,
+            metadata: args.clipboard_metadata,
+        },
+        newline: args.newline,
+    }
+}
+
+fn load_dictionary(path: Option<&Path>) -> Result<Option<Dictionary>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let dictionary = Dictionary::load(path)?;
+    report::progress!(
+        "Dictionary: {} rules from {:?}",
+        dictionary.rule_count(),
+        path
+    );
+    Ok(Some(dictionary))
+}
+
+fn load_hints(path: Option<&Path>) -> Result<Option<Dictionary>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let hints = Dictionary::load_hints(path)?;
+    report::progress!("Hints: {} from {:?}", hints.rule_count(), path);
+    Ok(Some(hints))
+}
+
+async fn run(args: Args) -> Result<()> {
+    // Resolved up front for commands that transcribe
+    let engine = match args.command {
+        Some(Command::Latency) => return latency::run(args.audio_device.as_deref()),
+        Some(Command::ListDevices) => return devices::run(),
+        Some(Command::Calibrate) => return calibrate::run(args.audio_device.as_deref()),
+        Some(Command::Ctl { request }) => return daemon::send(request).await,
+        Some(Command::DebugBundle { output, include }) => {
+            let configs: Vec<_> = [args.dictionary, args.hints]
+                .into_iter()
+                .flatten()
+                .chain(include)
+                .collect();
+            return debug_bundle::run(output, &configs);
+        }
+        Some(Command::History { action }) => {
+            return history::run(action, &output_options(&args)).await
+        }
+        _ => args
+            .engine
+            .resolve(args.model_size, args.model_variant, args.model.as_deref())?,
+    };
+
+    match args.command {
+        Some(Command::Model { action }) => {
+            return match action {
+                ModelCommand::Status => model::status(engine, args.model),
+                ModelCommand::Download => model::download(engine, args.model).await,
+                ModelCommand::Remove => model::remove(engine, args.model),
+                ModelCommand::Path => {
+                    println!("{}", model::model_path(engine, args.model).display());
+                    Ok(())
+                }
+                ModelCommand::Verify { deep } => {
+                    model::verify(engine, args.model, deep, args.verbose_load).await
+                }
+            };
+        }
+        Some(Command::Recover { keep }) => {
+            return recover::run(engine, args.model, keep, args.verbose_load).await;
+        }
+        Some(Command::EngineWorker) => return worker::serve(engine, args.model),
+        Some(Command::Transcribe {
+            files,
+            url,
+            srt,
+            chapters,
+            write,
+            keep,
+        }) => {
+            if args.post_process && !post_process::AVAILABLE {
+                return Err(post_process::not_compiled_in().into());
+            }
+            let options = transcribe::TranscribeOptions {
+                srt,
+                chapters,
+                keep,
+                write,
+                initial_prompt: args.initial_prompt,
+                hints: load_hints(args.hints.as_deref())?,
+                dictionary: load_dictionary(args.dictionary.as_deref())?,
+                post_processor: args.post_process.then(|| {
+                    PostProcessor::new(&args.ollama_host, args.ollama_port, &args.ollama_model)
+                }),
+                output_language: args.output_language,
+            };
+            let verbose_load = args.verbose_load;
+            return transcribe::run(engine, args.model, files, url, options, verbose_load).await;
+        }
+        Some(Command::Latency)
+        | Some(Command::ListDevices)
+        | Some(Command::Calibrate)
+        | Some(Command::Ctl { .. })
+        | Some(Command::History { .. })
+        | Some(Command::DebugBundle { .. })
+        | Some(Command::Daemon)
+        | None => {}
+    }
 
     let hotkey = parse_hotkey(&args.key)?;
-    let model_path = model::ensure_model(args.model).await?;
-    let engine = model::load_engine(&model_path)?;
+    hotkey_check::warn_conflicts(&hotkey);
+    let feedback_hotkeys = if args.feedback {
+        args.feedback_keys
+            .iter()
+            .map(|key| parse_hotkey(key))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+    for hotkey in &feedback_hotkeys {
+        hotkey_check::warn_conflicts(hotkey);
+    }
+    let cycle_hotkey = args.cycle_key.as_deref().map(parse_hotkey).transpose()?;
+    let repeat_hotkey = args.repeat_key.as_deref().map(parse_hotkey).transpose()?;
+    let urgent_hotkey = args.urgent_key.as_deref().map(parse_hotkey).transpose()?;
+    let profiles = match &args.profiles {
+        Some(path) => hotkey_profiles::load(path)?,
+        None => Vec::new(),
+    };
+    let profile_hotkeys = profiles
+        .iter()
+        .map(|profile| {
+            parse_hotkey(&profile.key).with_context(|| format!("Profile {:?}", profile.name))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if args.todo.is_none() {
+        if let Some(profile) = profiles.iter().find(|profile| profile.todo) {
+            anyhow::bail!(
+                "Profile {:?} adds to-dos, but --todo isn't set",
+                profile.name
+            );
+        }
+    }
+    // A profile on the --key hotkey is that hotkey, so they don't clash
+    let main_hotkey = (!profile_hotkeys.contains(&hotkey)).then(|| ("--key".to_string(), hotkey));
+    let named = |option: &str, hotkeys: &[Hotkey]| {
+        hotkeys
+            .iter()
+            .map(|&hotkey| (option.to_string(), hotkey))
+            .collect::<Vec<_>>()
+    };
+    let registered: Vec<_> = main_hotkey
+        .into_iter()
+        .chain(named("--feedback-keys", &feedback_hotkeys))
+        .chain(named("--cycle-key", cycle_hotkey.as_slice()))
+        .chain(named("--repeat-key", repeat_hotkey.as_slice()))
+        .chain(named("--urgent-key", urgent_hotkey.as_slice()))
+        .chain(
+            profiles
+                .iter()
+                .zip(&profile_hotkeys)
+                .map(|(profile, &hotkey)| (format!("Profile {:?}", profile.name), hotkey)),
+        )
+        .collect();
+    hotkey_check::check_duplicates(&registered)?;
+    for hotkey in cycle_hotkey
+        .iter()
+        .chain(&repeat_hotkey)
+        .chain(&urgent_hotkey)
+        .chain(profile_hotkeys.iter().filter(|&h| *h != hotkey))
+    {
+        hotkey_check::warn_conflicts(hotkey);
+    }
+    let output = output_options(&args);
+    output::check_tools(&output)?;
+    let mut types = output.types();
+    for mode in profiles.iter().filter_map(|profile| profile.output) {
+        let profile_output = OutputOptions { mode, ..output };
+        output::check_tools(&profile_output)?;
+        types |= profile_output.types();
+    }
+    if args.notify {
+        cues::check_notify()?;
+    }
+    if output.backend == OutputBackend::Uinput && types {
+        #[cfg(target_os = "linux")]
+        uinput::open(Duration::from_millis(args.key_delay))?;
+        #[cfg(not(target_os = "linux"))]
+        anyhow::bail!("--output-backend uinput is only available on Linux");
+    }
+    match &args.input_fifo {
+        Some(path) => audio::check_fifo(path)?,
+        None => audio::check_input_device(args.audio_device.as_deref())?,
+    }
+    let engine_kind = engine;
+    let model_path = model::ensure_model(engine, args.model).await?;
+    let mut engine = if args.isolate_engine {
+        worker::load_engine(engine, &model_path, args.verbose_load)?
+    } else {
+        model::load_engine(engine, &model_path, args.verbose_load)?
+    };
+    engine.set_initial_prompt(args.initial_prompt.clone());
 
-    let post_processor = if args.post_process {
+    let post_process = args.post_process
+        || profiles
+            .iter()
+            .any(|profile| profile.post_process(args.post_process));
+    if post_process && !post_process::AVAILABLE {
+        return Err(post_process::not_compiled_in().into());
+    }
+    if post_process {
         println!(
             "Post-processing enabled via Ollama ({}:{}, model: {})",
             args.ollama_host, args.ollama_port, args.ollama_model
         );
-        Some(PostProcessor::new(
-            &args.ollama_host,
-            args.ollama_port,
-            &args.ollama_model,
-        ))
-    } else {
-        None
+    }
+    let hints = load_hints(args.hints.as_deref())?;
+    let dictionary = load_dictionary(args.dictionary.as_deref())?;
+    // Always built so post-processing can be toggled at runtime
+    let post_processor =
+        PostProcessor::new(&args.ollama_host, args.ollama_port, &args.ollama_model);
+
+    let mut recorder = match args.input_fifo {
+        Some(path) => {
+            println!(
+                "Reading audio from FIFO {:?} ({} Hz s16le mono)",
+                path, args.input_fifo_rate
+            );
+            AudioRecorder::with_fifo(path, args.input_fifo_rate)
+        }
+        None => match args.audio_device {
+            Some(device) => AudioRecorder::new().with_device(device),
+            None => AudioRecorder::new(),
+        },
     };
+    if args.spill {
+        recorder = recorder.spill_to(audio::spill_dir());
+    }
+    if let Some(dir) = args.save_audio {
+        recorder = recorder.save_audio_to(dir);
+    }
+    let max_duration = (args.max_duration > 0).then(|| Duration::from_secs(args.max_duration));
+    if let Some(max_duration) = max_duration {
+        recorder = recorder.max_duration(max_duration);
+    }
+    if args.trim_silence {
+        recorder = recorder.trim_silence();
+    }
+    if args.vad == vad::VadKind::Silero {
+        let path = vad::ensure_silero_model().await?;
+        recorder = recorder.with_vad(vad::Vad::Silero(vad::Silero::load(&path)?));
+    }
 
-    // Build and start the hotkey listener
-    let handle = HotkeyListenerBuilder::new()
-        .add_hotkey(hotkey)
-        .build()?
-        .start()?;
+    // Start the hotkey listener
+    // Listener indices must match event_loop::GOOD_KEY and BAD_KEY
+    let mut hotkeys: Vec<_> = std::iter::once(hotkey).chain(feedback_hotkeys).collect();
+    let mut add_hotkey = |hotkey| {
+        hotkeys.push(hotkey);
+        hotkeys.len() - 1
+    };
+    let device_key = cycle_hotkey.map(&mut add_hotkey);
+    let repeat_key = repeat_hotkey.map(&mut add_hotkey);
+    let urgent_key = urgent_hotkey.map(&mut add_hotkey);
+    // A profile for the --key hotkey applies to the main listener
+    let profile_keys: Vec<_> = profile_hotkeys
+        .into_iter()
+        .map(|profile_hotkey| {
+            if profile_hotkey == hotkey {
+                0
+            } else {
+                add_hotkey(profile_hotkey)
+            }
+        })
+        .collect();
+    let mut hotkey_events = input::listen(&hotkeys, args.switch.as_deref())?;
+    if args.debounce > 0 {
+        hotkey_events = input::debounce(hotkey_events, Duration::from_millis(args.debounce));
+    }
 
-    println!("Listening for {:?}...", args.key);
-    println!("Hold the key to record, release to transcribe.");
+    println!("{}", trf(Msg::Listening, &[&format!("{:?}", args.key)]));
+    println!("{}", tr(Msg::HoldToRecord));
+    for profile in &profiles {
+        println!("{}", trf(Msg::ProfileKey, &[&profile.key, &profile.name]));
+    }
+    if let Some(key) = &args.urgent_key {
+        println!("{}", trf(Msg::UrgentKey, &[key]));
+    }
 
     #[cfg(target_os = "macos")]
-    println!("Note: You may need to grant Accessibility permissions.");
+    println!("{}", tr(Msg::AccessibilityNote));
+
+    let settings = Settings {
+        output,
+        auto_submit: args.auto_submit,
+        post_process: args.post_process,
+        output_language: args.output_language,
+        hints,
+        dictionary,
+        initial_prompt: args.initial_prompt,
+        profiles: profile_keys.into_iter().zip(profiles).collect(),
+        split_after: args.split_after,
+        stream: args.stream,
+        auto_stop: args.auto_stop,
+        max_duration,
+        watchdog: (args.watchdog > 0).then(|| Duration::from_secs(args.watchdog)),
+        min_hold: Duration::from_millis(args.min_hold),
+        latch: args.latch,
+        headset_button: args.headset_button,
+        feedback: args.feedback,
+        spoken_corrections: args.spoken_corrections,
+        compose: args.compose,
+        daemon: matches!(args.command, Some(Command::Daemon)),
+        devices: args.cycle_devices,
+        device_key,
+        history: args.history,
+        repeat_key,
+        urgent_key,
+        terminal_title: args.terminal_title,
+        status_file: args.status_file,
+        todo: args.todo,
+        reminders: args.reminders,
+        unload_after: args.unload_after.map(Duration::from_secs),
+        model: (engine_kind, model_path),
+        isolate_engine: args.isolate_engine,
+        beep: args.beep,
+        notify: args.notify,
+    };
 
-    event_loop::run(engine, handle, args.output, post_processor).await
+    event_loop::run(engine, hotkey_events, recorder, post_processor, settings).await
 }
@@ -1,58 +1,260 @@
+use crate::engine::{Engine, EngineKind, ParakeetVariant, WhisperSize};
+use crate::error::AppError;
+use crate::report::{self, progress};
+use crate::retry::{self, Backend};
 use anyhow::{Context, Result};
 use flate2::read::GzDecoder;
 use futures_util::StreamExt;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use std::fs::File;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 use tar::Archive;
 use tokio::io::AsyncWriteExt;
-use transcribe_rs::engines::parakeet::{ParakeetEngine, ParakeetModelParams};
-use transcribe_rs::TranscriptionEngine;
 
-const MODEL_URL: &str = "https://blob.handy.computer/parakeet-v3-int8.tar.gz";
-const MODEL_DIR_NAME: &str = "parakeet-tdt-0.6b-v3-int8";
+const CHECKSUM_MANIFEST: &str = "checksums.sha256";
+/// SHA-256 of `parakeet-v3-int8.tar.gz`, whose host publishes none. Not
+/// recorded yet, so the archive is downloaded unverified (with a warning)
+/// until it is set from a known-good download.
+const PARAKEET_INT8_SHA256: Option<&str> = None;
 
-fn cache_dir() -> PathBuf {
+/// Where an engine's model lives in the cache and how to download it.
+struct ModelSpec {
+    dir_name: &'static str,
+    /// Files that must be present for the model to load
+    files: &'static [&'static str],
+    source: ModelSource,
+}
+
+enum ModelSource {
+    /// A `.tar.gz` that extracts to `dir_name`
+    Archive {
+        url: &'static str,
+        size: &'static str,
+        /// SHA-256 of the archive, for hosts that don't publish one
+        sha256: Option<&'static str>,
+    },
+    /// Individual files downloaded from `base_url` + file name
+    Files { base_url: &'static str },
+}
+
+fn spec(kind: EngineKind) -> ModelSpec {
+    match kind {
+        EngineKind::Parakeet(ParakeetVariant::Int8) => ModelSpec {
+            dir_name: "parakeet-tdt-0.6b-v3-int8",
+            files: &[
+                "encoder-model.int8.onnx",
+                "decoder_joint-model.int8.onnx",
+                "vocab.txt",
+            ],
+            source: ModelSource::Archive {
+                url: "https://blob.handy.computer/parakeet-v3-int8.tar.gz",
+                size: "~478 MB",
+                sha256: PARAKEET_INT8_SHA256,
+            },
+        },
+        EngineKind::Parakeet(ParakeetVariant::Fp32) => ModelSpec {
+            dir_name: "parakeet-tdt-0.6b-v3-fp32",
+            files: &[
+                "encoder-model.onnx",
+                // The encoder's weights, too large to live inside the .onnx file
+                "encoder-model.onnx.data",
+                "decoder_joint-model.onnx",
+                "nemo128.onnx",
+                "vocab.txt",
+            ],
+            source: ModelSource::Files {
+                base_url:
+                    "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/",
+            },
+        },
+        EngineKind::Moonshine => ModelSpec {
+            dir_name: "moonshine-tiny",
+            files: &[
+                "encoder_model.onnx",
+                "decoder_model_merged.onnx",
+                "tokenizer.json",
+            ],
+            source: ModelSource::Files {
+                base_url:
+                    "https://huggingface.co/UsefulSensors/moonshine/resolve/main/onnx/merged/tiny/",
+            },
+        },
+        EngineKind::Whisper(size) => ModelSpec {
+            dir_name: match size {
+                WhisperSize::Tiny => "whisper-tiny",
+                WhisperSize::Base => "whisper-base",
+                WhisperSize::Small => "whisper-small",
+                WhisperSize::Medium => "whisper-medium",
+                WhisperSize::LargeV3Turbo => "whisper-large-v3-turbo",
+            },
+            files: match size {
+                WhisperSize::Tiny => &["ggml-tiny.bin"],
+                WhisperSize::Base => &["ggml-base.bin"],
+                WhisperSize::Small => &["ggml-small.bin"],
+                WhisperSize::Medium => &["ggml-medium.bin"],
+                WhisperSize::LargeV3Turbo => &["ggml-large-v3-turbo.bin"],
+            },
+            source: ModelSource::Files {
+                base_url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/",
+            },
+        },
+    }
+}
+
+pub fn cache_dir() -> PathBuf {
     dirs::cache_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join("parakeet-writer")
 }
 
-fn default_model_path() -> PathBuf {
-    cache_dir().join(MODEL_DIR_NAME)
+fn default_model_path(kind: EngineKind) -> PathBuf {
+    cache_dir().join(spec(kind).dir_name)
+}
+
+/// The engine whose model files are in `path`, if any.
+pub fn detect_engine(path: &Path) -> Option<EngineKind> {
+    EngineKind::ALL
+        .into_iter()
+        .find(|&kind| verify_model(kind, path))
+}
+
+/// Whether `kind`'s model is in its default directory.
+pub fn is_downloaded(kind: EngineKind) -> bool {
+    verify_model(kind, &default_model_path(kind))
 }
 
-fn verify_model(path: &Path) -> bool {
+fn verify_model(kind: EngineKind, path: &Path) -> bool {
     if !path.exists() || !path.is_dir() {
         return false;
     }
-    let encoder = path.join("encoder-model.int8.onnx");
-    let decoder = path.join("decoder_joint-model.int8.onnx");
-    let vocab = path.join("vocab.txt");
-    encoder.exists() && decoder.exists() && vocab.exists()
+    spec(kind).files.iter().all(|file| path.join(file).exists())
 }
 
-async fn download_model(dest_dir: &Path) -> Result<()> {
-    println!("Downloading Parakeet v3 model (~478 MB)...");
-
+async fn download_model(kind: EngineKind, dest_dir: &Path) -> Result<()> {
+    let spec = spec(kind);
     tokio::fs::create_dir_all(dest_dir.parent().unwrap_or(dest_dir))
         .await
         .context("Failed to create cache directory")?;
 
-    let response = reqwest::get(MODEL_URL)
+    match spec.source {
+        ModelSource::Archive { url, size, sha256 } => {
+            progress!("Downloading {} model ({})...", kind.name(), size);
+            let temp_path = dest_dir.with_extension("tar.gz.tmp");
+            download_file(url, &temp_path).await?;
+            verify_download(url, sha256, &temp_path).await?;
+
+            progress!("Extracting model...");
+
+            // Archive extraction is blocking, run in spawn_blocking
+            let temp_path_clone = temp_path.clone();
+            let extract_dir = dest_dir.parent().unwrap_or(dest_dir).to_path_buf();
+            tokio::task::spawn_blocking(move || {
+                let tar_gz = File::open(&temp_path_clone).context("Failed to open archive")?;
+                let tar = GzDecoder::new(tar_gz);
+                let mut archive = Archive::new(tar);
+                archive
+                    .unpack(&extract_dir)
+                    .context("Failed to extract archive")?;
+                Ok::<_, anyhow::Error>(())
+            })
+            .await
+            .context("Extraction task failed")??;
+
+            tokio::fs::remove_file(&temp_path).await.ok();
+        }
+        ModelSource::Files { base_url } => {
+            progress!("Downloading {} model...", kind.name());
+            tokio::fs::create_dir_all(dest_dir)
+                .await
+                .context("Failed to create model directory")?;
+            for file in spec.files {
+                progress!("  {}", file);
+                let temp_path = dest_dir.join(format!("{}.tmp", file));
+                let url = format!("{}{}", base_url, file);
+                download_file(&url, &temp_path).await?;
+                verify_download(&url, None, &temp_path).await?;
+                tokio::fs::rename(&temp_path, dest_dir.join(file))
+                    .await
+                    .context("Failed to move downloaded file into place")?;
+            }
+        }
+    }
+
+    progress!("[+] Model ready!");
+    Ok(())
+}
+
+/// Download `url` to `dest`, drawing a progress bar on stderr. A partial `dest`
+/// left behind by an interrupted download is resumed if the server supports it,
+/// including between the attempts of the `--retry` policy.
+pub async fn download_file(url: &str, dest: &Path) -> Result<()> {
+    retry::run(Backend::Download, "Download", is_transient, || {
+        download_once(url, dest)
+    })
+    .await
+}
+
+/// Whether a download error is worth retrying: a network error or a server
+/// error, but not a missing file or a full disk.
+fn is_transient(e: &anyhow::Error) -> bool {
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|e| match e.status() {
+            Some(status) => {
+                status.is_server_error()
+                    || status == reqwest::StatusCode::REQUEST_TIMEOUT
+                    || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            None => true,
+        })
+}
+
+async fn download_once(url: &str, dest: &Path) -> Result<()> {
+    let mut existing = tokio::fs::metadata(dest)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let mut response = request_from(url, existing)
         .await
         .context("Failed to start download")?;
+    if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file doesn't match what the server has; start over
+        existing = 0;
+        response = request_from(url, 0)
+            .await
+            .context("Failed to start download")?;
+    }
+    let response = response
+        .error_for_status()
+        .context("Failed to start download")?;
 
-    let total_size = response.content_length().unwrap_or(0);
+    let resumed = existing > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut downloaded: u64 = if resumed { existing } else { 0 };
+    let total_size = response
+        .content_length()
+        .map(|len| len + downloaded)
+        .unwrap_or(0);
 
-    let temp_path = dest_dir.with_extension("tar.gz.tmp");
-    let mut file = tokio::fs::File::create(&temp_path)
-        .await
-        .context("Failed to create temp file")?;
+    let mut file = if resumed {
+        progress!(
+            "Resuming download at {:.1} MB",
+            downloaded as f64 / 1_000_000.0
+        );
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(dest)
+            .await
+            .context("Failed to open partial download")?
+    } else {
+        tokio::fs::File::create(dest)
+            .await
+            .context("Failed to create temp file")?
+    };
 
     let mut stream = response.bytes_stream();
-    let mut downloaded: u64 = 0;
     let mut last_percent = 0;
 
     while let Some(chunk) = stream.next().await {
@@ -62,8 +264,8 @@ async fn download_model(dest_dir: &Path) -> Result<()> {
             .context("Failed to write to file")?;
         downloaded += chunk.len() as u64;
 
-        if total_size > 0 {
-            let percent = (downloaded * 100 / total_size) as usize;
+        if let Some(percent) = (downloaded * 100).checked_div(total_size) {
+            let percent = percent as usize;
             if percent != last_percent {
                 let filled = percent / 5;
                 let bar = "=".repeat(filled) + &" ".repeat(20 - filled);
@@ -81,58 +283,553 @@ async fn download_model(dest_dir: &Path) -> Result<()> {
         "\r[+] Download complete: {:.1} MB                    ",
         downloaded as f64 / 1_000_000.0
     );
+    Ok(())
+}
 
-    println!("Extracting model...");
-
-    // Archive extraction is blocking, run in spawn_blocking
-    let temp_path_clone = temp_path.clone();
-    let extract_dir = dest_dir.parent().unwrap_or(dest_dir).to_path_buf();
-    tokio::task::spawn_blocking(move || {
-        let tar_gz = File::open(&temp_path_clone).context("Failed to open archive")?;
-        let tar = GzDecoder::new(tar_gz);
-        let mut archive = Archive::new(tar);
-        archive
-            .unpack(&extract_dir)
-            .context("Failed to extract archive")?;
-        Ok::<_, anyhow::Error>(())
-    })
-    .await
-    .context("Extraction task failed")??;
+/// Check a downloaded file against its `pinned` SHA-256, or else the one its
+/// host publishes for it. A file that doesn't match is removed so the next
+/// attempt starts over; one with neither is kept, but reported as unverified.
+pub async fn verify_download(url: &str, pinned: Option<&str>, path: &Path) -> Result<()> {
+    let expected = match pinned {
+        Some(pinned) => Some(pinned.to_string()),
+        None => published_sha256(url).await,
+    };
+    let Some(expected) = expected else {
+        progress!(
+            "[!] Unverified download: there is no checksum for {} to check it against",
+            url
+        );
+        return Ok(());
+    };
+    let file = path.to_path_buf();
+    let actual = tokio::task::spawn_blocking(move || sha256_file(&file))
+        .await
+        .context("Checksum task failed")??;
+    if actual != expected {
+        tokio::fs::remove_file(path).await.ok();
+        anyhow::bail!(
+            "Checksum mismatch for {} (expected {}, got {}), please try again",
+            url,
+            expected,
+            actual
+        );
+    }
+    log::debug!("Checksum of {} OK", url);
+    Ok(())
+}
 
-    tokio::fs::remove_file(&temp_path).await.ok();
-    println!("[+] Model ready!");
+/// The SHA-256 the host publishes for `url`, if any. Hugging Face sends it as
+/// the `X-Linked-Etag` of files stored with Git LFS, on the redirect to its CDN.
+async fn published_sha256(url: &str) -> Option<String> {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .ok()?;
+    let response = client.head(url).send().await.ok()?;
+    sha256_from_etag(response.headers().get("x-linked-etag")?.to_str().ok()?)
+}
 
-    Ok(())
+fn sha256_from_etag(etag: &str) -> Option<String> {
+    let etag = etag.trim_start_matches("W/").trim_matches('"');
+    (etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| etag.to_ascii_lowercase())
+}
+
+/// GET `url`, asking for the bytes from `offset` onwards when it is non-zero.
+async fn request_from(url: &str, offset: u64) -> reqwest::Result<reqwest::Response> {
+    let mut request = reqwest::Client::new().get(url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+    request.send().await
 }
 
-pub async fn ensure_model(model_path: Option<PathBuf>) -> Result<PathBuf> {
+/// Where the model for `kind` is: `model_path` if given, otherwise the cache.
+pub fn model_path(kind: EngineKind, model_path: Option<PathBuf>) -> PathBuf {
+    model_path.unwrap_or_else(|| default_model_path(kind))
+}
+
+pub async fn ensure_model(kind: EngineKind, model_path: Option<PathBuf>) -> Result<PathBuf> {
     let user_provided = model_path.is_some();
-    let path = model_path.unwrap_or_else(default_model_path);
+    let path = self::model_path(kind, model_path);
 
-    if verify_model(&path) {
+    if verify_model(kind, &path) {
         return Ok(path);
     }
 
     if user_provided {
-        anyhow::bail!("Model not found at {:?}", path);
+        return Err(AppError::ModelMissing(path).into());
     }
 
-    download_model(&path).await?;
+    download_model(kind, &path).await?;
 
-    if !verify_model(&path) {
+    if !verify_model(kind, &path) {
         anyhow::bail!("Model verification failed after download");
     }
 
+    // Record checksums so later `model verify --deep` runs can detect corruption
+    let manifest_dir = path.clone();
+    tokio::task::spawn_blocking(move || write_checksums(&manifest_dir))
+        .await
+        .context("Checksum task failed")??;
+
     Ok(path)
 }
 
-pub fn load_engine(model_path: &Path) -> Result<ParakeetEngine> {
-    println!("Loading Parakeet model from {:?}...", model_path);
+pub fn load_engine(kind: EngineKind, model_path: &Path, verbose_load: bool) -> Result<Engine> {
+    progress!("Loading {} model from {:?}...", kind.name(), model_path);
+    let rss_before = if verbose_load {
+        report_file_reads(model_path)?;
+        current_rss_bytes()
+    } else {
+        None
+    };
+
     let load_start = Instant::now();
-    let mut engine = ParakeetEngine::new();
-    engine
-        .load_model_with_params(model_path, ParakeetModelParams::int8())
-        .map_err(|e| anyhow::anyhow!("{}", e))?;
-    println!("Model loaded in {:.2?}", load_start.elapsed());
+    let engine = match kind {
+        // whisper.cpp loads the model file rather than its directory
+        EngineKind::Whisper(_) => Engine::load(kind, &model_path.join(spec(kind).files[0]))?,
+        _ => Engine::load(kind, model_path)?,
+    };
+    progress!("Model loaded in {:.2?}", load_start.elapsed());
+
+    if verbose_load {
+        report_memory(rss_before, current_rss_bytes());
+        report_providers();
+    }
     Ok(engine)
 }
+
+#[derive(Serialize)]
+struct ModelStatus {
+    engine: &'static str,
+    path: PathBuf,
+    present: bool,
+    /// Bytes on disk
+    size: u64,
+    /// Whether this is the model the other commands would use
+    selected: bool,
+}
+
+/// `model status`: every model the engines can use, and which are downloaded.
+pub fn status(kind: EngineKind, model_path: Option<PathBuf>) -> Result<()> {
+    let mut models = Vec::new();
+    for other in EngineKind::ALL {
+        let selected = other == kind;
+        let path = if selected {
+            self::model_path(kind, model_path.clone())
+        } else {
+            default_model_path(other)
+        };
+        let present = verify_model(other, &path);
+        models.push(ModelStatus {
+            engine: other.name(),
+            size: if present { dir_size(&path)? } else { 0 },
+            path,
+            present,
+            selected,
+        });
+    }
+    if report::json() {
+        return report::emit(&models);
+    }
+    for model in &models {
+        let size = if model.present {
+            format!("{:.1} MB", model.size as f64 / 1_000_000.0)
+        } else {
+            "-".to_string()
+        };
+        println!(
+            "{} {:<24} {:>10}  {}",
+            if model.selected { "*" } else { " " },
+            model.engine,
+            size,
+            model.path.display()
+        );
+    }
+    Ok(())
+}
+
+/// `model download`: fetch the model unless it is already there.
+pub async fn download(kind: EngineKind, model_path: Option<PathBuf>) -> Result<()> {
+    let path = ensure_model(kind, model_path).await?;
+    if report::json() {
+        report::emit(&ModelStatus {
+            engine: kind.name(),
+            size: dir_size(&path)?,
+            path,
+            present: true,
+            selected: true,
+        })?;
+    } else {
+        println!("[+] {} model in {:?}", kind.name(), path);
+    }
+    Ok(())
+}
+
+/// `model remove`: delete the cached model to free the space.
+pub fn remove(kind: EngineKind, model_path: Option<PathBuf>) -> Result<()> {
+    if let Some(path) = model_path {
+        anyhow::bail!(
+            "Only cached models are removed, delete {:?} yourself if you no longer need it",
+            path
+        );
+    }
+    let path = default_model_path(kind);
+    if !path.exists() {
+        progress!("No {} model in {:?}", kind.name(), path);
+        return Ok(());
+    }
+    let size = dir_size(&path)?;
+    std::fs::remove_dir_all(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+    progress!(
+        "[+] Removed {} model, freeing {:.1} MB",
+        kind.name(),
+        size as f64 / 1_000_000.0
+    );
+    Ok(())
+}
+
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path).with_context(|| format!("Failed to read {:?}", path))? {
+        size += entry?.metadata()?.len();
+    }
+    Ok(size)
+}
+
+#[derive(Serialize)]
+struct VerifyReport {
+    path: PathBuf,
+    present: bool,
+    /// Per-file checksum results, only with `--deep`
+    files: Vec<FileCheck>,
+    /// Whether there is no checksum manifest to compare against, with `--deep`
+    manifest_missing: bool,
+    inference_ms: Option<f64>,
+    inference_text: Option<String>,
+    ok: bool,
+}
+
+#[derive(Serialize)]
+struct FileCheck {
+    name: String,
+    status: FileStatus,
+}
+
+#[derive(Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum FileStatus {
+    Ok,
+    Missing,
+    Mismatch,
+}
+
+/// Check the model at `model_path` (or the default cache location).
+///
+/// With `deep`, file checksums are compared against the manifest recorded at
+/// download time and a short test inference is run.
+pub async fn verify(
+    kind: EngineKind,
+    model_path: Option<PathBuf>,
+    deep: bool,
+    verbose_load: bool,
+) -> Result<()> {
+    let path = self::model_path(kind, model_path);
+    let mut report = VerifyReport {
+        path: path.clone(),
+        present: verify_model(kind, &path),
+        files: Vec::new(),
+        manifest_missing: false,
+        inference_ms: None,
+        inference_text: None,
+        ok: false,
+    };
+
+    if !report.present {
+        if report::json() {
+            report::emit(&report)?;
+        }
+        return Err(AppError::ModelMissing(path).into());
+    }
+    if !report::json() {
+        println!("[+] Required model files present in {:?}", path);
+    }
+
+    if deep {
+        progress!("Checking file checksums...");
+        let manifest_dir = path.clone();
+        let files = tokio::task::spawn_blocking(move || verify_checksums(&manifest_dir))
+            .await
+            .context("Checksum task failed")??;
+        report.manifest_missing = files.is_none();
+        report.files = files.unwrap_or_default();
+        if !report::json() {
+            print_checksums(&report);
+        }
+    }
+
+    let bad = report
+        .files
+        .iter()
+        .filter(|f| f.status != FileStatus::Ok)
+        .count();
+    if deep && bad == 0 && !report.manifest_missing {
+        let engine_path = path.clone();
+        let (elapsed, text) = tokio::task::spawn_blocking(move || {
+            let mut engine = load_engine(kind, &engine_path, verbose_load)?;
+            let start = Instant::now();
+            // One second of silence is enough to exercise the full pipeline
+            let result = engine
+                .transcribe_samples(vec![0.0; 16000])
+                .context("Test inference failed")?;
+            let elapsed = start.elapsed();
+            engine.unload_model();
+            Ok::<_, anyhow::Error>((elapsed, result.text.trim().to_string()))
+        })
+        .await
+        .context("Inference task failed")??;
+        if !report::json() {
+            println!(
+                "[+] Test inference ran in {:.2?} (output: {:?})",
+                elapsed, text
+            );
+        }
+        report.inference_ms = Some(elapsed.as_secs_f64() * 1000.0);
+        report.inference_text = Some(text);
+    }
+
+    report.ok = bad == 0 && !report.manifest_missing;
+    if report::json() {
+        report::emit(&report)?;
+    }
+    if bad > 0 {
+        anyhow::bail!(
+            "{} model file(s) failed verification, remove {:?} to re-download",
+            bad,
+            path
+        );
+    }
+    if report.manifest_missing {
+        anyhow::bail!("Model files in {:?} could not be verified", path);
+    }
+    if deep && !report::json() {
+        println!("[+] Model OK");
+    }
+    Ok(())
+}
+
+fn print_checksums(report: &VerifyReport) {
+    if report.manifest_missing {
+        println!(
+            "[!] No checksum manifest at {:?}, so the files can't be checked. \
+             Models downloaded by older versions have none; `model remove` and \
+             `model download` to get one.",
+            report.path.join(CHECKSUM_MANIFEST)
+        );
+    }
+    for file in &report.files {
+        match file.status {
+            FileStatus::Ok => println!("[+] {}: OK", file.name),
+            FileStatus::Missing => println!("[-] {}: missing", file.name),
+            FileStatus::Mismatch => println!("[-] {}: checksum mismatch", file.name),
+        }
+    }
+}
+
+/// All regular files in the model directory except the checksum manifest, sorted by name.
+fn model_files(model_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(model_path).context("Failed to read model directory")? {
+        let path = entry?.path();
+        if path.is_file() && path.file_name() != Some(CHECKSUM_MANIFEST.as_ref()) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Write a `sha256sum`-compatible manifest of the model files.
+fn write_checksums(model_path: &Path) -> Result<()> {
+    let mut manifest = String::new();
+    for path in model_files(model_path)? {
+        manifest.push_str(&format!("{}  {}\n", sha256_file(&path)?, file_name(&path)));
+    }
+    std::fs::write(model_path.join(CHECKSUM_MANIFEST), manifest)
+        .context("Failed to write checksum manifest")?;
+    Ok(())
+}
+
+/// Compare model files against the checksum manifest recorded at download
+/// time, or `None` if there is none. One isn't recorded now, as it would
+/// vouch for files that may already be damaged.
+fn verify_checksums(model_path: &Path) -> Result<Option<Vec<FileCheck>>> {
+    let manifest_path = model_path.join(CHECKSUM_MANIFEST);
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+
+    let manifest =
+        std::fs::read_to_string(&manifest_path).context("Failed to read checksum manifest")?;
+    let mut files = Vec::new();
+    for line in manifest.lines().filter(|l| !l.trim().is_empty()) {
+        let (expected, name) = line
+            .split_once("  ")
+            .with_context(|| format!("Malformed checksum manifest line: {:?}", line))?;
+        let path = model_path.join(name);
+        let status = if !path.exists() {
+            FileStatus::Missing
+        } else if sha256_file(&path)? == expected {
+            FileStatus::Ok
+        } else {
+            FileStatus::Mismatch
+        };
+        files.push(FileCheck {
+            name: name.to_string(),
+            status,
+        });
+    }
+    Ok(Some(files))
+}
+
+/// Read every model file once, reporting sizes and read times. This also warms the
+/// page cache so the engine load time that follows is mostly session setup.
+fn report_file_reads(model_path: &Path) -> Result<()> {
+    let mut buf = vec![0u8; 1 << 20];
+    for path in model_files(model_path)? {
+        let start = Instant::now();
+        let mut file = File::open(&path).with_context(|| format!("Failed to open {:?}", path))?;
+        let mut size: u64 = 0;
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            size += n as u64;
+        }
+        progress!(
+            "  {:<32} {:>8.1} MB  read in {:.2?}",
+            file_name(&path),
+            size as f64 / 1_000_000.0,
+            start.elapsed()
+        );
+    }
+    Ok(())
+}
+
+fn report_memory(before: Option<u64>, after: Option<u64>) {
+    match (before, after) {
+        (Some(before), Some(after)) => progress!(
+            "  RSS: {:.1} MB before, {:.1} MB after ({:+.1} MB)",
+            before as f64 / 1_000_000.0,
+            after as f64 / 1_000_000.0,
+            (after as f64 - before as f64) / 1_000_000.0
+        ),
+        _ => progress!("  RSS: unavailable on this platform"),
+    }
+}
+
+fn report_providers() {
+    progress!("  {}", ort::info());
+    progress!(
+        "  Available execution providers: {}",
+        available_providers().join(", ")
+    );
+    progress!("  Session execution provider: CPUExecutionProvider");
+}
+
+/// Names of the ONNX Runtime execution providers usable on this machine.
+pub fn available_providers() -> Vec<&'static str> {
+    use ort::execution_providers::{
+        CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProvider,
+        OpenVINOExecutionProvider, ROCmExecutionProvider, TensorRTExecutionProvider,
+        XNNPACKExecutionProvider,
+    };
+
+    let providers: [&dyn ExecutionProvider; 7] = [
+        &CPUExecutionProvider::default(),
+        &CUDAExecutionProvider::default(),
+        &TensorRTExecutionProvider::default(),
+        &ROCmExecutionProvider::default(),
+        &CoreMLExecutionProvider::default(),
+        &OpenVINOExecutionProvider::default(),
+        &XNNPACKExecutionProvider::default(),
+    ];
+    providers
+        .iter()
+        .filter(|p| p.is_available().unwrap_or(false))
+        .map(|p| p.name())
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn current_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(target_os = "macos")]
+fn current_rss_bytes() -> Option<u64> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "rss=", "-p", &std::process::id().to_string()])
+        .output()
+        .ok()?;
+    let kb: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_from_etag() {
+        let sha = "a".repeat(64);
+        assert_eq!(sha256_from_etag(&format!("\"{}\"", sha)), Some(sha.clone()));
+        assert_eq!(
+            sha256_from_etag(&format!("W/\"{}\"", sha.to_uppercase())),
+            Some(sha)
+        );
+        // Git blob ids of small files aren't checksums of the content
+        assert_eq!(sha256_from_etag(&format!("\"{}\"", "b".repeat(40))), None);
+    }
+
+    #[tokio::test]
+    async fn test_verify_fails_without_manifest() {
+        let kind = EngineKind::Parakeet(ParakeetVariant::Int8);
+        let dir = tempfile::tempdir().unwrap();
+        for file in spec(kind).files {
+            std::fs::write(dir.path().join(file), b"").unwrap();
+        }
+        let path = Some(dir.path().to_path_buf());
+        // Present is enough without --deep, but unchecked files aren't OK
+        verify(kind, path.clone(), false, false).await.unwrap();
+        assert!(verify(kind, path, true, false).await.is_err());
+    }
+}
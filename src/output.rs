@@ -1,13 +1,15 @@
+use crate::correction::Edit;
+use crate::error::AppError;
+use crate::i18n::{trf, Msg};
 use anyhow::{Context, Result};
 use clap::ValueEnum;
-use tokio::process::Command;
-
-#[cfg(target_os = "macos")]
+use serde::Deserialize;
 use std::process::Stdio;
-#[cfg(target_os = "macos")]
 use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
 
-#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputMode {
     /// Type text directly
     Typing,
@@ -18,17 +20,194 @@ pub enum OutputMode {
     Both,
 }
 
-pub async fn output_text(text: &str, mode: OutputMode) -> Result<()> {
-    match mode {
+/// How line breaks in a transcript are typed.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum NewlineMode {
+    /// Press Enter (submits the message in most chat apps)
+    #[default]
+    Enter,
+    /// Press Shift+Enter (line break without submitting in most chat apps)
+    ShiftEnter,
+    /// Paste the whole transcript from the clipboard, keeping newlines literal
+    Paste,
+}
+
+/// Whether Enter is pressed after a transcript has been typed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoSubmit {
+    #[default]
+    Off,
+    /// Always press Enter
+    On,
+    /// Press Enter only when the hotkey is tapped again shortly afterwards
+    Confirm,
+}
+
+/// The tools text is typed and copied with on Linux.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputBackend {
+    /// Wayland if WAYLAND_DISPLAY is set, otherwise X11 if DISPLAY is set
+    #[default]
+    Auto,
+    /// wtype and wl-copy
+    Wayland,
+    /// xdotool and xclip
+    X11,
+    /// A virtual keyboard via /dev/uinput for typing; the session's tool for the clipboard
+    Uinput,
+}
+
+impl OutputBackend {
+    /// Pick a concrete backend for the session we're running in.
+    pub fn resolve(self) -> Self {
+        let set = |var| std::env::var_os(var).is_some_and(|value| !value.is_empty());
+        match self {
+            OutputBackend::Auto if !set("WAYLAND_DISPLAY") && set("DISPLAY") => OutputBackend::X11,
+            OutputBackend::Auto => OutputBackend::Wayland,
+            backend => backend,
+        }
+    }
+
+    /// The backend that handles the clipboard, which uinput can't.
+    fn clipboard(self) -> Self {
+        match self {
+            OutputBackend::Uinput => OutputBackend::Auto.resolve(),
+            backend => backend,
+        }
+    }
+}
+
+/// Clipboard manager that gets each transcript pushed into its history explicitly,
+/// for managers that miss or drop copies made by short-lived `wl-copy` processes.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ClipboardHistory {
+    Cliphist,
+    Copyq,
+}
+
+impl ClipboardHistory {
+    fn tool(self) -> &'static str {
+        match self {
+            ClipboardHistory::Cliphist => "cliphist",
+            ClipboardHistory::Copyq => "copyq",
+        }
+    }
+}
+
+/// How clipboard copies interact with clipboard managers (Linux).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClipboardOptions {
+    /// Serve the copy for a single paste only, then clear it
+    pub paste_once: bool,
+    /// Also add the transcript to this clipboard manager's history
+    pub history: Option<ClipboardHistory>,
+    /// Offer the transcript's metadata as `application/json` next to the text
+    pub metadata: bool,
+}
+
+/// Everything that controls how a transcript is delivered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputOptions {
+    pub mode: OutputMode,
+    /// Resolved backend (never `Auto`); only used on Linux
+    pub backend: OutputBackend,
+    pub clipboard: ClipboardOptions,
+    pub newline: NewlineMode,
+}
+
+impl OutputOptions {
+    /// Whether transcripts end up in the focused window rather than only the clipboard.
+    pub fn types(&self) -> bool {
+        matches!(self.mode, OutputMode::Typing | OutputMode::Both)
+    }
+
+    /// Whether `text` is delivered by pasting it rather than typing it.
+    fn pastes(&self, text: &str) -> bool {
+        matches!(self.newline, NewlineMode::Paste) && text.contains('\n')
+    }
+}
+
+/// Fail early when the external programs needed for `options` aren't installed.
+pub fn check_tools(options: &OutputOptions) -> Result<()> {
+    let clipboard = &options.clipboard;
+    let typing = options.types();
+    let clipboard_mode = matches!(options.mode, OutputMode::Clipboard | OutputMode::Both)
+        || (typing && matches!(options.newline, NewlineMode::Paste));
+
+    #[cfg(target_os = "linux")]
+    let tools = [
+        match options.backend {
+            OutputBackend::X11 => (typing, "xdotool", "xdotool"),
+            // The virtual keyboard is created separately, see `uinput::open`
+            OutputBackend::Uinput => (false, "", ""),
+            OutputBackend::Auto | OutputBackend::Wayland => (typing, "wtype", "wtype"),
+        },
+        match options.backend.clipboard() {
+            OutputBackend::X11 => (clipboard_mode, "xclip", "xclip"),
+            _ => (clipboard_mode, "wl-copy", "wl-clipboard"),
+        },
+    ];
+    #[cfg(target_os = "macos")]
+    let tools = [
+        (typing, "osascript", "macOS"),
+        (clipboard_mode, "pbcopy", "macOS"),
+    ];
+
+    for (needed, tool, package) in tools {
+        if needed && !in_path(tool) {
+            return Err(AppError::OutputToolMissing { tool, package }.into());
+        }
+    }
+    // Neither wl-copy, xclip nor pbcopy can offer more than one type at a time
+    if clipboard.metadata && !matches!(clipboard.history, Some(ClipboardHistory::Copyq)) {
+        anyhow::bail!("--clipboard-metadata needs --clipboard-history copyq");
+    }
+    if let Some(history) = clipboard.history.filter(|_| clipboard_mode) {
+        let tool = history.tool();
+        if !in_path(tool) {
+            return Err(AppError::OutputToolMissing {
+                tool,
+                package: tool,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Whether `tool` is an executable on the PATH.
+pub fn in_path(tool: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(tool).is_file()))
+        .unwrap_or(false)
+}
+
+/// Type and/or copy `text`. `metadata` is the JSON offered alongside it on the
+/// clipboard with `--clipboard-metadata`.
+pub async fn output_text(
+    text: &str,
+    metadata: Option<&str>,
+    options: &OutputOptions,
+) -> Result<()> {
+    match options.mode {
         OutputMode::Typing => {
-            type_text(text).await?;
+            type_text(text, options).await?;
         }
         OutputMode::Clipboard => {
-            copy_to_clipboard(text).await?;
-            println!("Copied to clipboard: {}", text);
+            copy_to_clipboard(text, metadata, options).await?;
+            println!("{}", trf(Msg::CopiedToClipboard, &[&text]));
+        }
+        OutputMode::Both if options.pastes(text) => {
+            // The paste needs the clipboard set first, so this can't run concurrently
+            copy_to_clipboard(text, metadata, options).await?;
+            paste(options).await?;
         }
         OutputMode::Both => {
-            let (type_result, clip_result) = tokio::join!(type_text(text), copy_to_clipboard(text));
+            let (type_result, clip_result) = tokio::join!(
+                type_text(text, options),
+                copy_to_clipboard(text, metadata, options)
+            );
             type_result?;
             clip_result?;
         }
@@ -36,14 +215,75 @@ pub async fn output_text(text: &str, mode: OutputMode) -> Result<()> {
     Ok(())
 }
 
+/// Output a chunk of a split recording: type `text`, and copy `clipboard`, the
+/// text of the recording so far, so earlier chunks stay on the clipboard.
+pub async fn output_chunk(
+    text: &str,
+    clipboard: &str,
+    metadata: Option<&str>,
+    options: &OutputOptions,
+) -> Result<()> {
+    if options.types() {
+        type_text(text, options).await?;
+    }
+    if matches!(options.mode, OutputMode::Clipboard | OutputMode::Both) {
+        copy_to_clipboard(clipboard, metadata, options).await?;
+    }
+    if matches!(options.mode, OutputMode::Clipboard) {
+        println!("{}", trf(Msg::CopiedToClipboard, &[&text]));
+    }
+    Ok(())
+}
+
+/// A command for an output tool that is killed, rather than left running, if
+/// waiting for it is given up (see `--watchdog`).
+fn command(program: &str) -> Command {
+    let mut command = Command::new(program);
+    command.kill_on_drop(true);
+    command
+}
+
+/// Apply a spoken correction to the last output: retype its changed tail and/or
+/// replace the clipboard with the corrected text.
+pub async fn correct(edit: &Edit, options: &OutputOptions) -> Result<()> {
+    if options.types() {
+        press_backspace(edit.delete, options).await?;
+        if !edit.insert.is_empty() {
+            type_text(&edit.insert, options).await?;
+        }
+    }
+    if matches!(options.mode, OutputMode::Clipboard | OutputMode::Both) {
+        copy_to_clipboard(&edit.result, None, options).await?;
+    }
+    Ok(())
+}
+
 #[cfg(target_os = "macos")]
-async fn type_text(text: &str) -> Result<()> {
-    // Use osascript to type text on macOS
+async fn type_text(text: &str, options: &OutputOptions) -> Result<()> {
+    if options.pastes(text) {
+        copy_to_clipboard(text, None, options).await?;
+        return paste(options).await;
+    }
+
+    // Use osascript to type text on macOS, pressing Return (key code 36) between lines
+    let line_break = match options.newline {
+        NewlineMode::ShiftEnter => "key code 36 using shift down",
+        NewlineMode::Enter | NewlineMode::Paste => "key code 36",
+    };
+    let keystrokes: Vec<String> = text
+        .split('\n')
+        .map(|line| {
+            format!(
+                r#"keystroke "{}""#,
+                line.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        })
+        .collect();
     let script = format!(
-        r#"tell application "System Events" to keystroke "{}""#,
-        text.replace('\\', "\\\\").replace('"', "\\\"")
+        "tell application \"System Events\"\n{}\nend tell",
+        keystrokes.join(&format!("\n{}\n", line_break))
     );
-    Command::new("osascript")
+    command("osascript")
         .arg("-e")
         .arg(&script)
         .status()
@@ -52,19 +292,190 @@ async fn type_text(text: &str) -> Result<()> {
     Ok(())
 }
 
+#[cfg(target_os = "macos")]
+async fn press_backspace(count: usize, _options: &OutputOptions) -> Result<()> {
+    // Key code 51 is Delete (backspace)
+    let script = format!(
+        "tell application \"System Events\"\n{}end tell",
+        "key code 51\n".repeat(count)
+    );
+    command("osascript")
+        .arg("-e")
+        .arg(&script)
+        .status()
+        .await
+        .context("Failed to press backspace via osascript")?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+pub async fn press_enter(_options: &OutputOptions) -> Result<()> {
+    command("osascript")
+        .arg("-e")
+        .arg(r#"tell application "System Events" to key code 36"#)
+        .status()
+        .await
+        .context("Failed to press Enter via osascript")?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+async fn paste(_options: &OutputOptions) -> Result<()> {
+    command("osascript")
+        .arg("-e")
+        .arg(r#"tell application "System Events" to keystroke "v" using command down"#)
+        .status()
+        .await
+        .context("Failed to paste via osascript")?;
+    Ok(())
+}
+
 #[cfg(target_os = "linux")]
-async fn type_text(text: &str) -> Result<()> {
-    Command::new("wtype")
-        .arg(text)
+async fn type_text(text: &str, options: &OutputOptions) -> Result<()> {
+    if options.pastes(text) {
+        copy_to_clipboard(text, None, options).await?;
+        return paste(options).await;
+    }
+    match options.backend {
+        OutputBackend::X11 => return xdotool_type(text, options.newline).await,
+        // Characters outside the US layout are pasted instead
+        OutputBackend::Uinput if !crate::uinput::can_type(text) => {
+            copy_to_clipboard(text, None, options).await?;
+            return paste(options).await;
+        }
+        OutputBackend::Uinput => {
+            let (text, newline) = (text.to_string(), options.newline);
+            return uinput(move || crate::uinput::type_text(&text, newline)).await;
+        }
+        OutputBackend::Auto | OutputBackend::Wayland => {}
+    }
+
+    // wtype runs its arguments in order, so lines and key presses can be interleaved
+    let mut args: Vec<&str> = Vec::new();
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            match options.newline {
+                NewlineMode::ShiftEnter => {
+                    args.extend(["-M", "shift", "-k", "Return", "-m", "shift"])
+                }
+                NewlineMode::Enter | NewlineMode::Paste => args.extend(["-k", "Return"]),
+            }
+        }
+        if !line.is_empty() {
+            args.push(line);
+        }
+    }
+    if args.is_empty() {
+        return Ok(());
+    }
+    command("wtype")
+        .args(&args)
         .status()
         .await
         .context("Failed to type text (is wtype installed?)")?;
     Ok(())
 }
 
+/// `xdotool type` takes the rest of its arguments as text, so each line is a
+/// separate invocation with the line breaks pressed in between.
+#[cfg(target_os = "linux")]
+async fn xdotool_type(text: &str, newline: NewlineMode) -> Result<()> {
+    let line_break = match newline {
+        NewlineMode::ShiftEnter => "shift+Return",
+        NewlineMode::Enter | NewlineMode::Paste => "Return",
+    };
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            xdotool(&["key", "--clearmodifiers", line_break]).await?;
+        }
+        if !line.is_empty() {
+            xdotool(&["type", "--clearmodifiers", "--", line]).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Run a virtual keyboard operation, which sleeps between key events, off the runtime.
+#[cfg(target_os = "linux")]
+async fn uinput(f: impl FnOnce() -> Result<()> + Send + 'static) -> Result<()> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .context("Virtual keyboard task failed")?
+}
+
+#[cfg(target_os = "linux")]
+async fn xdotool(args: &[&str]) -> Result<()> {
+    command("xdotool")
+        .args(args)
+        .status()
+        .await
+        .context("Failed to run xdotool (is it installed?)")?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+async fn press_backspace(count: usize, options: &OutputOptions) -> Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+    match options.backend {
+        OutputBackend::X11 => {
+            let count = count.to_string();
+            return xdotool(&["key", "--clearmodifiers", "--repeat", &count, "BackSpace"]).await;
+        }
+        OutputBackend::Uinput => {
+            return uinput(move || crate::uinput::press_backspace(count)).await;
+        }
+        OutputBackend::Auto | OutputBackend::Wayland => {}
+    }
+    let args: Vec<&str> = std::iter::repeat_n(["-k", "BackSpace"], count)
+        .flatten()
+        .collect();
+    command("wtype")
+        .args(&args)
+        .status()
+        .await
+        .context("Failed to press backspace (is wtype installed?)")?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub async fn press_enter(options: &OutputOptions) -> Result<()> {
+    match options.backend {
+        OutputBackend::X11 => return xdotool(&["key", "--clearmodifiers", "Return"]).await,
+        OutputBackend::Uinput => return uinput(crate::uinput::press_enter).await,
+        OutputBackend::Auto | OutputBackend::Wayland => {}
+    }
+    command("wtype")
+        .args(["-k", "Return"])
+        .status()
+        .await
+        .context("Failed to press Enter (is wtype installed?)")?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+async fn paste(options: &OutputOptions) -> Result<()> {
+    match options.backend {
+        OutputBackend::X11 => return xdotool(&["key", "--clearmodifiers", "ctrl+v"]).await,
+        OutputBackend::Uinput => return uinput(crate::uinput::paste).await,
+        OutputBackend::Auto | OutputBackend::Wayland => {}
+    }
+    command("wtype")
+        .args(["-M", "ctrl", "v", "-m", "ctrl"])
+        .status()
+        .await
+        .context("Failed to paste (is wtype installed?)")?;
+    Ok(())
+}
+
 #[cfg(target_os = "macos")]
-async fn copy_to_clipboard(text: &str) -> Result<()> {
-    let mut child = Command::new("pbcopy")
+pub async fn copy_to_clipboard(
+    text: &str,
+    _metadata: Option<&str>,
+    _options: &OutputOptions,
+) -> Result<()> {
+    let mut child = command("pbcopy")
         .stdin(Stdio::piped())
         .spawn()
         .context("Failed to run pbcopy")?;
@@ -79,11 +490,106 @@ async fn copy_to_clipboard(text: &str) -> Result<()> {
 }
 
 #[cfg(target_os = "linux")]
-async fn copy_to_clipboard(text: &str) -> Result<()> {
-    Command::new("wl-copy")
+pub async fn copy_to_clipboard(
+    text: &str,
+    metadata: Option<&str>,
+    options: &OutputOptions,
+) -> Result<()> {
+    let clipboard = &options.clipboard;
+    let copyq = matches!(clipboard.history, Some(ClipboardHistory::Copyq));
+    if let Some(metadata) = metadata.filter(|_| clipboard.metadata && copyq) {
+        // CopyQ records what it copies in its history itself
+        return copyq_copy(text, metadata).await;
+    }
+    if options.backend.clipboard() == OutputBackend::X11 {
+        xclip_copy(text, clipboard).await?;
+    } else {
+        wl_copy(text, clipboard).await?;
+    }
+    if let Some(history) = clipboard.history {
+        push_to_history(history, text).await?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+async fn wl_copy(text: &str, clipboard: &ClipboardOptions) -> Result<()> {
+    let mut wl_copy = command("wl-copy");
+    // Without an explicit type wl-copy guesses one from the content, and some
+    // clipboard managers skip anything that isn't offered as plain text
+    wl_copy.args(["--type", "text/plain;charset=utf-8"]);
+    if clipboard.paste_once {
+        wl_copy.arg("--paste-once");
+    }
+    wl_copy
+        .arg("--")
         .arg(text)
         .status()
         .await
         .context("Failed to copy to clipboard (is wl-clipboard installed?)")?;
     Ok(())
 }
+
+/// xclip keeps running in the background to serve the selection once its input
+/// is read.
+#[cfg(target_os = "linux")]
+async fn xclip_copy(text: &str, clipboard: &ClipboardOptions) -> Result<()> {
+    let mut xclip = command("xclip");
+    xclip.args(["-selection", "clipboard"]);
+    if clipboard.paste_once {
+        xclip.args(["-loops", "1"]);
+    }
+    let mut child = xclip
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("Failed to run xclip (is it installed?)")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .await
+            .context("Failed to write to xclip")?;
+    }
+    child.wait().await.context("Failed to wait for xclip")?;
+    Ok(())
+}
+
+/// Have CopyQ own the clipboard, offering both the text and its metadata.
+#[cfg(target_os = "linux")]
+async fn copyq_copy(text: &str, metadata: &str) -> Result<()> {
+    let status = command("copyq")
+        .args(["copy", "text/plain", text, "application/json", metadata])
+        .stdout(Stdio::null())
+        .status()
+        .await
+        .context("Failed to run copyq")?;
+    if !status.success() {
+        anyhow::bail!("copyq copy failed ({}), is CopyQ running?", status);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+async fn push_to_history(history: ClipboardHistory, text: &str) -> Result<()> {
+    let args: &[&str] = match history {
+        ClipboardHistory::Cliphist => &["store"],
+        // "-" makes copyq read the item from stdin
+        ClipboardHistory::Copyq => &["add", "-"],
+    };
+    let mut child = command(history.tool())
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", history.tool()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .await
+            .with_context(|| format!("Failed to write to {}", history.tool()))?;
+    }
+    child
+        .wait()
+        .await
+        .with_context(|| format!("Failed to wait for {}", history.tool()))?;
+    Ok(())
+}
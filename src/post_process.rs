@@ -1,10 +1,30 @@
+use crate::error::AppError;
+#[cfg(feature = "post-process")]
+use crate::retry::{self, Backend};
 use anyhow::Result;
+#[cfg(feature = "post-process")]
 use ollama_rs::generation::chat::request::ChatMessageRequest;
+#[cfg(feature = "post-process")]
 use ollama_rs::generation::chat::ChatMessage;
+#[cfg(feature = "post-process")]
 use ollama_rs::generation::parameters::KeepAlive;
+#[cfg(feature = "post-process")]
 use ollama_rs::Ollama;
+#[cfg(feature = "post-process")]
 use std::time::{Duration, Instant};
 
+/// Whether this build includes post-processing.
+pub const AVAILABLE: bool = cfg!(feature = "post-process");
+
+/// Error for using post-processing in a build without it.
+pub fn not_compiled_in() -> AppError {
+    AppError::FeatureMissing {
+        name: "Post-processing",
+        feature: "post-process",
+    }
+}
+
+#[cfg(feature = "post-process")]
 const DEFAULT_PROMPT: &str = "Clean up this voice transcript for use as an AI coding prompt. \
 Remove filler words (um, uh, like, you know) and false starts. \
 Fix grammar and punctuation. If the speaker corrected themselves, keep only the correction. \
@@ -14,11 +34,13 @@ Preserve technical terms and abbreviations exactly as spoken (e.g., API, CLI, as
 Preserve the speaker's wording. Only restructure if the original is genuinely unclear. \
 Output only the cleaned text.";
 
+#[cfg(feature = "post-process")]
 pub struct PostProcessor {
     ollama: Ollama,
     model: String,
 }
 
+#[cfg(feature = "post-process")]
 impl PostProcessor {
     pub fn new(host: &str, port: u16, model: &str) -> Self {
         // Configure client to handle stale connections after long idle periods
@@ -36,48 +58,72 @@ impl PostProcessor {
         }
     }
 
-    pub async fn process(&self, text: &str) -> Result<String> {
+    /// Clean up `text` following `prompt`, or the built-in prompt if none is
+    /// given, writing the result in `language` if one is given.
+    pub async fn process(
+        &self,
+        text: &str,
+        prompt: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<String> {
         let total_start = Instant::now();
         let messages = vec![
-            ChatMessage::system(DEFAULT_PROMPT.to_string()),
+            ChatMessage::system(system_prompt(prompt.unwrap_or(DEFAULT_PROMPT), language)),
             ChatMessage::user(text.to_string()),
         ];
 
-        // Retry logic for stale connections after long idle periods (days)
-        let mut last_error = None;
-        for attempt in 0..3 {
-            if attempt > 0 {
-                log::info!("Retrying Ollama request (attempt {})", attempt + 1);
-                tokio::time::sleep(Duration::from_secs(1)).await;
-            }
+        // Retries mostly catch stale connections after long idle periods (days)
+        let response = retry::run(
+            Backend::Ollama,
+            "Ollama request",
+            |_| true,
+            || {
+                let request = ChatMessageRequest::new(self.model.clone(), messages.clone())
+                    .think(false)
+                    .keep_alive(KeepAlive::Indefinitely);
+                log::debug!("Sending request to Ollama");
+                self.ollama.send_chat_messages(request)
+            },
+        )
+        .await?;
+        log::debug!(
+            "Ollama request succeeded (total {:.2}s)",
+            total_start.elapsed().as_secs_f32()
+        );
+        Ok(response.message.content.trim().to_string())
+    }
+}
+
+/// `prompt`, plus the instruction to answer in `language` whatever language
+/// was spoken.
+#[cfg(feature = "post-process")]
+fn system_prompt(prompt: &str, language: Option<&str>) -> String {
+    match language {
+        Some(language) => format!(
+            "{} Always write the output in {}, translating it if the transcript is in \
+another language.",
+            prompt, language
+        ),
+        None => prompt.to_string(),
+    }
+}
 
-            let request = ChatMessageRequest::new(self.model.clone(), messages.clone())
-                .think(false)
-                .keep_alive(KeepAlive::Indefinitely);
+/// Stand-in for builds without the `post-process` feature.
+#[cfg(not(feature = "post-process"))]
+pub struct PostProcessor;
 
-            log::debug!("Sending request to Ollama (attempt {})", attempt + 1);
-            let request_start = Instant::now();
-            match self.ollama.send_chat_messages(request).await {
-                Ok(response) => {
-                    log::debug!(
-                        "Ollama request succeeded in {:.2}s (total {:.2}s)",
-                        request_start.elapsed().as_secs_f32(),
-                        total_start.elapsed().as_secs_f32()
-                    );
-                    return Ok(response.message.content.trim().to_string());
-                }
-                Err(e) => {
-                    log::warn!(
-                        "Ollama request failed (attempt {}) after {:.2}s: {}",
-                        attempt + 1,
-                        request_start.elapsed().as_secs_f32(),
-                        e
-                    );
-                    last_error = Some(e);
-                }
-            }
-        }
+#[cfg(not(feature = "post-process"))]
+impl PostProcessor {
+    pub fn new(_host: &str, _port: u16, _model: &str) -> Self {
+        Self
+    }
 
-        Err(last_error.unwrap().into())
+    pub async fn process(
+        &self,
+        _text: &str,
+        _prompt: Option<&str>,
+        _language: Option<&str>,
+    ) -> Result<String> {
+        Err(not_compiled_in().into())
     }
 }
@@ -0,0 +1,113 @@
+//! Per-microphone calibration, measured by `calibrate` and applied whenever
+//! the same device is recorded from again.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Speech is scaled towards this RMS level (about -20 dBFS)
+const TARGET_RMS: f32 = 0.1;
+const MIN_GAIN: f32 = 0.5;
+const MAX_GAIN: f32 = 20.0;
+/// A single channel is only preferred over the mix when it is this much louder
+/// than the quietest one, e.g. a headset mic on the left channel of a stereo jack
+const CHANNEL_PREFERENCE_RATIO: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DeviceProfile {
+    /// Applied to every sample
+    pub gain: f32,
+    /// RMS level of the room with nobody speaking, after gain
+    pub noise_floor: f32,
+    /// Record only this channel instead of averaging all of them
+    pub channel: Option<usize>,
+}
+
+impl DeviceProfile {
+    /// Derive a profile from per-channel RMS levels measured during silence and
+    /// during speech.
+    pub fn from_levels(quiet: &[f32], speech: &[f32]) -> Self {
+        let loudest = (0..speech.len())
+            .max_by(|&a, &b| speech[a].total_cmp(&speech[b]))
+            .unwrap_or(0);
+        let quietest = speech.iter().copied().fold(f32::INFINITY, f32::min);
+        let channel = (speech.len() > 1 && speech[loudest] >= quietest * CHANNEL_PREFERENCE_RATIO)
+            .then_some(loudest);
+
+        let mean = |levels: &[f32]| levels.iter().sum::<f32>() / levels.len().max(1) as f32;
+        let (quiet_level, speech_level) = match channel {
+            Some(channel) => (quiet[channel], speech[channel]),
+            None => (mean(quiet), mean(speech)),
+        };
+        let gain = (TARGET_RMS / speech_level.max(f32::EPSILON)).clamp(MIN_GAIN, MAX_GAIN);
+        Self {
+            gain,
+            noise_floor: quiet_level * gain,
+            channel,
+        }
+    }
+}
+
+pub fn profiles_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("parakeet-writer")
+        .join("devices.json")
+}
+
+fn load_all() -> Result<BTreeMap<String, DeviceProfile>> {
+    let path = profiles_path();
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let json =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    serde_json::from_str(&json).with_context(|| format!("Failed to parse {:?}", path))
+}
+
+/// The stored profile for the device called `name`, if it has been calibrated.
+pub fn get(name: &str) -> Option<DeviceProfile> {
+    match load_all() {
+        Ok(profiles) => profiles.get(name).copied(),
+        Err(e) => {
+            log::warn!("Ignoring device profiles: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Store `profile` for the device called `name`, replacing any earlier one.
+pub fn save(name: &str, profile: DeviceProfile) -> Result<()> {
+    let mut profiles = load_all()?;
+    profiles.insert(name.to_string(), profile);
+    let path = profiles_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create config directory")?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(&profiles)?)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quiet_mic_gets_gain() {
+        let profile = DeviceProfile::from_levels(&[0.001], &[0.02]);
+        assert!((profile.gain - 5.0).abs() < 1e-4);
+        assert!((profile.noise_floor - 0.005).abs() < 1e-6);
+        assert_eq!(profile.channel, None);
+    }
+
+    #[test]
+    fn test_prefers_the_channel_with_the_mic() {
+        let profile = DeviceProfile::from_levels(&[0.001, 0.001], &[0.1, 0.005]);
+        assert_eq!(profile.channel, Some(0));
+        assert_eq!(profile.gain, 1.0);
+
+        let balanced = DeviceProfile::from_levels(&[0.001, 0.001], &[0.1, 0.08]);
+        assert_eq!(balanced.channel, None);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::audio;
+use crate::engine::EngineKind;
+use crate::model;
+use crate::report::{self, progress};
+use anyhow::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Serialize)]
+struct RecoverReport {
+    dir: PathBuf,
+    recordings: Vec<Recovered>,
+}
+
+#[derive(Serialize)]
+struct Recovered {
+    path: PathBuf,
+    duration_secs: Option<f64>,
+    text: Option<String>,
+    error: Option<String>,
+}
+
+/// Transcribe recordings that a crashed session left in the spill directory.
+pub async fn run(
+    kind: EngineKind,
+    model_path: Option<PathBuf>,
+    keep: bool,
+    verbose_load: bool,
+) -> Result<()> {
+    let mut report = RecoverReport {
+        dir: audio::spill_dir(),
+        recordings: Vec::new(),
+    };
+    let recordings = audio::orphaned_recordings()?;
+    if recordings.is_empty() {
+        if report::json() {
+            report::emit(&report)?;
+        } else {
+            println!("No recordings to recover in {:?}", report.dir);
+        }
+        return Ok(());
+    }
+    progress!("Found {} recording(s) to recover", recordings.len());
+
+    let model_path = model::ensure_model(kind, model_path).await?;
+    let mut engine = model::load_engine(kind, &model_path, verbose_load)?;
+
+    for path in recordings {
+        let mut recovered = Recovered {
+            path: path.clone(),
+            duration_secs: None,
+            text: None,
+            error: None,
+        };
+        match audio::read_recording(&path) {
+            Ok(samples) => {
+                let duration = samples.len() as f64 / 16000.0;
+                recovered.duration_secs = Some(duration);
+                if !report::json() {
+                    println!("\n== {:?} ({:.1}s) ==", path, duration);
+                }
+                match engine.transcribe_samples(samples) {
+                    Ok(transcription) => {
+                        let text = transcription.text.trim().to_string();
+                        if !report::json() {
+                            println!("{}", text);
+                        }
+                        recovered.text = Some(text);
+                        if !keep {
+                            std::fs::remove_file(&path).ok();
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Transcription of {:?} failed: {}", path, e);
+                        recovered.error = Some(e.to_string());
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to read {:?}: {}", path, e);
+                recovered.error = Some(e.to_string());
+            }
+        }
+        report.recordings.push(recovered);
+    }
+
+    engine.unload_model();
+    if report::json() {
+        report::emit(&report)?;
+    }
+    let failed = report
+        .recordings
+        .iter()
+        .filter(|r| r.error.is_some())
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{} recording(s) could not be recovered", failed);
+    }
+    Ok(())
+}
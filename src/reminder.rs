@@ -0,0 +1,407 @@
+//! `--reminders`: dictations like "Remind me Friday at 3pm to call Anna" become
+//! calendar events or reminders instead of being typed. The date and time are
+//! read with a few simple rules (see [`parse`]), not a language model.
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use serde::Deserialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Time of day for reminders that only name a day
+const DEFAULT_HOUR: u32 = 9;
+/// Length of calendar events
+const EVENT_MINUTES: i64 = 15;
+
+/// Where reminders go.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ReminderTarget {
+    /// Events with an alarm, added to an iCalendar file
+    Ics(PathBuf),
+    /// Events imported into khal's default calendar
+    Khal,
+    /// `REM` lines appended to a Remind file
+    Remind(PathBuf),
+}
+
+impl FromStr for ReminderTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("expected an .ics or .rem path, `khal` or `remind`".to_string()),
+            "khal" => Ok(ReminderTarget::Khal),
+            "remind" => {
+                let home = dirs::home_dir().ok_or("no home directory for ~/.reminders")?;
+                Ok(ReminderTarget::Remind(home.join(".reminders")))
+            }
+            path if path.ends_with(".rem") => Ok(ReminderTarget::Remind(PathBuf::from(path))),
+            path => Ok(ReminderTarget::Ics(PathBuf::from(path))),
+        }
+    }
+}
+
+impl TryFrom<String> for ReminderTarget {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Reminder {
+    /// Local time
+    pub when: NaiveDateTime,
+    pub what: String,
+}
+
+impl ReminderTarget {
+    pub async fn add(&self, reminder: &Reminder) -> Result<()> {
+        match self {
+            ReminderTarget::Ics(path) => {
+                let calendar = match std::fs::read_to_string(path) {
+                    Ok(calendar) => calendar,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                    Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+                };
+                std::fs::write(path, add_event(&calendar, &event(reminder)))
+                    .with_context(|| format!("Failed to write {:?}", path))?;
+            }
+            ReminderTarget::Khal => {
+                let mut file = tempfile::Builder::new().suffix(".ics").tempfile()?;
+                file.write_all(add_event("", &event(reminder)).as_bytes())?;
+                let output = tokio::process::Command::new("khal")
+                    .args(["import", "--batch"])
+                    .arg(file.path())
+                    .output()
+                    .await
+                    .context("Failed to run `khal` (is it installed?)")?;
+                if !output.status.success() {
+                    anyhow::bail!(
+                        "khal import failed: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                }
+            }
+            ReminderTarget::Remind(path) => {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open {:?}", path))?;
+                writeln!(
+                    file,
+                    "REM {} AT {} MSG {}",
+                    reminder.when.format("%-d %b %Y"),
+                    reminder.when.format("%H:%M"),
+                    // % starts a substitution in Remind messages
+                    reminder.what.replace('%', "%%")
+                )
+                .with_context(|| format!("Failed to write to {:?}", path))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The reminder in a transcript like "Remind me Friday at 3pm to call Anna" or
+/// "Remind me to call Anna tomorrow morning", relative to `now`. `None` when
+/// the transcript isn't one or its time can't be read.
+///
+/// Times can be given as a day (today, tomorrow, tonight, a weekday), a time
+/// of day (3pm, 3:30 p.m., 15:00, noon, morning, afternoon, evening) or both,
+/// or as "in" a number of minutes, hours, days or weeks. A bare hour from 1 to
+/// 7 is taken as afternoon unless "morning" says otherwise.
+pub fn parse(transcript: &str, now: NaiveDateTime) -> Option<Reminder> {
+    let text = transcript.trim().trim_end_matches(['.', '!']).trim_end();
+    let rest = strip_prefix_ignore_case(text, "remind me")?;
+    if !rest.starts_with([' ', ',']) {
+        return None;
+    }
+    let words: Vec<&str> = rest
+        .trim_start_matches([' ', ','])
+        .split_whitespace()
+        .collect();
+    let to = words
+        .iter()
+        .position(|word| word.eq_ignore_ascii_case("to"))?;
+    let (when, what) = if to > 0 {
+        (parse_when(&words[..to], now)?, words[to + 1..].join(" "))
+    } else {
+        // The time is the longest tail that reads as one
+        let task = &words[1..];
+        (1..task.len()).find_map(|i| Some((parse_when(&task[i..], now)?, task[..i].join(" "))))?
+    };
+    let what = what.trim_matches([',', ' ']);
+    if what.is_empty() {
+        return None;
+    }
+    Some(Reminder {
+        when,
+        what: what.to_string(),
+    })
+}
+
+fn parse_when(words: &[&str], now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let words: Vec<String> = words
+        .iter()
+        .map(|word| word.trim_matches(',').replace('.', "").to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut date = None;
+    // A spoken time, with whether it was am (false) or pm (true) if that was said
+    let mut clock: Option<(u32, u32, Option<bool>)> = None;
+    // Part of the day, with its default time and whether it is pm
+    let mut part: Option<(u32, bool)> = None;
+    let mut offset = None;
+    let mut i = 0;
+    while i < words.len() {
+        match words[i].as_str() {
+            "on" | "at" | "this" | "next" | "the" | "o'clock" => {}
+            "today" => date = Some(now.date()),
+            "tomorrow" => date = Some(now.date() + Duration::days(1)),
+            "tonight" => {
+                date = Some(now.date());
+                part = Some((20, true));
+            }
+            "noon" | "midday" => clock = Some((12, 0, Some(true))),
+            "morning" => part = Some((DEFAULT_HOUR, false)),
+            "afternoon" => part = Some((15, true)),
+            "evening" => part = Some((18, true)),
+            "in" if words.get(i + 1).is_some_and(|word| word == "the") => {}
+            "in" => {
+                let n = number(words.get(i + 1)?)?;
+                offset = Some(match words.get(i + 2)?.trim_end_matches('s') {
+                    "minute" => Duration::minutes(n),
+                    "hour" => Duration::hours(n),
+                    "day" => Duration::days(n),
+                    "week" => Duration::weeks(n),
+                    _ => return None,
+                });
+                i += 2;
+            }
+            word => {
+                if let Some(weekday) = weekday(word) {
+                    date = Some(next_weekday(now.date(), weekday));
+                } else {
+                    let (hour, minute, pm, consumed) = parse_time(word, words.get(i + 1))?;
+                    clock = Some((hour, minute, pm));
+                    if consumed {
+                        i += 1;
+                    }
+                }
+            }
+        }
+        i += 1;
+    }
+    if let Some(offset) = offset {
+        return (date.is_none() && clock.is_none() && part.is_none()).then(|| now + offset);
+    }
+    let time = match (clock, part) {
+        (Some((hour, minute, pm)), part) => {
+            // Without am or pm, go by the part of the day, or take 1 to 7 as afternoon
+            let pm = pm
+                .or(part.map(|(_, pm)| pm))
+                .unwrap_or((1..=7).contains(&hour));
+            let hour = if pm && hour < 12 { hour + 12 } else { hour };
+            Some(hm(hour, minute)?)
+        }
+        (None, Some((hour, _))) => Some(hm(hour, 0)?),
+        (None, None) => None,
+    };
+    match (date, time) {
+        (None, None) => None,
+        (Some(date), time) => Some(date.and_time(time.or(hm(DEFAULT_HOUR, 0))?)),
+        (None, Some(time)) => {
+            let today = now.date().and_time(time);
+            Some(if today > now {
+                today
+            } else {
+                today + Duration::days(1)
+            })
+        }
+    }
+}
+
+/// A time like "3pm", "3:30", "15:00" or "3" followed by "pm", as hour,
+/// minute, whether it was said to be pm, and whether it used the next word.
+fn parse_time(word: &str, next: Option<&String>) -> Option<(u32, u32, Option<bool>, bool)> {
+    let (digits, pm, consumed) = if let Some(digits) = word.strip_suffix("pm") {
+        (digits, Some(true), false)
+    } else if let Some(digits) = word.strip_suffix("am") {
+        (digits, Some(false), false)
+    } else {
+        match next.map(String::as_str) {
+            Some("pm") => (word, Some(true), true),
+            Some("am") => (word, Some(false), true),
+            _ => (word, None, false),
+        }
+    };
+    let (hour, minute) = match digits.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        None => (digits.parse::<u32>().ok()?, 0),
+    };
+    let hour = match pm {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some(_) => hour % 12,
+        None => hour,
+    };
+    hm(hour, minute)?;
+    Some((hour, minute, pm, consumed))
+}
+
+fn number(word: &str) -> Option<i64> {
+    const WORDS: [&str; 12] = [
+        "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven",
+        "twelve",
+    ];
+    match word {
+        "a" | "an" => Some(1),
+        "fifteen" => Some(15),
+        "twenty" => Some(20),
+        "thirty" => Some(30),
+        _ => word
+            .parse()
+            .ok()
+            .or_else(|| WORDS.iter().position(|&w| w == word).map(|i| i as i64 + 1)),
+    }
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    Some(match word {
+        "monday" => Weekday::Mon,
+        "tuesday" => Weekday::Tue,
+        "wednesday" => Weekday::Wed,
+        "thursday" => Weekday::Thu,
+        "friday" => Weekday::Fri,
+        "saturday" => Weekday::Sat,
+        "sunday" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// The next `weekday` after `today`, a week ahead if today is one.
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    today + Duration::days(if days == 0 { 7 } else { days as i64 })
+}
+
+fn hm(hour: u32, minute: u32) -> Option<NaiveTime> {
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &text[prefix.len()..])
+}
+
+/// A VEVENT with an alarm at its start. Times are floating, i.e. local to
+/// whoever reads the calendar.
+fn event(reminder: &Reminder) -> String {
+    const LOCAL: &str = "%Y%m%dT%H%M%S";
+    let now = Utc::now();
+    let summary = escape(&reminder.what);
+    [
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}@parakeet-writer", now.format("%Y%m%dT%H%M%S%.9f")),
+        format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")),
+        format!("DTSTART:{}", reminder.when.format(LOCAL)),
+        format!(
+            "DTEND:{}",
+            (reminder.when + Duration::minutes(EVENT_MINUTES)).format(LOCAL)
+        ),
+        format!("SUMMARY:{}", summary),
+        "BEGIN:VALARM".to_string(),
+        "ACTION:DISPLAY".to_string(),
+        format!("DESCRIPTION:{}", summary),
+        "TRIGGER:PT0M".to_string(),
+        "END:VALARM".to_string(),
+        "END:VEVENT".to_string(),
+    ]
+    .iter()
+    .map(|line| format!("{}\r\n", line))
+    .collect()
+}
+
+/// Add `event` to the end of `calendar`, or to a new calendar when it is empty.
+fn add_event(calendar: &str, event: &str) -> String {
+    const END: &str = "END:VCALENDAR";
+    match calendar.rfind(END) {
+        Some(end) => format!("{}{}{}", &calendar[..end], event, &calendar[end..]),
+        None => format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//parakeet-writer//EN\r\n{}{}\r\n",
+            event, END
+        ),
+    }
+}
+
+/// Escape iCalendar TEXT.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_reminder() {
+        // A Wednesday
+        let now = at("2024-03-13", "10:00");
+        let parse = |text| parse(text, now).map(|r| (r.when, r.what));
+        assert_eq!(
+            parse("Remind me Friday 3pm to call Anna."),
+            Some((at("2024-03-15", "15:00"), "call Anna".to_string()))
+        );
+        assert_eq!(
+            parse("Remind me to water the plants tomorrow morning."),
+            Some((at("2024-03-14", "09:00"), "water the plants".to_string()))
+        );
+        assert_eq!(
+            parse("Remind me in 20 minutes to check the oven"),
+            Some((at("2024-03-13", "10:20"), "check the oven".to_string()))
+        );
+        assert_eq!(
+            parse("Remind me at 8 p.m. to go to the gym"),
+            Some((at("2024-03-13", "20:00"), "go to the gym".to_string()))
+        );
+        // Times already past today are tomorrow's
+        assert_eq!(
+            parse("Remind me at 9:30 to stand up"),
+            Some((at("2024-03-14", "09:30"), "stand up".to_string()))
+        );
+        assert_eq!(
+            parse("Remind me tomorrow at 7 in the morning to go running"),
+            Some((at("2024-03-14", "07:00"), "go running".to_string()))
+        );
+        assert_eq!(
+            parse("remind me, on Wednesday at 4, to send the report"),
+            Some((at("2024-03-20", "16:00"), "send the report".to_string()))
+        );
+        assert_eq!(parse("Remind me to call Anna."), None);
+        assert_eq!(parse("Remind me how this works."), None);
+        assert_eq!(parse("Call Anna on Friday."), None);
+    }
+
+    #[test]
+    fn test_add_event() {
+        let event = "BEGIN:VEVENT\r\nEND:VEVENT\r\n";
+        let calendar = add_event("", event);
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        let twice = add_event(&calendar, event);
+        assert_eq!(twice.matches("BEGIN:VEVENT").count(), 2);
+        assert_eq!(twice.matches("END:VCALENDAR").count(), 1);
+    }
+}
@@ -0,0 +1,52 @@
+//! Output for subcommands: human-readable text by default, or a single JSON
+//! document on stdout with `--json`. Progress messages move to stderr in JSON
+//! mode so stdout stays parseable.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(enabled: bool) {
+    JSON.store(enabled, Ordering::Relaxed);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Print a progress message: stdout normally, stderr with `--json`.
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::report::json() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use progress;
+
+/// Print a subcommand's result as JSON.
+pub fn emit<T: Serialize>(report: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(report)?);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ErrorReport {
+    error: String,
+    exit_code: u8,
+}
+
+/// Print a failed command's error to stderr as JSON.
+pub fn emit_error(err: &anyhow::Error, exit_code: u8) {
+    let report = ErrorReport {
+        error: format!("{:#}", err),
+        exit_code,
+    };
+    if let Ok(json) = serde_json::to_string(&report) {
+        eprintln!("{}", json);
+    }
+}
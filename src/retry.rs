@@ -0,0 +1,263 @@
+//! `--retry`: how network calls are retried when they fail: how many
+//! attempts, how long to wait between them (doubling each time, up to a
+//! limit), and how much to randomize the wait. Each backend has its own
+//! policy, which `--retry` can change for all of them or for one.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// The longest `backoff` or `max-backoff` accepted, in seconds
+const MAX_BACKOFF_SECS: f64 = 3600.0;
+
+/// What a network call talks to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Model, VAD model and media downloads
+    Download,
+    /// Ollama, for post-processing
+    Ollama,
+}
+
+impl Backend {
+    const ALL: [Backend; 2] = [Backend::Download, Backend::Ollama];
+
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Download => "download",
+            Backend::Ollama => "ollama",
+        }
+    }
+
+    fn default_policy(self) -> Policy {
+        match self {
+            // Large files over flaky connections; each attempt resumes the last
+            Backend::Download => Policy {
+                attempts: 5,
+                backoff: 2.0,
+                max_backoff: 60.0,
+                jitter: 0.2,
+            },
+            // Mostly stale connections after long idle periods, which a
+            // quick retry fixes; the user is waiting for their text
+            Backend::Ollama => Policy {
+                attempts: 3,
+                backoff: 1.0,
+                max_backoff: 5.0,
+                jitter: 0.2,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Policy {
+    /// Attempts in total, including the first
+    attempts: u32,
+    /// Seconds to wait before the first retry, doubled for each one after
+    backoff: f64,
+    /// The most seconds to wait between attempts
+    max_backoff: f64,
+    /// Fraction of the wait to randomly add or take off
+    jitter: f64,
+}
+
+impl Policy {
+    /// How long to wait before retry number `retry` (1 for the first), with
+    /// `random` in 0..1 picking where in the jitter range it falls.
+    fn delay(&self, retry: u32, random: f64) -> Duration {
+        let backoff = (self.backoff * 2f64.powi(retry as i32 - 1)).min(self.max_backoff);
+        Duration::from_secs_f64(backoff * (1.0 + self.jitter * (2.0 * random - 1.0)))
+    }
+}
+
+/// One `--retry` value: `[BACKEND:]KEY=VALUE,...`, changing the policy of
+/// one backend, or of all of them without a backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryOverride {
+    backend: Option<Backend>,
+    attempts: Option<u32>,
+    backoff: Option<f64>,
+    max_backoff: Option<f64>,
+    jitter: Option<f64>,
+}
+
+impl RetryOverride {
+    fn apply(&self, policy: &mut Policy) {
+        policy.attempts = self.attempts.unwrap_or(policy.attempts);
+        policy.backoff = self.backoff.unwrap_or(policy.backoff);
+        policy.max_backoff = self.max_backoff.unwrap_or(policy.max_backoff);
+        policy.jitter = self.jitter.unwrap_or(policy.jitter);
+    }
+}
+
+impl FromStr for RetryOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (backend, settings) = match s.split_once(':') {
+            Some((name, settings)) => {
+                let backend = Backend::ALL
+                    .into_iter()
+                    .find(|backend| backend.name() == name)
+                    .ok_or_else(|| {
+                        format!("unknown backend {:?} (expected download or ollama)", name)
+                    })?;
+                (Some(backend), settings)
+            }
+            None => (None, s),
+        };
+        let mut retry = RetryOverride {
+            backend,
+            attempts: None,
+            backoff: None,
+            max_backoff: None,
+            jitter: None,
+        };
+        for setting in settings.split(',') {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, got {:?}", setting))?;
+            let seconds = || match value.parse::<f64>() {
+                Ok(secs) if (0.0..=MAX_BACKOFF_SECS).contains(&secs) => Ok(secs),
+                _ => Err(format!(
+                    "{} must be a number of seconds up to {}",
+                    key, MAX_BACKOFF_SECS
+                )),
+            };
+            match key.trim() {
+                "attempts" => {
+                    retry.attempts = match value.parse() {
+                        Ok(attempts) if attempts >= 1 => Some(attempts),
+                        _ => return Err("attempts must be at least 1".to_string()),
+                    }
+                }
+                "backoff" => retry.backoff = Some(seconds()?),
+                "max-backoff" => retry.max_backoff = Some(seconds()?),
+                "jitter" => {
+                    retry.jitter = match value.parse() {
+                        Ok(jitter) if (0.0..=1.0).contains(&jitter) => Some(jitter),
+                        _ => return Err("jitter must be between 0 and 1".to_string()),
+                    }
+                }
+                key => {
+                    return Err(format!(
+                        "unknown setting {:?} (expected attempts, backoff, max-backoff or jitter)",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(retry)
+    }
+}
+
+static POLICIES: OnceLock<Vec<(Backend, Policy)>> = OnceLock::new();
+
+/// Apply the `--retry` overrides: the ones for all backends first, then the
+/// ones for a single backend.
+pub fn configure(overrides: &[RetryOverride]) {
+    POLICIES.set(policies(overrides)).ok();
+}
+
+fn policies(overrides: &[RetryOverride]) -> Vec<(Backend, Policy)> {
+    Backend::ALL
+        .into_iter()
+        .map(|backend| {
+            let mut policy = backend.default_policy();
+            let general = overrides.iter().filter(|o| o.backend.is_none());
+            let specific = overrides.iter().filter(|o| o.backend == Some(backend));
+            for retry in general.chain(specific) {
+                retry.apply(&mut policy);
+            }
+            (backend, policy)
+        })
+        .collect()
+}
+
+fn policy(backend: Backend) -> Policy {
+    POLICIES
+        .get()
+        .and_then(|policies| policies.iter().find(|(b, _)| *b == backend))
+        .map(|(_, policy)| *policy)
+        .unwrap_or_else(|| backend.default_policy())
+}
+
+/// Run `attempt` until it succeeds, fails with an error `retryable` rejects,
+/// or runs out of attempts under `backend`'s policy. `what` names the call
+/// in log messages.
+pub async fn run<T, E, F, Fut>(
+    backend: Backend,
+    what: &str,
+    retryable: impl Fn(&E) -> bool,
+    mut attempt: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let policy = policy(backend);
+    let mut retry = 0;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(e) if retry + 1 < policy.attempts && retryable(&e) => {
+                retry += 1;
+                let delay = policy.delay(retry, fastrand::f64());
+                log::warn!(
+                    "{} failed (attempt {} of {}), retrying in {:.1}s: {:#}",
+                    what,
+                    retry,
+                    policy.attempts,
+                    delay.as_secs_f32(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_doubles_up_to_max() {
+        let policy = Policy {
+            attempts: 5,
+            backoff: 1.0,
+            max_backoff: 5.0,
+            jitter: 0.5,
+        };
+        assert_eq!(policy.delay(1, 0.5), Duration::from_secs(1));
+        assert_eq!(policy.delay(3, 0.5), Duration::from_secs(4));
+        assert_eq!(policy.delay(4, 0.5), Duration::from_secs(5));
+        assert_eq!(policy.delay(2, 0.0), Duration::from_secs(1));
+        assert_eq!(policy.delay(2, 1.0), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let overrides: Vec<RetryOverride> = ["ollama:attempts=1", "attempts=4,backoff=0.5"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let policies = policies(&overrides);
+        let (_, download) = policies[0];
+        assert_eq!((download.attempts, download.backoff), (4, 0.5));
+        // The backend's own override wins, whatever the order on the command line
+        let (_, ollama) = policies[1];
+        assert_eq!((ollama.attempts, ollama.backoff), (1, 0.5));
+
+        assert!("cloud:attempts=2".parse::<RetryOverride>().is_err());
+        assert!("attempts=0".parse::<RetryOverride>().is_err());
+        assert!("jitter=2".parse::<RetryOverride>().is_err());
+        assert!("max-backoff=1e300".parse::<RetryOverride>().is_err());
+        assert!("tries=2".parse::<RetryOverride>().is_err());
+    }
+}
@@ -0,0 +1,79 @@
+//! `--status-file`: the loop's state as a small JSON object, rewritten
+//! atomically on every change, for status bars. The fields `text`, `alt`,
+//! `class` and `tooltip` are what a Waybar custom module with
+//! `"return-type": "json"` expects.
+
+use crate::feedback::TranscriptInfo;
+use crate::title::State;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Serialize)]
+struct Status {
+    state: &'static str,
+    text: &'static str,
+    alt: &'static str,
+    class: &'static str,
+    tooltip: String,
+    /// Unix time of the last transcription output
+    last_transcription: Option<u64>,
+}
+
+pub struct StatusFile {
+    path: PathBuf,
+    state: State,
+    last_transcription: Option<u64>,
+}
+
+impl StatusFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            state: State::Idle,
+            last_transcription: None,
+        }
+    }
+
+    pub fn set(&mut self, state: State) {
+        self.state = state;
+        self.update();
+    }
+
+    /// Record that a transcription was just output.
+    pub fn transcribed(&mut self) {
+        self.last_transcription = Some(TranscriptInfo::now());
+        self.update();
+    }
+
+    fn update(&self) {
+        if let Err(e) = self.write() {
+            log::warn!("Failed to write status file: {:#}", e);
+        }
+    }
+
+    /// Write to a temporary file next to the status file and move it into
+    /// place, so readers never see it half written.
+    fn write(&self) -> Result<()> {
+        let name = self.state.name();
+        let status = Status {
+            state: name,
+            text: name,
+            alt: name,
+            class: name,
+            tooltip: format!("parakeet-writer: {}", name),
+            last_transcription: self.last_transcription,
+        };
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => std::path::Path::new("."),
+        };
+        let mut file = tempfile::NamedTempFile::new_in(dir)
+            .with_context(|| format!("Failed to create a temporary file in {:?}", dir))?;
+        writeln!(file, "{}", serde_json::to_string(&status)?)?;
+        file.persist(&self.path)
+            .with_context(|| format!("Failed to replace {:?}", self.path))?;
+        Ok(())
+    }
+}
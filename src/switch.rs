@@ -0,0 +1,91 @@
+use crate::error::AppError;
+use crate::input::HotkeyEvent;
+use anyhow::Result;
+use std::sync::mpsc::Sender;
+
+/// Use a single-switch assistive device (a USB switch interface or big button
+/// that presents itself as a keyboard or mouse) as the main hotkey. Any key or
+/// button on it counts, so it doesn't matter which one the switch sends.
+/// `selector` is the device's path, e.g. under `/dev/input/by-id/`, or part of
+/// its name.
+#[cfg(target_os = "linux")]
+pub fn spawn(selector: &str, tx: Sender<HotkeyEvent>) -> Result<()> {
+    use std::time::Duration;
+
+    const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+    let mut found = find(selector);
+    if found.is_none() {
+        return Err(AppError::InputDeviceNotFound(format!("--switch {:?}", selector)).into());
+    }
+    let selector = selector.to_string();
+    std::thread::spawn(move || loop {
+        if let Some((path, device)) = found.take() {
+            log::info!(
+                "Using switch {:?} ({})",
+                path,
+                device.name().unwrap_or("unknown")
+            );
+            if listen(device, &tx).is_err() {
+                return;
+            }
+            log::info!("Switch {:?} disconnected", path);
+        }
+        std::thread::sleep(RESCAN_INTERVAL);
+        found = find(&selector);
+    });
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn spawn(_selector: &str, _tx: Sender<HotkeyEvent>) -> Result<()> {
+    anyhow::bail!("Switch devices are only supported on Linux")
+}
+
+#[cfg(target_os = "linux")]
+fn find(selector: &str) -> Option<(std::path::PathBuf, evdev::Device)> {
+    // Symlinks like /dev/input/by-id/... point at the event device
+    let target = std::fs::canonicalize(selector).ok();
+    let name = selector.to_lowercase();
+    evdev::enumerate().find(|(path, device)| {
+        target.as_ref() == Some(path)
+            || device
+                .name()
+                .is_some_and(|device_name| device_name.to_lowercase().contains(&name))
+    })
+}
+
+/// Forward the switch's presses and releases as the main hotkey's until it
+/// disconnects. Fails once the receiver is gone.
+#[cfg(target_os = "linux")]
+fn listen(
+    mut device: evdev::Device,
+    tx: &Sender<HotkeyEvent>,
+) -> Result<(), std::sync::mpsc::SendError<HotkeyEvent>> {
+    // Keep the key the switch sends out of the focused window
+    if let Err(e) = device.grab() {
+        log::warn!("Failed to grab switch device: {}", e);
+    }
+
+    loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) => {
+                log::debug!("Switch read error: {}", e);
+                return Ok(());
+            }
+        };
+        for event in events {
+            if !matches!(event.kind(), evdev::InputEventKind::Key(_)) {
+                continue;
+            }
+            // 0 is release, 1 press and 2 autorepeat
+            let hotkey_event = match event.value() {
+                0 => HotkeyEvent::Released(0),
+                1 => HotkeyEvent::Pressed(0),
+                _ => continue,
+            };
+            tx.send(hotkey_event)?;
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! `--terminal-title`: show the recording state in the terminal's window or tab
+//! title, and in the pane title under tmux, so it can be seen while the
+//! terminal is in the background.
+
+use crate::i18n::{tr, Msg};
+use std::io::{IsTerminal, Write};
+
+const APP_NAME: &str = "parakeet-writer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Idle,
+    Recording,
+    Transcribing,
+    PostProcessing,
+    /// Waiting for the model to load again after `--unload-after`
+    LoadingModel,
+}
+
+impl State {
+    /// Name for machine-readable output, e.g. `--status-file`.
+    pub fn name(self) -> &'static str {
+        match self {
+            State::Idle => "idle",
+            State::Recording => "recording",
+            State::Transcribing => "transcribing",
+            State::PostProcessing => "post-processing",
+            State::LoadingModel => "loading-model",
+        }
+    }
+}
+
+/// Remember the current title so [`restore`] can put it back (xterm-compatible
+/// terminals; tmux keeps its own pane title).
+pub fn save() {
+    write("\x1b[22;0t");
+}
+
+pub fn restore() {
+    write("\x1b[23;0t");
+}
+
+pub fn set(state: State) {
+    let title = match state {
+        State::Idle => APP_NAME.to_string(),
+        State::Recording => format!("{} – {}", tr(Msg::TitleRecording), APP_NAME),
+        State::Transcribing => format!("{} – {}", tr(Msg::TitleTranscribing), APP_NAME),
+        State::PostProcessing => format!("{} – {}", tr(Msg::TitlePostProcessing), APP_NAME),
+        State::LoadingModel => format!("{} – {}", tr(Msg::TitleLoadingModel), APP_NAME),
+    };
+    write(&set_title_sequence(&title));
+}
+
+/// OSC 2, which sets the window title in terminals and the pane title in tmux.
+fn set_title_sequence(title: &str) -> String {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1b]2;{}\x07", title)
+}
+
+/// Escape sequences only go to a terminal, never into redirected output.
+fn write(sequence: &str) {
+    let mut stdout = std::io::stdout();
+    if stdout.is_terminal() {
+        let _ = stdout.write_all(sequence.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_title_sequence() {
+        assert_eq!(set_title_sequence("● REC"), "\x1b]2;● REC\x07");
+        // A stray BEL or ESC would end the sequence early
+        assert_eq!(set_title_sequence("a\x07b\x1bc"), "\x1b]2;abc\x07");
+    }
+}
@@ -0,0 +1,134 @@
+//! `--todo`: dictations starting with "To-do:" are added to a todo.txt file or
+//! to Taskwarrior instead of being typed, for quick capture without switching
+//! apps.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where to-dos go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TodoTarget {
+    /// A todo.txt file, one task per line
+    File(PathBuf),
+    /// Taskwarrior, through `task add`
+    Taskwarrior,
+}
+
+impl FromStr for TodoTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("expected a todo.txt path or `taskwarrior`".to_string()),
+            "task" | "taskwarrior" => Ok(TodoTarget::Taskwarrior),
+            path => Ok(TodoTarget::File(PathBuf::from(path))),
+        }
+    }
+}
+
+impl TodoTarget {
+    pub async fn add(&self, task: &str) -> Result<()> {
+        match self {
+            TodoTarget::File(path) => {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open {:?}", path))?;
+                // todo.txt puts the creation date in front of the task
+                writeln!(file, "{} {}", today(), task)
+                    .with_context(|| format!("Failed to write to {:?}", path))?;
+            }
+            TodoTarget::Taskwarrior => {
+                // `--` keeps words like "due:" in the description
+                let output = tokio::process::Command::new("task")
+                    .args(["add", "--", task])
+                    .output()
+                    .await
+                    .context("Failed to run `task` (is Taskwarrior installed?)")?;
+                if !output.status.success() {
+                    anyhow::bail!(
+                        "task add failed: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The task in a transcript like "To-do: buy milk.", or `None` when it isn't
+/// one. Engines write the prefix as "Todo", "To-do" or "To do".
+pub fn parse(transcript: &str) -> Option<String> {
+    let text = transcript.trim();
+    let rest = ["todo", "to-do", "to do"]
+        .iter()
+        .find_map(|prefix| strip_prefix_ignore_case(text, prefix))?;
+    let task = rest
+        .strip_prefix([':', ','])?
+        .trim()
+        .trim_end_matches('.')
+        .trim_end();
+    if task.is_empty() {
+        return None;
+    }
+    Some(task.to_string())
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &text[prefix.len()..])
+}
+
+/// Today's date in UTC as YYYY-MM-DD.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Convert days since 1970-01-01 to a calendar date (Howard Hinnant's algorithm).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_todo() {
+        assert_eq!(parse("To-do: buy milk."), Some("buy milk".to_string()));
+        assert_eq!(parse("todo, call Anna"), Some("call Anna".to_string()));
+        assert_eq!(
+            parse("To do: renew passport"),
+            Some("renew passport".to_string())
+        );
+        assert_eq!(parse("To do that, we need more time."), None);
+        assert_eq!(parse("Todo:"), None);
+        assert_eq!(parse("Buy milk."), None);
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+    }
+}
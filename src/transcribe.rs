@@ -0,0 +1,624 @@
+use crate::audio;
+use crate::dictionary::Dictionary;
+use crate::engine::{Engine, EngineKind};
+use crate::model;
+use crate::output;
+use crate::post_process::PostProcessor;
+use crate::report::{self, progress};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+const SAMPLE_RATE: usize = 16000;
+/// Long audio is transcribed in chunks of at most this many seconds
+const CHUNK_SECS: usize = 30;
+/// How far back from the chunk limit to look for a pause to cut at
+const CUT_SEARCH_SECS: usize = 5;
+/// Granularity of the pause search (100 ms)
+const FRAME_LEN: usize = SAMPLE_RATE / 10;
+/// Extensions that are downloaded directly; other URLs go through yt-dlp if it's installed
+const AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "m4a", "aac", "mp4", "ogg", "oga", "flac", "wav"];
+/// With `--chapters`, a pause at least this long starts a new chapter...
+const CHAPTER_PAUSE_SECS: f64 = 3.0;
+/// ...once the current chapter is at least this long
+const MIN_CHAPTER_SECS: f64 = 120.0;
+/// Within a chapter, paragraphs break at shorter pauses
+const PARAGRAPH_PAUSE_SECS: f64 = 1.0;
+const MIN_PARAGRAPH_SECS: f64 = 20.0;
+/// Prefer audio-only formats symphonia can decode (AAC, MP3) over Opus
+const YT_DLP_FORMAT: &str = "bestaudio[ext=m4a]/bestaudio[ext=mp3]/bestaudio/best";
+
+#[derive(Serialize)]
+struct TranscribeReport {
+    transcripts: Vec<Transcript>,
+}
+
+#[derive(Serialize)]
+struct Transcript {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    path: PathBuf,
+    duration_secs: Option<f64>,
+    text: Option<String>,
+    segments: Vec<Segment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chapters: Option<Vec<Chapter>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// A stretch of the transcript with its position in the audio.
+#[derive(Debug, Serialize, PartialEq)]
+struct Segment {
+    start_secs: f64,
+    end_secs: f64,
+    text: String,
+}
+
+/// A stretch of the transcript between long pauses, split into paragraphs.
+#[derive(Debug, Serialize, PartialEq)]
+struct Chapter {
+    start_secs: f64,
+    end_secs: f64,
+    paragraphs: Vec<String>,
+}
+
+/// Files `--write` can produce next to each input.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TranscriptFormat {
+    /// The transcript as printed: plain text, or markdown with --chapters
+    Txt,
+    /// The transcript as reported by --json
+    Json,
+    /// SubRip subtitles
+    Srt,
+}
+
+impl TranscriptFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            TranscriptFormat::Txt => "txt",
+            TranscriptFormat::Json => "json",
+            TranscriptFormat::Srt => "srt",
+        }
+    }
+}
+
+pub struct TranscribeOptions {
+    /// Also write subtitles to this file (single input only)
+    pub srt: Option<PathBuf>,
+    /// Print the transcript as markdown chapters
+    pub chapters: bool,
+    /// Keep downloads in the cache
+    pub keep: bool,
+    /// Write these files next to each input instead of printing the transcript
+    pub write: Vec<TranscriptFormat>,
+    /// Context for engines that support decode-time biasing
+    pub initial_prompt: Option<String>,
+    /// Pronunciation respellings applied before the dictionary
+    pub hints: Option<Dictionary>,
+    /// Replacement rules applied before post-processing
+    pub dictionary: Option<Dictionary>,
+    /// Clean up each paragraph through Ollama
+    pub post_processor: Option<PostProcessor>,
+    /// Have the post-processor write in this language
+    pub output_language: Option<String>,
+}
+
+/// Transcribe local audio `files` and the audio at `url`, loading the engine
+/// once for all of them.
+pub async fn run(
+    kind: EngineKind,
+    model_path: Option<PathBuf>,
+    files: Vec<PathBuf>,
+    url: Option<String>,
+    options: TranscribeOptions,
+    verbose_load: bool,
+) -> Result<()> {
+    if options.srt.is_some() && files.len() + usize::from(url.is_some()) > 1 {
+        anyhow::bail!("--srt takes a single input; use --write srt for several");
+    }
+    // Not worth loading the model for; among others, a missing file is
+    // reported like one that doesn't decode
+    if url.is_none() && files.iter().all(|file| !file.is_file()) {
+        if let Some(missing) = files.first() {
+            anyhow::bail!("No such file: {:?}", missing);
+        }
+    }
+
+    let mut inputs = Vec::new();
+    if let Some(url) = url {
+        let path = download(&url).await?;
+        inputs.push((Some(url), path));
+    }
+    inputs.extend(files.into_iter().map(|path| (None, path)));
+
+    let model_path = model::ensure_model(kind, model_path).await?;
+    let mut engine = model::load_engine(kind, &model_path, verbose_load)?;
+    engine.set_initial_prompt(options.initial_prompt.clone());
+
+    let mut report = TranscribeReport {
+        transcripts: Vec::new(),
+    };
+    let several = inputs.len() > 1;
+    for (url, path) in inputs {
+        let mut transcript = Transcript {
+            url,
+            path,
+            duration_secs: None,
+            text: None,
+            segments: Vec::new(),
+            chapters: None,
+            error: None,
+        };
+        match transcribe_file(&mut engine, &mut transcript, &options).await {
+            Ok(()) => {
+                if transcript.url.is_some() && !options.keep {
+                    std::fs::remove_file(&transcript.path).ok();
+                }
+                if !report::json() && options.write.is_empty() {
+                    if several {
+                        println!("\n== {:?} ==", transcript.path);
+                    }
+                    print!("{}", transcript.rendered());
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to transcribe {:?}: {:#}", transcript.path, e);
+                transcript.error = Some(format!("{:#}", e));
+            }
+        }
+        report.transcripts.push(transcript);
+    }
+    engine.unload_model();
+
+    if report::json() {
+        report::emit(&report)?;
+    }
+    let failed: Vec<&Transcript> = report
+        .transcripts
+        .iter()
+        .filter(|t| t.error.is_some())
+        .collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    // The errors were logged as they happened, possibly hundreds of files ago
+    if several && !report::json() {
+        eprintln!(
+            "\n{} of {} files failed:",
+            failed.len(),
+            report.transcripts.len()
+        );
+        for transcript in &failed {
+            eprintln!(
+                "  {:?}: {}",
+                transcript.path,
+                transcript.error.as_deref().unwrap_or_default()
+            );
+        }
+    }
+    anyhow::bail!("{} file(s) could not be transcribed", failed.len())
+}
+
+/// Decode, transcribe and optionally post-process one input, filling in
+/// `transcript` and writing the files asked for.
+async fn transcribe_file(
+    engine: &mut Engine,
+    transcript: &mut Transcript,
+    options: &TranscribeOptions,
+) -> Result<()> {
+    progress!("Decoding {:?}...", transcript.path);
+    let decode_path = transcript.path.clone();
+    let samples = tokio::task::spawn_blocking(move || audio::decode_audio(&decode_path))
+        .await
+        .context("Decoding task failed")??;
+    let duration_secs = samples.len() as f64 / SAMPLE_RATE as f64;
+
+    progress!("Transcribing {:.1}s of audio...", duration_secs);
+    let (mut text, mut segments) = transcribe_long(engine, samples)?;
+    for dictionary in [&options.hints, &options.dictionary].into_iter().flatten() {
+        text = dictionary.apply(&text);
+        for segment in &mut segments {
+            segment.text = dictionary.apply(&segment.text);
+        }
+    }
+    let mut chapters = to_chapters(&segments);
+    if let Some(post_processor) = &options.post_processor {
+        // Paragraph by paragraph, so the model never sees more than a few minutes of speech
+        progress!("Post-processing...");
+        for paragraph in chapters.iter_mut().flat_map(|c| c.paragraphs.iter_mut()) {
+            match post_processor
+                .process(paragraph, None, options.output_language.as_deref())
+                .await
+            {
+                Ok(processed) => *paragraph = processed.trim().to_string(),
+                Err(e) => log::error!("Post-processing failed: {}", e),
+            }
+        }
+        let paragraphs: Vec<&str> = chapters
+            .iter()
+            .flat_map(|c| c.paragraphs.iter().map(String::as_str))
+            .collect();
+        text = paragraphs.join(" ");
+    }
+
+    if let Some(srt_path) = &options.srt {
+        std::fs::write(srt_path, to_srt(&segments))
+            .with_context(|| format!("Failed to write {:?}", srt_path))?;
+        progress!("Subtitles written to {:?}", srt_path);
+    }
+    transcript.duration_secs = Some(duration_secs);
+    transcript.text = Some(text);
+    transcript.segments = segments;
+    transcript.chapters = options.chapters.then_some(chapters);
+
+    for &format in &options.write {
+        let out_path = transcript.output_path(format);
+        let contents = match format {
+            TranscriptFormat::Txt => transcript.rendered(),
+            TranscriptFormat::Json => serde_json::to_string_pretty(&*transcript)? + "\n",
+            TranscriptFormat::Srt => to_srt(&transcript.segments),
+        };
+        std::fs::write(&out_path, contents)
+            .with_context(|| format!("Failed to write {:?}", out_path))?;
+        progress!("Written to {:?}", out_path);
+    }
+    Ok(())
+}
+
+impl Transcript {
+    /// The transcript as printed: markdown with chapters, otherwise plain text.
+    fn rendered(&self) -> String {
+        match &self.chapters {
+            Some(chapters) => to_markdown(chapters),
+            None => format!("{}\n", self.text.as_deref().unwrap_or_default()),
+        }
+    }
+
+    /// Where `--write` puts `format`: next to a local file, or in the current
+    /// directory for a download, named after the last part of its URL.
+    fn output_path(&self, format: TranscriptFormat) -> PathBuf {
+        let path = match &self.url {
+            Some(url) => PathBuf::from(url_file_stem(url)),
+            None => self.path.clone(),
+        };
+        path.with_extension(format.extension())
+    }
+}
+
+/// Transcribe audio of any length chunk by chunk. Returns the joined text and
+/// its timed segments; engines without timings get one segment per chunk.
+fn transcribe_long(engine: &mut Engine, samples: Vec<f32>) -> Result<(String, Vec<Segment>)> {
+    let chunks = chunk_bounds(&samples);
+    let secs = |pos: usize| pos as f64 / SAMPLE_RATE as f64;
+    let mut texts = Vec::with_capacity(chunks.len());
+    let mut segments = Vec::new();
+    for (i, range) in chunks.iter().enumerate() {
+        if chunks.len() > 1 {
+            log::debug!("Transcribing chunk {}/{}", i + 1, chunks.len());
+        }
+        let transcription = engine.transcribe_samples(samples[range.clone()].to_vec())?;
+        let text = transcription.text.trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+        let offset = secs(range.start);
+        match transcription.segments.filter(|s| !s.is_empty()) {
+            Some(timed) => segments.extend(timed.into_iter().filter_map(|segment| {
+                let text = segment.text.trim();
+                (!text.is_empty()).then(|| Segment {
+                    start_secs: offset + segment.start as f64,
+                    end_secs: offset + segment.end as f64,
+                    text: text.to_string(),
+                })
+            })),
+            None => segments.push(Segment {
+                start_secs: offset,
+                end_secs: secs(range.end),
+                text: text.clone(),
+            }),
+        }
+        texts.push(text);
+    }
+    Ok((texts.join(" "), segments))
+}
+
+/// Group segments into chapters at long pauses, and chapters into paragraphs at
+/// shorter ones. Engines that don't report timings only give pauses between chunks.
+fn to_chapters(segments: &[Segment]) -> Vec<Chapter> {
+    split_at_pauses(segments, CHAPTER_PAUSE_SECS, MIN_CHAPTER_SECS)
+        .into_iter()
+        .map(|chapter| Chapter {
+            start_secs: chapter[0].start_secs,
+            end_secs: chapter[chapter.len() - 1].end_secs,
+            paragraphs: split_at_pauses(chapter, PARAGRAPH_PAUSE_SECS, MIN_PARAGRAPH_SECS)
+                .into_iter()
+                .map(|paragraph| {
+                    let texts: Vec<&str> = paragraph.iter().map(|s| s.text.as_str()).collect();
+                    texts.join(" ")
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Split `segments` before every pause of at least `min_gap` seconds, as long
+/// as the group so far spans at least `min_len` seconds.
+fn split_at_pauses(segments: &[Segment], min_gap: f64, min_len: f64) -> Vec<&[Segment]> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for i in 1..segments.len() {
+        let gap = segments[i].start_secs - segments[i - 1].end_secs;
+        let len = segments[i - 1].end_secs - segments[start].start_secs;
+        if gap >= min_gap && len >= min_len {
+            groups.push(&segments[start..i]);
+            start = i;
+        }
+    }
+    if start < segments.len() {
+        groups.push(&segments[start..]);
+    }
+    groups
+}
+
+/// Format chapters as markdown with a timestamp heading each.
+fn to_markdown(chapters: &[Chapter]) -> String {
+    let mut markdown = String::new();
+    for chapter in chapters {
+        let _ = writeln!(
+            markdown,
+            "## {} – {}\n",
+            clock(chapter.start_secs),
+            clock(chapter.end_secs)
+        );
+        for paragraph in &chapter.paragraphs {
+            let _ = writeln!(markdown, "{}\n", paragraph);
+        }
+    }
+    markdown
+}
+
+/// `secs` as HH:MM:SS.
+fn clock(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Format segments as SubRip subtitles.
+fn to_srt(segments: &[Segment]) -> String {
+    let timestamp = |secs: f64| {
+        let ms = (secs.max(0.0) * 1000.0).round() as u64;
+        format!(
+            "{:02}:{:02}:{:02},{:03}",
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            ms % 1000
+        )
+    };
+    let mut srt = String::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let _ = write!(
+            srt,
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            timestamp(segment.start_secs),
+            timestamp(segment.end_secs),
+            segment.text
+        );
+    }
+    srt
+}
+
+/// Split `samples` into chunks of at most `CHUNK_SECS`, each cut at the quietest
+/// moment of its last `CUT_SEARCH_SECS` so words aren't split in half.
+fn chunk_bounds(samples: &[f32]) -> Vec<Range<usize>> {
+    let max_len = CHUNK_SECS * SAMPLE_RATE;
+    let search_len = CUT_SEARCH_SECS * SAMPLE_RATE;
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while samples.len() - start > max_len {
+        let search_start = start + max_len - search_len;
+        let quietest = samples[search_start..start + max_len]
+            .chunks_exact(FRAME_LEN)
+            .enumerate()
+            .min_by(|(_, a), (_, b)| energy(a).total_cmp(&energy(b)))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        // Cut in the middle of the quietest frame
+        let end = search_start + quietest * FRAME_LEN + FRAME_LEN / 2;
+        chunks.push(start..end);
+        start = end;
+    }
+    chunks.push(start..samples.len());
+    chunks
+}
+
+fn energy(frame: &[f32]) -> f32 {
+    frame.iter().map(|s| s * s).sum()
+}
+
+/// Fetch `url` into the download cache, resuming a partial download left behind
+/// by an earlier attempt. Pages that aren't audio files, such as videos, are
+/// handed to yt-dlp when it is installed.
+async fn download(url: &str) -> Result<PathBuf> {
+    let path = download_path(url);
+    let is_audio_file = path
+        .extension()
+        .is_some_and(|ext| AUDIO_EXTENSIONS.iter().any(|audio| ext == *audio));
+    if !is_audio_file && output::in_path("yt-dlp") {
+        return fetch_with_yt_dlp(url, &path).await;
+    }
+    if path.exists() {
+        progress!("Using previously downloaded {:?}", path);
+        return Ok(path);
+    }
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .context("Failed to create download directory")?;
+    }
+
+    progress!("Downloading {}...", url);
+    let partial = path.with_extension("part");
+    model::download_file(url, &partial).await?;
+    tokio::fs::rename(&partial, &path)
+        .await
+        .context("Failed to move download into place")?;
+    Ok(path)
+}
+
+/// Let yt-dlp pick and download the audio track of `url`, next to where a direct
+/// download would go. yt-dlp itself skips finished and resumes partial downloads.
+async fn fetch_with_yt_dlp(url: &str, path: &Path) -> Result<PathBuf> {
+    progress!("Fetching audio with yt-dlp...");
+    let template = path.with_extension("%(ext)s");
+    let output = Command::new("yt-dlp")
+        .args(["--no-playlist", "--no-simulate", "-f", YT_DLP_FORMAT])
+        .args(["--print", "after_move:filepath", "-o"])
+        .arg(&template)
+        .arg(url)
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("Failed to run yt-dlp")?;
+    if !output.status.success() {
+        anyhow::bail!("yt-dlp failed to fetch {}", url);
+    }
+    let printed = String::from_utf8_lossy(&output.stdout);
+    let file = printed
+        .lines()
+        .last()
+        .filter(|line| !line.is_empty())
+        .context("yt-dlp didn't report a file")?;
+    Ok(PathBuf::from(file))
+}
+
+/// Downloads are named after a hash of the URL, keeping the file extension as a
+/// hint for the decoder.
+fn download_path(url: &str) -> PathBuf {
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let extension = url_file_name(url)
+        .rsplit_once('.')
+        .map(|(_, ext)| ext)
+        .filter(|ext| {
+            !ext.is_empty() && ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+        .unwrap_or("audio");
+    model::cache_dir()
+        .join("downloads")
+        .join(format!("{}.{}", &hash[..16], extension))
+}
+
+/// The last path segment of `url`, without query or fragment.
+fn url_file_name(url: &str) -> &str {
+    url.split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .unwrap_or_default()
+}
+
+/// `url`'s file name without its extension, for naming `--write` outputs.
+fn url_file_stem(url: &str) -> String {
+    Path::new(url_file_name(url))
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or("transcript")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_audio_is_one_chunk() {
+        let samples = vec![0.1; 10 * SAMPLE_RATE];
+        assert_eq!(chunk_bounds(&samples), vec![0..samples.len()]);
+    }
+
+    #[test]
+    fn test_chunks_cut_at_pause() {
+        let mut samples = vec![0.5; 70 * SAMPLE_RATE];
+        // A pause at 27s, inside the search window of the first chunk
+        let pause = 27 * SAMPLE_RATE;
+        samples[pause..pause + FRAME_LEN].fill(0.0);
+
+        let chunks = chunk_bounds(&samples);
+        assert_eq!(chunks[0], 0..pause + FRAME_LEN / 2);
+        assert!(chunks.iter().all(|c| c.len() <= CHUNK_SECS * SAMPLE_RATE));
+        assert_eq!(chunks.last().unwrap().end, samples.len());
+    }
+
+    #[test]
+    fn test_srt() {
+        let segments = [
+            Segment {
+                start_secs: 0.0,
+                end_secs: 2.5,
+                text: "Hello there.".into(),
+            },
+            Segment {
+                start_secs: 3661.25,
+                end_secs: 3663.0,
+                text: "An hour later.".into(),
+            },
+        ];
+        assert_eq!(
+            to_srt(&segments),
+            "1\n00:00:00,000 --> 00:00:02,500\nHello there.\n\n\
+             2\n01:01:01,250 --> 01:01:03,000\nAn hour later.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_chapters_at_long_pauses() {
+        let segment = |start_secs: f64, end_secs: f64, text: &str| Segment {
+            start_secs,
+            end_secs,
+            text: text.into(),
+        };
+        let segments = [
+            segment(0.0, 15.0, "Welcome."),
+            segment(16.5, 130.0, "Intro."),
+            // Too short a pause for a chapter, but a paragraph break
+            segment(132.0, 140.0, "Still intro."),
+            segment(145.0, 200.0, "Main topic."),
+            // Long pause, but the chapter has just started
+            segment(204.0, 210.0, "More."),
+        ];
+
+        let chapters = to_chapters(&segments);
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].paragraphs, ["Welcome. Intro.", "Still intro."]);
+        assert_eq!(chapters[1].start_secs, 145.0);
+        assert_eq!(chapters[1].paragraphs, ["Main topic.", "More."]);
+        assert!(to_markdown(&chapters).starts_with("## 00:00:00 – 00:02:20\n\nWelcome. Intro.\n\n"));
+    }
+
+    #[test]
+    fn test_download_path_keeps_extension() {
+        let path = download_path("https://example.com/feed/episode-12.mp3?token=abc");
+        assert_eq!(path.extension().unwrap(), "mp3");
+        let path = download_path("https://example.com/voicemail/1234");
+        assert_eq!(path.extension().unwrap(), "audio");
+    }
+
+    #[test]
+    fn test_url_file_stem() {
+        assert_eq!(
+            url_file_stem("https://example.com/feed/episode-12.mp3?t=1"),
+            "episode-12"
+        );
+        assert_eq!(url_file_stem("https://example.com/"), "transcript");
+    }
+}
@@ -0,0 +1,179 @@
+//! Typing through a virtual keyboard created with uinput, instead of spawning
+//! wtype or xdotool for every transcript. Works in any Wayland compositor, on
+//! X11 and on virtual consoles, but only knows the US keyboard layout.
+
+use crate::input::{self, Key};
+use crate::output::NewlineMode;
+use anyhow::{Context, Result};
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{AttributeSet, EventType, InputEvent, Key as EvKey};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Name of the virtual device, so the hotkey listener can ignore it.
+pub const DEVICE_NAME: &str = "parakeet-writer virtual keyboard";
+
+/// How long the desktop gets to pick up the new device before it is used.
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// Characters typed by their unshifted and shifted keys on a US layout.
+const SYMBOLS: [(char, char, EvKey); 11] = [
+    ('-', '_', EvKey::KEY_MINUS),
+    ('=', '+', EvKey::KEY_EQUAL),
+    ('[', '{', EvKey::KEY_LEFTBRACE),
+    (']', '}', EvKey::KEY_RIGHTBRACE),
+    ('\\', '|', EvKey::KEY_BACKSLASH),
+    (';', ':', EvKey::KEY_SEMICOLON),
+    ('\'', '"', EvKey::KEY_APOSTROPHE),
+    (',', '<', EvKey::KEY_COMMA),
+    ('.', '>', EvKey::KEY_DOT),
+    ('/', '?', EvKey::KEY_SLASH),
+    ('`', '~', EvKey::KEY_GRAVE),
+];
+
+/// Shifted digit row, starting at 1.
+const SHIFTED_DIGITS: &str = "!@#$%^&*()";
+
+struct Keyboard {
+    device: VirtualDevice,
+    /// Pause after each key press and release
+    delay: Duration,
+}
+
+static KEYBOARD: Mutex<Option<Keyboard>> = Mutex::new(None);
+
+/// Create the virtual keyboard. `delay` is the pause between key events; some
+/// applications drop keys that arrive too quickly.
+pub fn open(delay: Duration) -> Result<()> {
+    let mut keys = AttributeSet::<EvKey>::new();
+    for c in (' '..='~').filter(|c| !c.is_ascii_uppercase()) {
+        if let Some((key, _)) = key_for(c) {
+            keys.insert(key);
+        }
+    }
+    for key in [
+        EvKey::KEY_LEFTSHIFT,
+        EvKey::KEY_LEFTCTRL,
+        EvKey::KEY_ENTER,
+        EvKey::KEY_BACKSPACE,
+        EvKey::KEY_TAB,
+    ] {
+        keys.insert(key);
+    }
+
+    let device = VirtualDeviceBuilder::new()
+        .and_then(|builder| builder.name(DEVICE_NAME).with_keys(&keys))
+        .and_then(|builder| builder.build())
+        .context("Failed to create a virtual keyboard (is /dev/uinput writable?)")?;
+    std::thread::sleep(SETTLE_TIME);
+    *KEYBOARD.lock().unwrap() = Some(Keyboard { device, delay });
+    Ok(())
+}
+
+/// Whether every character of `text` can be typed on the virtual keyboard.
+pub fn can_type(text: &str) -> bool {
+    text.chars().all(|c| c == '\n' || key_for(c).is_some())
+}
+
+/// Type `text`, which must pass [`can_type`].
+pub fn type_text(text: &str, newline: NewlineMode) -> Result<()> {
+    with_keyboard(|keyboard| {
+        for c in text.chars() {
+            let (key, shift) = match c {
+                '\n' => (EvKey::KEY_ENTER, matches!(newline, NewlineMode::ShiftEnter)),
+                c => key_for(c).with_context(|| format!("Can't type {:?}", c))?,
+            };
+            let modifiers: &[EvKey] = if shift { &[EvKey::KEY_LEFTSHIFT] } else { &[] };
+            keyboard.chord(modifiers, key)?;
+        }
+        Ok(())
+    })
+}
+
+pub fn press_backspace(count: usize) -> Result<()> {
+    with_keyboard(|keyboard| {
+        for _ in 0..count {
+            keyboard.chord(&[], EvKey::KEY_BACKSPACE)?;
+        }
+        Ok(())
+    })
+}
+
+pub fn press_enter() -> Result<()> {
+    with_keyboard(|keyboard| keyboard.chord(&[], EvKey::KEY_ENTER))
+}
+
+pub fn paste() -> Result<()> {
+    with_keyboard(|keyboard| keyboard.chord(&[EvKey::KEY_LEFTCTRL], EvKey::KEY_V))
+}
+
+fn with_keyboard(f: impl FnOnce(&mut Keyboard) -> Result<()>) -> Result<()> {
+    let mut keyboard = KEYBOARD.lock().unwrap();
+    let keyboard = keyboard
+        .as_mut()
+        .context("The virtual keyboard hasn't been created")?;
+    f(keyboard)
+}
+
+impl Keyboard {
+    /// Press `key` while holding `modifiers`.
+    fn chord(&mut self, modifiers: &[EvKey], key: EvKey) -> Result<()> {
+        for &modifier in modifiers {
+            self.send(modifier, 1)?;
+        }
+        self.send(key, 1)?;
+        self.send(key, 0)?;
+        for &modifier in modifiers.iter().rev() {
+            self.send(modifier, 0)?;
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, key: EvKey, value: i32) -> Result<()> {
+        self.device
+            .emit(&[InputEvent::new(EventType::KEY, key.code(), value)])
+            .context("Failed to send key event")?;
+        std::thread::sleep(self.delay);
+        Ok(())
+    }
+}
+
+/// The key for `c` on a US layout and whether it needs Shift.
+fn key_for(c: char) -> Option<(EvKey, bool)> {
+    match c {
+        'a'..='z' => Some((
+            input::to_evdev_key(Key::Letter(c.to_ascii_uppercase())),
+            false,
+        )),
+        'A'..='Z' => Some((input::to_evdev_key(Key::Letter(c)), true)),
+        '0'..='9' => Some((input::to_evdev_key(Key::Digit(c as u8 - b'0')), false)),
+        ' ' => Some((EvKey::KEY_SPACE, false)),
+        '\t' => Some((EvKey::KEY_TAB, false)),
+        c => {
+            if let Some(i) = SHIFTED_DIGITS.find(c) {
+                let digit = (i as u8 + 1) % 10;
+                return Some((input::to_evdev_key(Key::Digit(digit)), true));
+            }
+            SYMBOLS.iter().find_map(|&(plain, shifted, key)| {
+                (c == plain || c == shifted).then_some((key, c == shifted))
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_for() {
+        assert_eq!(key_for('q'), Some((EvKey::KEY_Q, false)));
+        assert_eq!(key_for('Q'), Some((EvKey::KEY_Q, true)));
+        assert_eq!(key_for('0'), Some((EvKey::KEY_0, false)));
+        assert_eq!(key_for(')'), Some((EvKey::KEY_0, true)));
+        assert_eq!(key_for('!'), Some((EvKey::KEY_1, true)));
+        assert_eq!(key_for('?'), Some((EvKey::KEY_SLASH, true)));
+        assert!(can_type("Hello, world!\nIt's 5 o'clock."));
+        assert!(!can_type("Grüße"));
+    }
+}
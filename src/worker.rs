@@ -0,0 +1,363 @@
+//! `--isolate-engine`: the engine runs in a child process (the hidden
+//! `engine-worker` subcommand), so a crash in onnxruntime or whisper.cpp
+//! costs one transcription rather than the hotkey listener, and the child is
+//! started again for the next one.
+//!
+//! Requests go to the child's stdin as a JSON line followed by the samples as
+//! little-endian f32s; replies come back from its stdout as JSON lines. The
+//! child runs with `--json`, so its progress messages go to stderr.
+
+use crate::engine::{Engine, EngineKind};
+use crate::model;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use transcribe_rs::{TranscriptionResult, TranscriptionSegment};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    initial_prompt: Option<String>,
+    /// Number of samples following the line
+    samples: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Reply {
+    /// The model is loaded
+    Ready,
+    Transcript {
+        text: String,
+        /// Start and end in seconds, and text
+        segments: Option<Vec<(f32, f32, String)>>,
+    },
+    Error(String),
+}
+
+/// The engine in a child process.
+pub struct Worker {
+    kind: EngineKind,
+    model_path: PathBuf,
+    initial_prompt: Option<String>,
+    /// `None` while the model is unloaded, or the child couldn't be started again
+    process: Option<Process>,
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    /// Whether the child has reported its model loaded
+    ready: bool,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Process {
+    fn spawn(kind: EngineKind, model_path: &Path, verbose_load: bool) -> Result<Self> {
+        let exe = std::env::current_exe().context("Failed to find the executable")?;
+        let mut command = Command::new(exe);
+        command
+            .args(engine_args(kind))
+            .arg("--model")
+            .arg(model_path)
+            .arg("--json");
+        if verbose_load {
+            command.arg("--verbose-load");
+        }
+        if log::log_enabled!(log::Level::Debug) {
+            command.arg("--verbose");
+        }
+        let mut child = command
+            .arg("engine-worker")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Failed to start the engine worker")?;
+        log::debug!("Started engine worker {}", child.id());
+        let stdin = child.stdin.take().context("No stdin")?;
+        let stdout = BufReader::new(child.stdout.take().context("No stdout")?);
+        Ok(Process {
+            child,
+            stdin,
+            stdout,
+            ready: false,
+        })
+    }
+
+    /// Wait for the child to report its model loaded.
+    fn wait_ready(&mut self) -> Result<()> {
+        if self.ready {
+            return Ok(());
+        }
+        match read_reply(&mut self.stdout)? {
+            Reply::Ready => {
+                self.ready = true;
+                Ok(())
+            }
+            Reply::Error(e) => Err(anyhow::anyhow!(e)),
+            reply => anyhow::bail!("Unexpected reply from the engine worker: {:?}", reply),
+        }
+    }
+
+    /// Transcribe `samples`, or the engine's error. An `Err` means the worker
+    /// itself failed, e.g. it crashed.
+    fn transcribe(
+        &mut self,
+        samples: &[f32],
+        initial_prompt: Option<String>,
+    ) -> Result<Result<TranscriptionResult, String>> {
+        self.wait_ready()?;
+        let request = Request {
+            initial_prompt,
+            samples: samples.len(),
+        };
+        write_request(&mut self.stdin, &request, samples)?;
+        match read_reply(&mut self.stdout)? {
+            Reply::Transcript { text, segments } => Ok(Ok(TranscriptionResult {
+                text,
+                segments: segments.map(|segments| {
+                    segments
+                        .into_iter()
+                        .map(|(start, end, text)| TranscriptionSegment { start, end, text })
+                        .collect()
+                }),
+            })),
+            Reply::Error(e) => Ok(Err(e)),
+            Reply::Ready => anyhow::bail!("Unexpected reply from the engine worker"),
+        }
+    }
+}
+
+impl Worker {
+    /// Start the engine worker and wait for it to load the model.
+    pub fn start(kind: EngineKind, model_path: &Path, verbose_load: bool) -> Result<Self> {
+        let mut process = Process::spawn(kind, model_path, verbose_load)?;
+        process
+            .wait_ready()
+            .context("The engine worker failed to load the model")?;
+        Ok(Worker {
+            kind,
+            model_path: model_path.to_path_buf(),
+            initial_prompt: None,
+            process: Some(process),
+        })
+    }
+
+    pub fn transcribe_samples(&mut self, samples: Vec<f32>) -> Result<TranscriptionResult> {
+        let mut process = match self.process.take() {
+            Some(process) => process,
+            // Unloaded, or couldn't be started again after a crash
+            None => Process::spawn(self.kind, &self.model_path, false)?,
+        };
+        match process.transcribe(&samples, self.initial_prompt.clone()) {
+            Ok(result) => {
+                self.process = Some(process);
+                result.map_err(|e| anyhow::anyhow!(e))
+            }
+            Err(e) => {
+                drop(process);
+                log::error!("Engine worker failed, starting it again");
+                // Loads the model while the error is reported
+                self.process = Process::spawn(self.kind, &self.model_path, false)
+                    .map_err(|e| log::error!("{:#}", e))
+                    .ok();
+                Err(e.context("The engine worker crashed"))
+            }
+        }
+    }
+
+    pub fn set_initial_prompt(&mut self, prompt: Option<String>) {
+        self.initial_prompt = prompt;
+    }
+
+    /// Stop the child, which frees all of its memory.
+    pub fn unload_model(&mut self) {
+        self.process = None;
+    }
+}
+
+/// Load the model in an engine worker rather than in this process.
+pub fn load_engine(kind: EngineKind, model_path: &Path, verbose_load: bool) -> Result<Engine> {
+    Ok(Engine::Isolated(Worker::start(
+        kind,
+        model_path,
+        verbose_load,
+    )?))
+}
+
+/// The global options that select `kind` on the command line.
+fn engine_args(kind: EngineKind) -> Vec<String> {
+    fn name(value: impl ValueEnum) -> String {
+        value
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+    let mut args = vec!["--engine".to_string()];
+    match kind {
+        EngineKind::Parakeet(variant) => {
+            args.extend(["parakeet".to_string(), "--model-variant".to_string()]);
+            args.push(name(variant));
+        }
+        EngineKind::Moonshine => args.push("moonshine".to_string()),
+        EngineKind::Whisper(size) => {
+            args.extend(["whisper".to_string(), "--model-size".to_string()]);
+            args.push(name(size));
+        }
+    }
+    args
+}
+
+/// `engine-worker`: load the model and transcribe requests from stdin until
+/// it is closed.
+pub fn serve(kind: EngineKind, model_path: Option<PathBuf>) -> Result<()> {
+    let model_path = model_path.context("The engine worker needs --model")?;
+    let mut stdin = BufReader::new(std::io::stdin().lock());
+    let mut stdout = std::io::stdout().lock();
+    let mut engine = match model::load_engine(kind, &model_path, false) {
+        Ok(engine) => engine,
+        Err(e) => {
+            write_reply(&mut stdout, &Reply::Error(format!("{:#}", e)))?;
+            return Err(e);
+        }
+    };
+    write_reply(&mut stdout, &Reply::Ready)?;
+
+    let mut initial_prompt = None;
+    while let Some((request, samples)) = read_request(&mut stdin)? {
+        // Only when it changes, as engines without prompts warn every time
+        if request.initial_prompt != initial_prompt {
+            initial_prompt = request.initial_prompt;
+            engine.set_initial_prompt(initial_prompt.clone());
+        }
+        let reply = match engine.transcribe_samples(samples) {
+            Ok(result) => Reply::Transcript {
+                text: result.text,
+                segments: result.segments.map(|segments| {
+                    segments
+                        .into_iter()
+                        .map(|segment| (segment.start, segment.end, segment.text))
+                        .collect()
+                }),
+            },
+            Err(e) => Reply::Error(format!("{:#}", e)),
+        };
+        write_reply(&mut stdout, &reply)?;
+    }
+    engine.unload_model();
+    Ok(())
+}
+
+fn write_request(writer: &mut impl Write, request: &Request, samples: &[f32]) -> Result<()> {
+    serde_json::to_writer(&mut *writer, request)?;
+    writer.write_all(b"\n")?;
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    writer.write_all(&bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// The next request and its samples, or `None` once the parent is gone.
+fn read_request(reader: &mut impl BufRead) -> Result<Option<(Request, Vec<f32>)>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let request: Request = serde_json::from_str(&line).context("Invalid request")?;
+    let mut bytes = vec![0; request.samples * 4];
+    reader.read_exact(&mut bytes)?;
+    let samples = bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    Ok(Some((request, samples)))
+}
+
+fn write_reply(writer: &mut impl Write, reply: &Reply) -> Result<()> {
+    serde_json::to_writer(&mut *writer, reply)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_reply(reader: &mut impl BufRead) -> Result<Reply> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        anyhow::bail!("The engine worker closed its output");
+    }
+    serde_json::from_str(&line).context("Invalid reply from the engine worker")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_protocol_round_trip() {
+        let mut pipe = Vec::new();
+        let request = Request {
+            initial_prompt: Some("Kubernetes".to_string()),
+            samples: 3,
+        };
+        write_request(&mut pipe, &request, &[0.5, -1.0, 0.25]).unwrap();
+        write_request(
+            &mut pipe,
+            &Request {
+                initial_prompt: None,
+                samples: 0,
+            },
+            &[],
+        )
+        .unwrap();
+        let mut reader = Cursor::new(pipe);
+        let (request, samples) = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!(request.initial_prompt.as_deref(), Some("Kubernetes"));
+        assert_eq!(samples, [0.5, -1.0, 0.25]);
+        let (_, samples) = read_request(&mut reader).unwrap().unwrap();
+        assert!(samples.is_empty());
+        assert!(read_request(&mut reader).unwrap().is_none());
+
+        let mut pipe = Vec::new();
+        let segments = Some(vec![(0.0, 1.5, "Hello\nworld.".to_string())]);
+        write_reply(&mut pipe, &Reply::Ready).unwrap();
+        write_reply(
+            &mut pipe,
+            &Reply::Transcript {
+                text: "Hello\nworld.".to_string(),
+                segments,
+            },
+        )
+        .unwrap();
+        let mut reader = Cursor::new(pipe);
+        assert!(matches!(read_reply(&mut reader).unwrap(), Reply::Ready));
+        let Reply::Transcript { text, segments } = read_reply(&mut reader).unwrap() else {
+            panic!("expected a transcript");
+        };
+        assert_eq!(text, "Hello\nworld.");
+        assert_eq!(segments.unwrap()[0].1, 1.5);
+        // A crashed worker closes its end
+        assert!(read_reply(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_engine_args() {
+        use crate::engine::{ParakeetVariant, WhisperSize};
+        assert_eq!(
+            engine_args(EngineKind::Parakeet(ParakeetVariant::Fp32)),
+            ["--engine", "parakeet", "--model-variant", "fp32"]
+        );
+        assert_eq!(
+            engine_args(EngineKind::Whisper(WhisperSize::LargeV3Turbo)),
+            ["--engine", "whisper", "--model-size", "large-v3-turbo"]
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! End-to-end tests of the whole pipeline, from hotkey to transcript: a uinput
+//! virtual keyboard holds the hotkey while audio is fed through `--input-fifo`,
+//! and the result is read back from the history and status files.
+//!
+//! They need write access to `/dev/uinput` and read access to `/dev/input`
+//! (see "Keyboard access" in the README), and download the model on first
+//! use, so they are ignored by default:
+//!
+//! ```bash
+//! cargo test --test e2e -- --ignored --test-threads 1
+//! ```
+//!
+//! Transcripts are collected with `--compose` and never output, so nothing is
+//! typed into the focused window. The virtual keyboard only has F13, which
+//! desktops don't bind by default.
+//!
+//! Fixtures are 16 kHz mono 16-bit WAVs in `tests/fixtures/` with the expected
+//! transcript next to them in a `.txt` file of the same name. Transcripts are
+//! compared ignoring case and punctuation.
+
+#![cfg(target_os = "linux")]
+
+use anyhow::{Context, Result};
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{AttributeSet, EventType, InputEvent, Key};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const HOTKEY: Key = Key::KEY_F13;
+const SAMPLE_RATE: u32 = 16000;
+/// How long the binary gets to load the model (or download it)
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(600);
+/// How long a recording gets to be transcribed
+const TRANSCRIBE_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long the desktop and the hotkey listener get to pick up a new device
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// A running parakeet-writer with a virtual keyboard and an audio pipe.
+struct Harness {
+    child: Child,
+    keyboard: VirtualDevice,
+    audio: File,
+    dir: TempDir,
+}
+
+impl Harness {
+    /// Start parakeet-writer with `args` on top of the harness's own and wait
+    /// until it is listening.
+    fn start(args: &[&str]) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let fifo = dir.path().join("audio.pcm");
+        nix::unistd::mkfifo(&fifo, nix::sys::stat::Mode::S_IRWXU)?;
+
+        // Created first, as the hotkey listener only looks for devices at startup
+        let mut keys = AttributeSet::<Key>::new();
+        keys.insert(HOTKEY);
+        let keyboard = VirtualDeviceBuilder::new()?
+            .name("parakeet-writer e2e keyboard")
+            .with_keys(&keys)?
+            .build()
+            .context("Failed to create a virtual keyboard (is /dev/uinput writable?)")?;
+        std::thread::sleep(SETTLE_TIME);
+
+        let status = dir.path().join("status.json");
+        let mut child = Command::new(env!("CARGO_BIN_EXE_parakeet-writer"))
+            .args(["--key", "F13", "--compose", "--history"])
+            .arg("--input-fifo")
+            .arg(&fifo)
+            .args(["--input-fifo-rate", &SAMPLE_RATE.to_string()])
+            .arg("--status-file")
+            .arg(&status)
+            .args(args)
+            // Keeps the history out of the real one, and messages in English
+            .env("XDG_DATA_HOME", dir.path())
+            .env("LC_ALL", "C")
+            .stdin(Stdio::null())
+            .spawn()
+            .context("Failed to start parakeet-writer")?;
+        wait_for_state(&mut child, &status, "idle", STARTUP_TIMEOUT)?;
+        // Only once parakeet-writer reads from the pipe, as opening blocks until then
+        let audio = File::options().write(true).open(&fifo)?;
+        Ok(Harness {
+            child,
+            keyboard,
+            audio,
+            dir,
+        })
+    }
+
+    fn press(&mut self) -> Result<()> {
+        self.send(1)
+    }
+
+    fn release(&mut self) -> Result<()> {
+        self.send(0)
+    }
+
+    fn send(&mut self, value: i32) -> Result<()> {
+        self.keyboard
+            .emit(&[InputEvent::new(EventType::KEY, HOTKEY.code(), value)])?;
+        Ok(())
+    }
+
+    /// Hold the hotkey while `samples` are played, and return what was
+    /// transcribed, if anything.
+    fn dictate(&mut self, samples: &[i16]) -> Result<Option<String>> {
+        let before = self.history()?.len();
+        self.press()?;
+        self.wait_for_state("recording", TRANSCRIBE_TIMEOUT)?;
+        self.play(samples)?;
+        self.release()?;
+        self.wait_for_state("idle", TRANSCRIBE_TIMEOUT)?;
+        Ok(self.history()?.into_iter().nth(before))
+    }
+
+    /// Write `samples` to the pipe at about real-time speed.
+    fn play(&mut self, samples: &[i16]) -> Result<()> {
+        const CHUNK: Duration = Duration::from_millis(100);
+        let chunk_len = (SAMPLE_RATE as u128 * CHUNK.as_millis() / 1000) as usize;
+        for chunk in samples.chunks(chunk_len) {
+            let bytes: Vec<u8> = chunk.iter().flat_map(|s| s.to_le_bytes()).collect();
+            self.audio.write_all(&bytes)?;
+            std::thread::sleep(CHUNK);
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> Option<String> {
+        read_state(&self.dir.path().join("status.json"))
+    }
+
+    fn wait_for_state(&mut self, state: &str, timeout: Duration) -> Result<()> {
+        let status = self.dir.path().join("status.json");
+        wait_for_state(&mut self.child, &status, state, timeout)
+    }
+
+    /// Raw transcripts in the history, oldest first.
+    fn history(&self) -> Result<Vec<String>> {
+        let path = self.dir.path().join("parakeet-writer/history.jsonl");
+        let Ok(history) = std::fs::read_to_string(path) else {
+            return Ok(Vec::new());
+        };
+        history
+            .lines()
+            .map(|line| {
+                let entry: serde_json::Value = serde_json::from_str(line)?;
+                Ok(entry["raw_text"].as_str().unwrap_or_default().to_string())
+            })
+            .collect()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The state in the `--status-file` at `path`.
+fn read_state(path: &Path) -> Option<String> {
+    let status = std::fs::read_to_string(path).ok()?;
+    let status: serde_json::Value = serde_json::from_str(&status).ok()?;
+    Some(status["state"].as_str()?.to_string())
+}
+
+fn wait_for_state(child: &mut Child, path: &Path, state: &str, timeout: Duration) -> Result<()> {
+    let start = Instant::now();
+    while read_state(path).as_deref() != Some(state) {
+        if let Some(status) = child.try_wait()? {
+            anyhow::bail!("parakeet-writer exited with {}", status);
+        }
+        if start.elapsed() > timeout {
+            anyhow::bail!(
+                "Timed out waiting for {:?}, state is {:?}",
+                state,
+                read_state(path)
+            );
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+fn read_fixture(path: &Path) -> Result<Vec<i16>> {
+    let reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    anyhow::ensure!(
+        spec.channels == 1 && spec.sample_rate == SAMPLE_RATE && spec.bits_per_sample == 16,
+        "{:?} isn't 16 kHz mono 16-bit",
+        path
+    );
+    Ok(reader.into_samples::<i16>().collect::<Result<_, _>>()?)
+}
+
+fn fixtures() -> Result<Vec<(PathBuf, String)>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut fixtures = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "wav") {
+            let expected = std::fs::read_to_string(path.with_extension("txt"))
+                .with_context(|| format!("No transcript for {:?}", path))?;
+            fixtures.push((path, expected));
+        }
+    }
+    fixtures.sort();
+    Ok(fixtures)
+}
+
+/// Lowercase words without punctuation, for comparing transcripts.
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+#[test]
+#[ignore = "needs /dev/uinput and the model"]
+fn silence_is_not_transcribed() {
+    let mut harness = Harness::start(&[]).unwrap();
+    let silence = vec![0; SAMPLE_RATE as usize * 2];
+    assert_eq!(harness.dictate(&silence).unwrap(), None);
+}
+
+#[test]
+#[ignore = "needs /dev/uinput and the model"]
+fn latch_toggles_recording() {
+    let mut harness = Harness::start(&["--latch"]).unwrap();
+    harness.press().unwrap();
+    harness.release().unwrap();
+    harness
+        .wait_for_state("recording", TRANSCRIBE_TIMEOUT)
+        .unwrap();
+    // Still recording after the release
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(harness.state().as_deref(), Some("recording"));
+    harness.press().unwrap();
+    harness.release().unwrap();
+    harness.wait_for_state("idle", TRANSCRIBE_TIMEOUT).unwrap();
+}
+
+#[test]
+#[ignore = "needs /dev/uinput, the model and fixtures"]
+fn fixtures_are_transcribed() {
+    let fixtures = fixtures().unwrap();
+    if fixtures.is_empty() {
+        eprintln!("No fixtures in tests/fixtures/, nothing to check");
+        return;
+    }
+    let mut harness = Harness::start(&[]).unwrap();
+    for (path, expected) in fixtures {
+        let samples = read_fixture(&path).unwrap();
+        let transcript = harness.dictate(&samples).unwrap().unwrap_or_default();
+        assert_eq!(words(&transcript), words(&expected), "{:?}", path);
+    }
+}